@@ -0,0 +1,131 @@
+//! A fixed workload for comparing builds of an engine, like the `bench` command of UCI engines.
+//!
+//! Running the same positions with the same seeds on two builds shows whether a change
+//! made the engine faster or slower, and, through the node count, whether it changed the search at all.
+//!
+//! ```
+//! use engine_trait::bench::{bench_positions, run_bench, BenchLimits};
+//! # use engine_trait::{async_trait, server_types::EngineInfo, Engine, InfallibleError, MoveSummary, NoStatus};
+//! # use shakmaty::{Chess, Move, Position};
+//! # /// Plays the first legal move, after "searching" each of them.
+//! # struct FirstMove;
+//! # #[async_trait]
+//! # impl Engine for FirstMove {
+//! #     type State = ();
+//! #     type StatusInfo = u64;
+//! #     type Error = InfallibleError;
+//! #     fn get_info() -> EngineInfo<Self> {
+//! #         EngineInfo::new("first-move", "Plays the first legal move", ())
+//! #     }
+//! #     async fn propose_move(&mut self, _: u64, _: &(), position: &Chess) -> Result<(Move, u64), InfallibleError> {
+//! #         let moves = position.legal_moves();
+//! #         Ok((moves[0].clone(), moves.len() as u64))
+//! #     }
+//! #     async fn observe_move(&mut self, _: u64, _: &mut (), _: &Move, _: &Chess) -> Result<(), InfallibleError> { Ok(()) }
+//! #     fn summarize(nodes: &u64) -> MoveSummary {
+//! #         MoveSummary { nodes: Some(*nodes), ..Default::default() }
+//! #     }
+//! # }
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let report = run_bench(&mut FirstMove, BenchLimits { max_positions: Some(2) }).await.unwrap();
+//! assert_eq!(report.positions, 2);
+//! // The start position has 20 legal moves, and the second bench position has 48.
+//! assert_eq!(report.nodes, Some(68));
+//! assert!(bench_positions().len() > 2);
+//! # });
+//! ```
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    epd::{read_epd, EpdPosition},
+    Engine,
+};
+
+/// The bench positions, as EPD: openings, middlegames and endgames of varying difficulty.
+pub const BENCH_EPD: &str = "\
+rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - id \"start\";
+r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - id \"kiwipete\";
+8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - id \"rook-endgame\";
+4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - id \"bench.04\";
+rq3rk1/ppp2ppp/1bnpb3/3N2B1/3NP3/7P/PPPQ1PP1/2KR3R w - - id \"bench.05\";
+r1bbk1nr/pp3p1p/2n5/1N4p1/2Np1B2/8/PPP2PPP/2KR1B1R w kq - id \"bench.06\";
+r1bq1r1k/b1p1npp1/p2p3p/1p6/3PP3/1B2NN2/PP3PPP/R2Q1RK1 w - - id \"bench.07\";
+3r1rk1/p5pp/bpp1pp2/8/q1PP1P2/b3P3/P2NQRPP/1R2B1K1 b - - id \"bench.08\";
+6k1/6p1/6Pp/ppp5/3pn2P/1P3K2/1PP2P2/3N4 b - - id \"bench.09\";
+1r3k2/4q3/2Pp3b/3Bp3/2Q2p2/1p1P2P1/1P2KP2/3N4 w - - id \"bench.10\";
+8/8/8/8/5kp1/P7/8/1K1N4 w - - id \"knight-endgame\";
+8/3k4/8/8/8/4B3/4KB2/2B5 w - - id \"bishops-endgame\";
+";
+
+/// The bench positions, read from [`BENCH_EPD`].
+pub fn bench_positions() -> Vec<EpdPosition> {
+    read_epd(BENCH_EPD).expect("the bench positions are valid")
+}
+
+/// Limits on how much of the bench to run.
+#[derive(Clone, Debug, Default)]
+pub struct BenchLimits {
+    /// Only run the first this many positions.
+    pub max_positions: Option<usize>,
+}
+
+/// How long the engine took on the bench, and how much it searched.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BenchReport {
+    /// How many positions were run.
+    pub positions: usize,
+
+    /// The total number of nodes searched, if the engine reported them for every position.
+    /// See [`MoveSummary::nodes`](crate::MoveSummary::nodes).
+    pub nodes: Option<u64>,
+
+    /// The wall-clock time of the whole run, in milliseconds.
+    #[serde(with = "crate::chess_serde::duration_ms_serde")]
+    pub elapsed: Duration,
+}
+
+impl BenchReport {
+    /// Nodes per second, if the engine reported nodes and the run took any measurable time.
+    pub fn nodes_per_second(&self) -> Option<f64> {
+        let seconds = self.elapsed.as_secs_f64();
+        self.nodes
+            .filter(|_| seconds > 0.0)
+            .map(|nodes| nodes as f64 / seconds)
+    }
+}
+
+/// Ask the engine for a move in every bench position, timing the whole run and adding up the nodes it reports.
+///
+/// As in [`run_epd_suite`](crate::epd::run_epd_suite), the engine starts from its initial state in every position,
+/// and position number `i` is given `i` as its random number, so that runs are reproducible.
+pub async fn run_bench<E: Engine>(
+    engine: &mut E,
+    limits: BenchLimits,
+) -> Result<BenchReport, E::Error> {
+    let initial_state = E::get_info().initial_state;
+    let positions = bench_positions();
+    let count = limits.max_positions.unwrap_or(positions.len());
+
+    let start = Instant::now();
+    let mut nodes = Some(0u64);
+    let mut run = 0;
+    for (i, bench_position) in positions.iter().take(count).enumerate() {
+        let (_, info) = engine
+            .propose_move(i as u64, &initial_state, &bench_position.position)
+            .await?;
+        nodes = nodes
+            .zip(E::summarize(&info).nodes)
+            .map(|(total, v)| total.saturating_add(v));
+        run += 1;
+    }
+
+    Ok(BenchReport {
+        positions: run,
+        nodes,
+        elapsed: start.elapsed(),
+    })
+}
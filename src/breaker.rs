@@ -0,0 +1,53 @@
+//! A circuit breaker that stops calling an engine that keeps failing.
+//!
+//! After enough consecutive engine errors, the breaker opens, and requests are turned away
+//! until the cooldown is over. The next request after that is let through as a trial:
+//! if it succeeds the breaker closes, and if it fails the breaker opens again right away.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::server::CircuitBreakerConfig;
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+pub(crate) struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(config: &CircuitBreakerConfig) -> CircuitBreaker {
+        CircuitBreaker {
+            failure_threshold: config.failure_threshold.max(1),
+            cooldown: config.cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Whether requests should be turned away right now.
+    pub(crate) fn is_open(&self) -> bool {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.open_until.is_some_and(|until| Instant::now() < until)
+    }
+
+    pub(crate) fn record_success(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        *state = BreakerState::default();
+    }
+
+    pub(crate) fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        if state.consecutive_failures >= self.failure_threshold {
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
@@ -1,3 +1,28 @@
+/// Serialize a [`shakmaty::Chess`] position as a FEN string.
+///
+/// Deserialization is lenient about trailing fields:
+/// many tools omit the halfmove clock and fullmove number, which then default to `0 1`.
+/// Surrounding whitespace is ignored as well.
+///
+/// ```
+/// use engine_trait::shakmaty::{Chess, Position};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Wrapper(#[serde(with = "engine_trait::chess_serde::position_serde")] Chess);
+///
+/// let full: Wrapper = serde_json::from_str(
+///     "\"rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1\"",
+/// )
+/// .unwrap();
+/// let short: Wrapper = serde_json::from_str(
+///     "\"rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq -\"",
+/// )
+/// .unwrap();
+/// assert_eq!(full.0, short.0);
+/// assert_eq!(short.0.halfmoves(), 0);
+/// assert_eq!(short.0.fullmoves().get(), 1);
+/// ```
 pub mod position_serde {
     use std::str::FromStr;
 
@@ -5,7 +30,7 @@ pub mod position_serde {
         de::{Error, Visitor},
         Deserializer, Serializer,
     };
-    use shakmaty::{fen::Fen, Chess};
+    use shakmaty::{fen::Fen, CastlingMode, Chess};
 
     pub fn serialize<S: Serializer>(b: &Chess, ser: S) -> Result<S::Ok, S::Error> {
         let fen = Fen::from_position(b.clone(), shakmaty::EnPassantMode::Legal);
@@ -24,12 +49,13 @@ pub mod position_serde {
             where
                 E: serde::de::Error,
             {
-                Ok(Fen::from_str(v)
-                    .map_err(|_| Error::custom("error in parsing board's FEN"))?
-                    .into_position(shakmaty::CastlingMode::Standard)
-                    .map_err(|v| {
-                        Error::custom(format!("error in parsing FEN into game position: {v}"))
-                    })?)
+                let fen = Fen::from_str(v.trim())
+                    .map_err(|_| Error::custom("error in parsing board's FEN"))?;
+                // Chess960 castling rights cannot be represented in standard mode, so detect which one this is.
+                let mode = CastlingMode::detect(fen.as_setup());
+                fen.into_position(mode).map_err(|v| {
+                    Error::custom(format!("error in parsing FEN into game position: {v}"))
+                })
             }
             fn visit_none<E>(self) -> Result<Self::Value, E>
             where
@@ -68,9 +94,510 @@ pub mod uci_serde {
             where
                 E: serde::de::Error,
             {
-                Ok(Uci::from_str(v).map_err(|_| Error::custom("error in parsing move's UCI"))?)
+                Uci::from_str(v).map_err(|_| Error::custom("error in parsing move's UCI"))
             }
         }
         d.deserialize_string(UciVisitor {})
     }
 }
+
+/// Serialize an optional move as a UCI string, or null.
+pub mod uci_option_serde {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use shakmaty::uci::Uci;
+
+    pub fn serialize<S: Serializer>(u: &Option<Uci>, ser: S) -> Result<S::Ok, S::Error> {
+        match u {
+            Some(u) => ser.serialize_str(&u.to_string()),
+            None => ser.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Uci>, D::Error> {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "super::uci_serde")] Uci);
+
+        Ok(Option::<Wrapper>::deserialize(d)?.map(|Wrapper(u)| u))
+    }
+}
+
+pub mod uci_list_serde {
+    use serde::{ser::SerializeSeq, Deserialize, Deserializer, Serializer};
+    use shakmaty::uci::Uci;
+
+    pub fn serialize<S: Serializer>(list: &[Uci], ser: S) -> Result<S::Ok, S::Error> {
+        let mut seq = ser.serialize_seq(Some(list.len()))?;
+        for u in list {
+            seq.serialize_element(&u.to_string())?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Uci>, D::Error> {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "super::uci_serde")] Uci);
+
+        let list = Vec::<Wrapper>::deserialize(d)?;
+        Ok(list.into_iter().map(|Wrapper(u)| u).collect())
+    }
+}
+
+pub mod position_list_serde {
+    use serde::{ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
+    use shakmaty::Chess;
+
+    pub fn serialize<S: Serializer>(list: &[Chess], ser: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Wrapper<'a>(#[serde(with = "super::position_serde")] &'a Chess);
+
+        let mut seq = ser.serialize_seq(Some(list.len()))?;
+        for p in list {
+            seq.serialize_element(&Wrapper(p))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Chess>, D::Error> {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "super::position_serde")] Chess);
+
+        let list = Vec::<Wrapper>::deserialize(d)?;
+        Ok(list.into_iter().map(|Wrapper(p)| p).collect())
+    }
+}
+
+/// Serialize a list of [`shakmaty::Square`]s by their names, like `e4`.
+pub mod square_list_serde {
+    use serde::{de::Error, ser::SerializeSeq, Deserialize, Deserializer, Serializer};
+    use shakmaty::Square;
+
+    pub fn serialize<S: Serializer>(list: &[Square], ser: S) -> Result<S::Ok, S::Error> {
+        let mut seq = ser.serialize_seq(Some(list.len()))?;
+        for sq in list {
+            seq.serialize_element(&sq.to_string())?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Square>, D::Error> {
+        Vec::<String>::deserialize(d)?
+            .iter()
+            .map(|name| {
+                name.parse()
+                    .map_err(|_| Error::custom("error in parsing square name"))
+            })
+            .collect()
+    }
+}
+
+/// A move, along with its SAN, so that clients can show it without chess logic of their own.
+///
+/// Serialize it with [`rich_move_serde`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RichMove {
+    pub uci: shakmaty::uci::Uci,
+
+    /// The move in SAN, if known. It is always known for moves made with [`RichMove::new`].
+    pub san: Option<shakmaty::san::SanPlus>,
+}
+
+impl RichMove {
+    /// Describe a move that is legal in `position`.
+    pub fn new(position: &shakmaty::Chess, m: &shakmaty::Move) -> RichMove {
+        use shakmaty::Position;
+
+        RichMove {
+            uci: m.to_uci(position.castles().mode()),
+            san: Some(shakmaty::san::SanPlus::from_move(position.clone(), m)),
+        }
+    }
+}
+
+/// Serialize a [`RichMove`] as an object: `{ "uci", "san", "from", "to", "promotion" }`.
+///
+/// The squares and promotion come from the UCI, so castling goes to the square the UCI names:
+/// the king's destination in standard chess, and the rook's in Chess960.
+/// Deserialization only needs the `uci` key. The `san` key is parsed when present;
+/// `from`, `to` and `promotion` are ignored, since they only repeat the UCI.
+///
+/// ```
+/// use engine_trait::{
+///     chess_serde::RichMove,
+///     shakmaty::{fen::Fen, CastlingMode, Chess, Position},
+/// };
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Wrapper(#[serde(with = "engine_trait::chess_serde::rich_move_serde")] RichMove);
+///
+/// let position: Chess = "4k3/1P6/8/8/8/8/8/4K2R w K - 0 1"
+///     .parse::<Fen>()
+///     .unwrap()
+///     .into_position(CastlingMode::Standard)
+///     .unwrap();
+/// for uci in ["e1g1", "b7b8q"] {
+///     let m = uci.parse::<engine_trait::shakmaty::uci::Uci>().unwrap().to_move(&position).unwrap();
+///     let rich = RichMove::new(&position, &m);
+///     let json = serde_json::to_value(Wrapper(rich.clone())).unwrap();
+///     assert_eq!(serde_json::from_value::<Wrapper>(json.clone()).unwrap().0, rich);
+///     assert_eq!(json["uci"], uci);
+/// }
+///
+/// let promotion = serde_json::to_value(Wrapper(RichMove::new(
+///     &position,
+///     &"b7b8q".parse::<engine_trait::shakmaty::uci::Uci>().unwrap().to_move(&position).unwrap(),
+/// )))
+/// .unwrap();
+/// assert_eq!(promotion["san"], "b8=Q+");
+/// assert_eq!(promotion["from"], "b7");
+/// assert_eq!(promotion["to"], "b8");
+/// assert_eq!(promotion["promotion"], "q");
+///
+/// let short: Wrapper = serde_json::from_str(r#"{"uci": "e1g1"}"#).unwrap();
+/// assert_eq!(short.0.san, None);
+/// ```
+pub mod rich_move_serde {
+    use std::str::FromStr;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+    use shakmaty::{san::SanPlus, uci::Uci};
+
+    use super::RichMove;
+
+    #[derive(Serialize, Deserialize)]
+    struct Wire {
+        #[serde(with = "super::uci_serde")]
+        uci: Uci,
+        #[serde(default)]
+        san: Option<String>,
+        #[serde(default)]
+        from: Option<String>,
+        #[serde(default)]
+        to: Option<String>,
+        #[serde(default)]
+        promotion: Option<String>,
+    }
+
+    pub fn serialize<S: Serializer>(m: &RichMove, ser: S) -> Result<S::Ok, S::Error> {
+        let (from, to, promotion) = match &m.uci {
+            Uci::Normal {
+                from,
+                to,
+                promotion,
+            } => (
+                Some(from.to_string()),
+                Some(to.to_string()),
+                promotion.map(|v| v.char().to_string()),
+            ),
+            Uci::Put { to, .. } => (None, Some(to.to_string()), None),
+            Uci::Null => (None, None, None),
+        };
+        Wire {
+            uci: m.uci.clone(),
+            san: m.san.as_ref().map(|v| v.to_string()),
+            from,
+            to,
+            promotion,
+        }
+        .serialize(ser)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<RichMove, D::Error> {
+        let wire = Wire::deserialize(d)?;
+        let san = wire
+            .san
+            .map(|v| {
+                SanPlus::from_str(&v).map_err(|_| Error::custom("error in parsing move's SAN"))
+            })
+            .transpose()?;
+        Ok(RichMove { uci: wire.uci, san })
+    }
+}
+
+pub mod color_serde {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use shakmaty::Color;
+
+    pub fn serialize<S: Serializer>(c: &Color, ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_str(match c {
+            Color::White => "white",
+            Color::Black => "black",
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Color, D::Error> {
+        match String::deserialize(d)?.as_str() {
+            "white" => Ok(Color::White),
+            "black" => Ok(Color::Black),
+            _ => Err(Error::custom("expected \"white\" or \"black\"")),
+        }
+    }
+}
+
+pub mod castling_side_serde {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use shakmaty::CastlingSide;
+
+    pub fn serialize<S: Serializer>(c: &CastlingSide, ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_str(match c {
+            CastlingSide::KingSide => "king",
+            CastlingSide::QueenSide => "queen",
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<CastlingSide, D::Error> {
+        match String::deserialize(d)?.as_str() {
+            "king" => Ok(CastlingSide::KingSide),
+            "queen" => Ok(CastlingSide::QueenSide),
+            _ => Err(Error::custom("expected \"king\" or \"queen\"")),
+        }
+    }
+}
+
+/// Serialize an optional [`CastlingMode`](shakmaty::CastlingMode) as `"standard"` or `"chess960"`, or null if it is not set.
+pub mod castling_mode_serde {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use shakmaty::CastlingMode;
+
+    pub fn serialize<S: Serializer>(c: &Option<CastlingMode>, ser: S) -> Result<S::Ok, S::Error> {
+        match c {
+            Some(CastlingMode::Standard) => ser.serialize_str("standard"),
+            Some(CastlingMode::Chess960) => ser.serialize_str("chess960"),
+            None => ser.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<CastlingMode>, D::Error> {
+        match Option::<String>::deserialize(d)?.as_deref() {
+            Some("standard") => Ok(Some(CastlingMode::Standard)),
+            Some("chess960") => Ok(Some(CastlingMode::Chess960)),
+            None => Ok(None),
+            _ => Err(Error::custom("expected \"standard\" or \"chess960\"")),
+        }
+    }
+}
+
+/// Serialize bytes as a lowercase hexadecimal string. Uppercase is accepted as well when deserializing.
+pub mod hex_serde {
+    use std::fmt::Write;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], ser: S) -> Result<S::Ok, S::Error> {
+        let mut hex = String::with_capacity(bytes.len() * 2);
+        for byte in bytes {
+            write!(hex, "{byte:02x}").expect("writing to a string cannot fail");
+        }
+        ser.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(d)?;
+        if hex.len() % 2 != 0 || !hex.is_ascii() {
+            return Err(Error::custom(
+                "expected an even number of hexadecimal digits",
+            ));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                u8::from_str_radix(&hex[i..i + 2], 16)
+                    .map_err(|_| Error::custom("expected hexadecimal digits"))
+            })
+            .collect()
+    }
+}
+
+/// Serialize a `u64` as a decimal string, since JavaScript numbers cannot hold every `u64` exactly.
+///
+/// Plain numbers are accepted as well when deserializing.
+pub mod u64_string_serde {
+    use serde::{
+        de::{Error, Visitor},
+        Deserializer, Serializer,
+    };
+
+    pub fn serialize<S: Serializer>(v: &u64, ser: S) -> Result<S::Ok, S::Error> {
+        ser.collect_str(v)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<u64, D::Error> {
+        struct U64Visitor {}
+        impl<'de> Visitor<'de> for U64Visitor {
+            type Value = u64;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(
+                    formatter,
+                    "an unsigned 64-bit integer, or a string holding one"
+                )
+            }
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(v)
+            }
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                v.parse()
+                    .map_err(|_| Error::custom("error in parsing unsigned 64-bit integer"))
+            }
+        }
+        d.deserialize_any(U64Visitor {})
+    }
+}
+
+/// Like [`u64_string_serde`], for an optional `u64`, which is `null` when absent.
+pub mod u64_string_option_serde {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    struct U64Repr(#[serde(with = "super::u64_string_serde")] u64);
+
+    pub fn serialize<S: Serializer>(v: &Option<u64>, ser: S) -> Result<S::Ok, S::Error> {
+        match v {
+            Some(v) => super::u64_string_serde::serialize(v, ser),
+            None => ser.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u64>, D::Error> {
+        Ok(Option::<U64Repr>::deserialize(d)?.map(|v| v.0))
+    }
+}
+
+/// Serialize a [`Duration`](std::time::Duration) as a whole number of milliseconds, rounding down,
+/// which is what JavaScript clients work with. Every duration in the crate's types is written this way.
+///
+/// Serde's own form, an object with `secs` and `nanos`, is accepted as well when deserializing,
+/// for clients of older servers.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, Debug, PartialEq)]
+/// struct Budget {
+///     #[serde(with = "engine_trait::chess_serde::duration_ms_serde")]
+///     think: Duration,
+///     #[serde(with = "engine_trait::chess_serde::duration_ms_option_serde", default)]
+///     extra: Option<Duration>,
+/// }
+///
+/// let budget = Budget { think: Duration::from_millis(1500), extra: None };
+/// let json = serde_json::to_value(&budget).unwrap();
+/// assert_eq!(json, serde_json::json!({ "think": 1500, "extra": null }));
+/// assert_eq!(serde_json::from_value::<Budget>(json).unwrap(), budget);
+///
+/// let old = serde_json::json!({ "think": { "secs": 2, "nanos": 500_000_000 }, "extra": 250 });
+/// let budget = serde_json::from_value::<Budget>(old).unwrap();
+/// assert_eq!(budget.think, Duration::from_millis(2500));
+/// assert_eq!(budget.extra, Some(Duration::from_millis(250)));
+///
+/// // Durations too long to represent are refused rather than panicking.
+/// let huge = serde_json::json!({ "think": { "secs": u64::MAX, "nanos": 1_000_000_000 } });
+/// assert!(serde_json::from_value::<Budget>(huge).is_err());
+/// ```
+pub mod duration_ms_serde {
+    use std::time::Duration;
+
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &Duration, ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_u64(u64::try_from(v.as_millis()).unwrap_or(u64::MAX))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+        DurationRepr::deserialize(d)?.into_duration()
+    }
+
+    /// The forms a duration is accepted in.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    pub(super) enum DurationRepr {
+        Millis(u64),
+        Serde { secs: u64, nanos: u32 },
+    }
+
+    impl DurationRepr {
+        /// The duration, or an error if it overflows, which `Duration::new` would panic on.
+        pub(super) fn into_duration<E: de::Error>(self) -> Result<Duration, E> {
+            match self {
+                DurationRepr::Millis(ms) => Ok(Duration::from_millis(ms)),
+                DurationRepr::Serde { secs, nanos } => Duration::from_secs(secs)
+                    .checked_add(Duration::from_nanos(nanos.into()))
+                    .ok_or_else(|| E::custom("duration overflows")),
+            }
+        }
+    }
+}
+
+/// Like [`duration_ms_serde`], for an optional duration, which is `null` when absent.
+pub mod duration_ms_option_serde {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::duration_ms_serde::DurationRepr;
+
+    pub fn serialize<S: Serializer>(v: &Option<Duration>, ser: S) -> Result<S::Ok, S::Error> {
+        match v {
+            Some(v) => super::duration_ms_serde::serialize(v, ser),
+            None => ser.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+        Option::<DurationRepr>::deserialize(d)?
+            .map(DurationRepr::into_duration)
+            .transpose()
+    }
+}
+
+/// Serialize `value` as JSON with the keys of every object sorted, so that equal values always give equal strings.
+///
+/// Serde writes map keys in whatever order the type iterates them, which for a `HashMap` changes from run to run,
+/// so serialized states and requests should go through this before they are hashed or used as cache keys.
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use engine_trait::chess_serde::canonical_json;
+///
+/// let a: HashMap<&str, u32> = [("knight", 3), ("bishop", 3), ("rook", 5)].into_iter().collect();
+/// let b: HashMap<&str, u32> = [("rook", 5), ("knight", 3), ("bishop", 3)].into_iter().collect();
+/// assert_eq!(canonical_json(&a).unwrap(), canonical_json(&b).unwrap());
+/// assert_eq!(
+///     canonical_json(&serde_json::json!({ "b": { "z": 1, "y": [{ "d": 0, "c": 0 }] }, "a": null })).unwrap(),
+///     r#"{"a":null,"b":{"y":[{"c":0,"d":0}],"z":1}}"#,
+/// );
+/// ```
+pub fn canonical_json<T: serde::Serialize + ?Sized>(
+    value: &T,
+) -> Result<String, serde_json::Error> {
+    fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => {
+                let mut entries: Vec<_> = map.into_iter().collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                // Building the map in sorted order keeps it sorted even if serde_json preserves insertion order.
+                serde_json::Value::Object(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (key, sort_keys(value)))
+                        .collect(),
+                )
+            }
+            serde_json::Value::Array(values) => {
+                serde_json::Value::Array(values.into_iter().map(sort_keys).collect())
+            }
+            other => other,
+        }
+    }
+
+    serde_json::to_string(&sort_keys(serde_json::to_value(value)?))
+}
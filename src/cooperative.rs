@@ -0,0 +1,84 @@
+//! Yielding to the async runtime from long computations.
+//!
+//! Engine methods are `async`, but their work is usually CPU-bound.
+//! A method that computes for a long time without reaching an `.await` that actually waits
+//! keeps the runtime's thread to itself, so other requests on that thread stall until it is done.
+//! Long loops, like rebuilding a cache in [`Engine::observe_move`](crate::Engine::observe_move),
+//! should give the runtime a chance to run other tasks every so often:
+//!
+//! ```
+//! use engine_trait::cooperative::Yielder;
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let mut yielder = Yielder::new(1000);
+//! let mut total = 0u64;
+//! for i in 0..100_000u64 {
+//!     total += i;
+//!     // Lets other tasks run once every 1000 iterations.
+//!     yielder.tick().await;
+//! }
+//! assert_eq!(total, 4_999_950_000);
+//! # });
+//! ```
+//!
+//! Yielding does not make the work any shorter, it only interleaves it with other tasks.
+//! Work that cannot be broken up is better moved off the runtime, e.g. with `tokio::task::spawn_blocking`.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Let the runtime run other tasks before continuing.
+///
+/// This works with any runtime, since it only returns to the executor once, after asking to be polled again.
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+/// The future returned by [`yield_now`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless awaited"]
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Yields to the runtime once every so many steps of a loop, so that yielding costs little in tight loops.
+#[derive(Clone, Debug)]
+pub struct Yielder {
+    every: u32,
+    steps: u32,
+}
+
+impl Yielder {
+    /// A yielder that yields on every `every`th call to [`Yielder::tick`].
+    pub fn new(every: u32) -> Yielder {
+        Yielder {
+            every: every.max(1),
+            steps: 0,
+        }
+    }
+
+    /// Count one step, and yield if it is time to.
+    pub async fn tick(&mut self) {
+        self.steps += 1;
+        if self.steps >= self.every {
+            self.steps = 0;
+            yield_now().await;
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Reading EPD test suites, and running an engine against them.
+//!
+//! An EPD line is the first four fields of a FEN, followed by operations such as
+//! `bm Nf3;` (the best move), `am Qxb2;` (a move to avoid), and `id "WAC.001";`.
+//!
+//! ```
+//! use engine_trait::epd::read_epd;
+//!
+//! let suite = read_epd(
+//!     "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";",
+//! ).unwrap();
+//! assert_eq!(suite[0].id.as_deref(), Some("WAC.001"));
+//! assert_eq!(suite[0].best_moves.len(), 1);
+//!
+//! // Fields may be separated by any run of spaces and tabs.
+//! let suite = read_epd(
+//!     "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1  w\t-  -   bm Qg6; id \"WAC.001\";",
+//! ).unwrap();
+//! assert_eq!(suite[0].id.as_deref(), Some("WAC.001"));
+//! ```
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use shakmaty::{fen::Fen, san::SanPlus, uci::Uci, CastlingMode, Chess, Move, Position};
+
+use crate::Engine;
+
+/// One position of an EPD suite.
+#[derive(Clone, Debug)]
+pub struct EpdPosition {
+    pub position: Chess,
+
+    /// The moves from the `bm` operation. If not empty, the engine must play one of them.
+    pub best_moves: Vec<Move>,
+
+    /// The moves from the `am` operation. The engine must not play any of them.
+    pub avoid_moves: Vec<Move>,
+
+    /// The `id` operation, if any.
+    pub id: Option<String>,
+}
+
+/// Ways in which reading an EPD suite can fail. Lines count from 1.
+#[derive(Clone, Debug)]
+pub enum EpdError {
+    /// The position fields of the line are not a valid position.
+    InvalidPosition { line: usize },
+
+    /// A `bm` or `am` move is not legal in the line's position.
+    IllegalMove { line: usize, san: String },
+}
+
+impl std::fmt::Display for EpdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EpdError::InvalidPosition { line } => write!(f, "invalid position on line {line}"),
+            EpdError::IllegalMove { line, san } => {
+                write!(f, "illegal move on line {line}: {san}")
+            }
+        }
+    }
+}
+
+/// Read every non-empty line of an EPD text.
+pub fn read_epd(epd: &str) -> Result<Vec<EpdPosition>, EpdError> {
+    epd.lines()
+        .enumerate()
+        .filter(|(_, text)| !text.trim().is_empty())
+        .map(|(i, text)| read_epd_line(text, i + 1))
+        .collect()
+}
+
+fn read_epd_line(text: &str, line: usize) -> Result<EpdPosition, EpdError> {
+    // Fields may be separated by runs of spaces or tabs, which would make empty fields of `splitn`.
+    let setup: Vec<&str> = text.split_ascii_whitespace().take(4).collect();
+    let mut operations = text;
+    for _ in 0..4 {
+        operations = operations
+            .trim_start_matches(|c: char| c.is_ascii_whitespace())
+            .trim_start_matches(|c: char| !c.is_ascii_whitespace());
+    }
+    let operations = operations.trim();
+
+    let position: Chess = Fen::from_str(&setup.join(" "))
+        .ok()
+        .and_then(|v| {
+            let mode = CastlingMode::detect(v.as_setup());
+            v.into_position(mode).ok()
+        })
+        .ok_or(EpdError::InvalidPosition { line })?;
+
+    let mut suite_position = EpdPosition {
+        position,
+        best_moves: Vec::new(),
+        avoid_moves: Vec::new(),
+        id: None,
+    };
+    for operation in split_operations(operations) {
+        let (opcode, operands) = operation
+            .split_once(char::is_whitespace)
+            .unwrap_or((operation, ""));
+        let moves = match opcode {
+            "bm" => &mut suite_position.best_moves,
+            "am" => &mut suite_position.avoid_moves,
+            "id" => {
+                suite_position.id = Some(operands.trim().trim_matches('"').to_string());
+                continue;
+            }
+            _ => continue,
+        };
+        for san in operands.split_whitespace() {
+            let m = SanPlus::from_str(san)
+                .ok()
+                .and_then(|v| v.san.to_move(&suite_position.position).ok())
+                .ok_or_else(|| EpdError::IllegalMove {
+                    line,
+                    san: san.to_string(),
+                })?;
+            moves.push(m);
+        }
+    }
+
+    Ok(suite_position)
+}
+
+/// Split operations on semicolons, except those inside quoted strings.
+fn split_operations(operations: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    for (i, c) in operations.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                result.push(operations[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    result.push(operations[start..].trim());
+    result.retain(|v| !v.is_empty());
+    result
+}
+
+/// Limits on how much of a suite to run.
+#[derive(Clone, Debug, Default)]
+pub struct SuiteLimits {
+    /// Only run the first this many positions.
+    pub max_positions: Option<usize>,
+}
+
+/// How the engine did on one position of a suite.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PositionResult {
+    pub id: Option<String>,
+
+    /// The move the engine chose.
+    #[serde(with = "crate::chess_serde::uci_serde")]
+    pub r#move: Uci,
+
+    /// Whether the move is one of the best moves, and none of the moves to avoid.
+    pub passed: bool,
+}
+
+/// How the engine did on a whole suite.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SuiteReport {
+    /// The result for every position that was run, in order.
+    pub results: Vec<PositionResult>,
+
+    /// How many positions the engine passed.
+    pub passed: usize,
+}
+
+impl SuiteReport {
+    /// The fraction of positions the engine passed, from 0 to 1.
+    /// An empty suite scores 0.
+    pub fn score(&self) -> f64 {
+        if self.results.is_empty() {
+            0.0
+        } else {
+            self.passed as f64 / self.results.len() as f64
+        }
+    }
+}
+
+/// Ask the engine for a move in every position of a suite, and check it against the suite's `bm` and `am` moves.
+///
+/// The engine starts from its initial state in every position, like in analysis,
+/// and position number `i` (counting from 0) is given `i` as its random number, so that runs are reproducible.
+pub async fn run_epd_suite<E: Engine>(
+    engine: &mut E,
+    positions: &[EpdPosition],
+    limits: SuiteLimits,
+) -> Result<SuiteReport, E::Error> {
+    let initial_state = E::get_info().initial_state;
+    let count = limits.max_positions.unwrap_or(positions.len());
+    let mut results = Vec::with_capacity(count.min(positions.len()));
+    for (i, suite_position) in positions.iter().take(count).enumerate() {
+        let m = engine
+            .propose_move_without_info(i as u64, &initial_state, &suite_position.position)
+            .await?;
+        let passed = (suite_position.best_moves.is_empty()
+            || suite_position.best_moves.contains(&m))
+            && !suite_position.avoid_moves.contains(&m);
+        results.push(PositionResult {
+            id: suite_position.id.clone(),
+            r#move: m.to_uci(suite_position.position.castles().mode()),
+            passed,
+        });
+    }
+
+    Ok(SuiteReport {
+        passed: results.iter().filter(|v| v.passed).count(),
+        results,
+    })
+}
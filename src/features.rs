@@ -0,0 +1,153 @@
+//! Engine-independent descriptors of a position, for building datasets without running a search.
+//!
+//! ```
+//! use engine_trait::{features::features, shakmaty::Chess};
+//!
+//! let start = features(&Chess::new());
+//! assert_eq!(start.white.pawns, 8);
+//! assert_eq!(start.black.mobility, Some(20));
+//! assert_eq!(start.black.king_file, Some('e'));
+//! assert_eq!(start.white.passed_pawns, 0);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use shakmaty::{Bitboard, Chess, Color, File, Position, Square};
+
+/// The descriptors of a position, for each side.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PositionFeatures {
+    pub white: SideFeatures,
+    pub black: SideFeatures,
+}
+
+/// The descriptors of one side of a position.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SideFeatures {
+    pub pawns: u8,
+    pub knights: u8,
+    pub bishops: u8,
+    pub rooks: u8,
+    pub queens: u8,
+
+    /// How many legal moves the side would have if it were its turn.
+    /// None if it is not its turn, and it cannot be, because it is giving check.
+    pub mobility: Option<usize>,
+
+    /// Pawns beyond the first on each file.
+    pub doubled_pawns: u8,
+
+    /// Pawns with no friendly pawns on the adjacent files.
+    pub isolated_pawns: u8,
+
+    /// Pawns with no enemy pawns in front of them, on their own or the adjacent files.
+    pub passed_pawns: u8,
+
+    /// The file the king is on, as a letter from `a` to `h`.
+    pub king_file: Option<char>,
+}
+
+/// Describe a position with [`PositionFeatures`].
+pub fn features(position: &Chess) -> PositionFeatures {
+    PositionFeatures {
+        white: side_features(position, Color::White),
+        black: side_features(position, Color::Black),
+    }
+}
+
+fn side_features(position: &Chess, color: Color) -> SideFeatures {
+    let board = position.board();
+    let material = board.material_side(color);
+    let mobility = if position.turn() == color {
+        Some(position.legal_moves().len())
+    } else {
+        position
+            .clone()
+            .swap_turn()
+            .ok()
+            .map(|v| v.legal_moves().len())
+    };
+
+    let pawns = board.by_piece(color.pawn());
+    let enemy_pawns = board.by_piece((!color).pawn());
+    let on_file = |pawns: Bitboard, file: Option<File>| {
+        file.map_or(Bitboard::EMPTY, |file| pawns & Bitboard::from_file(file))
+    };
+    let adjacent = |pawns: Bitboard, file: File| {
+        on_file(pawns, file.offset(-1)) | on_file(pawns, file.offset(1))
+    };
+    let is_ahead = |pawn: Square, other: Square| match color {
+        Color::White => other.rank() > pawn.rank(),
+        Color::Black => other.rank() < pawn.rank(),
+    };
+
+    let doubled_pawns = File::ALL
+        .into_iter()
+        .map(|file| on_file(pawns, Some(file)).count().saturating_sub(1))
+        .sum::<usize>() as u8;
+    let isolated_pawns = pawns
+        .into_iter()
+        .filter(|pawn| adjacent(pawns, pawn.file()).is_empty())
+        .count() as u8;
+    let passed_pawns = pawns
+        .into_iter()
+        .filter(|&pawn| {
+            (on_file(enemy_pawns, Some(pawn.file())) | adjacent(enemy_pawns, pawn.file()))
+                .into_iter()
+                .all(|enemy| !is_ahead(pawn, enemy))
+        })
+        .count() as u8;
+
+    SideFeatures {
+        pawns: material.pawn,
+        knights: material.knight,
+        bishops: material.bishop,
+        rooks: material.rook,
+        queens: material.queen,
+        mobility,
+        doubled_pawns,
+        isolated_pawns,
+        passed_pawns,
+        king_file: board
+            .by_piece(color.king())
+            .first()
+            .map(|king| king.file().char()),
+    }
+}
+
+/// The squares of the pieces of `color` that are hanging, in square order (`a1`, `b1`, ..., `h8`).
+///
+/// A piece is hanging if an enemy piece attacks it, and no piece of its own side defends it.
+/// Kings are never hanging. Attacks are counted as the pieces stand, so a pinned piece still attacks and defends,
+/// and neither x-rays through other pieces nor en passant captures are considered.
+/// Whether the exchange would actually win material is not considered either.
+///
+/// ```
+/// use engine_trait::{
+///     features::hanging_pieces,
+///     shakmaty::{fen::Fen, CastlingMode, Chess, Color, Square},
+/// };
+///
+/// let position = |fen: &str| -> Chess {
+///     fen.parse::<Fen>().unwrap().into_position(CastlingMode::Standard).unwrap()
+/// };
+///
+/// // The rook attacks the knight, and nothing defends it.
+/// let undefended = position("4k3/8/8/3n4/8/8/8/3RK3 w - - 0 1");
+/// assert_eq!(hanging_pieces(&undefended, Color::Black), [Square::D5]);
+/// assert_eq!(hanging_pieces(&undefended, Color::White), []);
+///
+/// // Now the pawn defends the knight.
+/// let defended = position("4k3/8/4p3/3n4/8/8/8/3RK3 w - - 0 1");
+/// assert_eq!(hanging_pieces(&defended, Color::Black), []);
+/// ```
+pub fn hanging_pieces(position: &Chess, color: Color) -> Vec<Square> {
+    let board = position.board();
+    let occupied = board.occupied();
+    (board.by_color(color) & !board.kings())
+        .into_iter()
+        .filter(|&square| {
+            board.attacks_to(square, !color, occupied).any()
+                && board.attacks_to(square, color, occupied).is_empty()
+        })
+        .collect()
+}
@@ -0,0 +1,88 @@
+//! Engines for the examples in this crate's documentation, so that each example does not need its own.
+//!
+//! This is not part of the public API, and may change at any time.
+
+use shakmaty::{Chess, Move, Position};
+
+use crate::{
+    async_trait, server_types::EngineInfo, Engine, EngineAction, InfallibleError, NoStatus,
+};
+
+/// Plays the first legal move, in [`Position::legal_moves`] order.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FirstMove;
+
+#[async_trait]
+impl Engine for FirstMove {
+    type State = ();
+    type StatusInfo = NoStatus;
+    type Error = InfallibleError;
+
+    fn get_info() -> EngineInfo<Self> {
+        EngineInfo::new("first-move", "Plays the first legal move", ())
+    }
+
+    async fn propose_move(
+        &mut self,
+        _rand: u64,
+        _state: &(),
+        position: &Chess,
+    ) -> Result<(Move, NoStatus), InfallibleError> {
+        Ok((position.legal_moves()[0].clone(), NoStatus))
+    }
+
+    async fn observe_move(
+        &mut self,
+        _rand: u64,
+        _state: &mut (),
+        _move_taken: &Move,
+        _position_after: &Chess,
+    ) -> Result<(), InfallibleError> {
+        Ok(())
+    }
+}
+
+/// Aborts the game whenever it is asked to move, with [`EngineAction::Abort`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Aborter;
+
+#[async_trait]
+impl Engine for Aborter {
+    type State = ();
+    type StatusInfo = NoStatus;
+    type Error = InfallibleError;
+
+    fn get_info() -> EngineInfo<Self> {
+        EngineInfo::new("aborter", "Aborts every game it plays", ())
+    }
+
+    async fn choose_action(
+        &mut self,
+        _rand: u64,
+        _state: &(),
+        _position: &Chess,
+    ) -> Result<EngineAction, InfallibleError> {
+        Ok(EngineAction::Abort {
+            reason: "always aborts".to_string(),
+        })
+    }
+
+    async fn propose_move(
+        &mut self,
+        rand: u64,
+        state: &(),
+        position: &Chess,
+    ) -> Result<(Move, NoStatus), InfallibleError> {
+        FirstMove.propose_move(rand, state, position).await
+    }
+
+    async fn observe_move(
+        &mut self,
+        _rand: u64,
+        _state: &mut (),
+        _move_taken: &Move,
+        _position_after: &Chess,
+    ) -> Result<(), InfallibleError> {
+        Ok(())
+    }
+}
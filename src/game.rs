@@ -0,0 +1,1329 @@
+//! Playing a turn of a game in-process, without going through HTTP.
+//!
+//! This is what the server does for every move request, and it can be used directly to drive an engine.
+//!
+//! The seeds in the request go to the engine calls they are named after,
+//! and the response reports each one in the matching `_used` field:
+//!
+//! ```
+//! use engine_trait::{
+//!     async_trait, game::play_turn, position::position_hash,
+//!     server_types::{AnyEngineResponse, EngineInfo, EngineRequest, EngineResult},
+//!     shakmaty::{uci::Uci, Chess, Move, Position},
+//!     Engine, InfallibleError,
+//! };
+//!
+//! /// Plays the first legal move, and records the seed of every call it gets.
+//! #[derive(Default)]
+//! struct Recorder {
+//!     calls: Vec<(&'static str, u64)>,
+//! }
+//!
+//! #[async_trait]
+//! impl Engine for Recorder {
+//!     type State = ();
+//!     type StatusInfo = ();
+//!     type Error = InfallibleError;
+//!
+//!     fn get_info() -> EngineInfo<Self> {
+//!         EngineInfo::new("recorder", "Records the seeds it is given", ())
+//!     }
+//!
+//!     async fn propose_move(
+//!         &mut self,
+//!         rand: u64,
+//!         _state: &(),
+//!         position: &Chess,
+//!     ) -> Result<(Move, ()), InfallibleError> {
+//!         self.calls.push(("propose", rand));
+//!         Ok((position.legal_moves()[0].clone(), ()))
+//!     }
+//!
+//!     async fn observe_move(
+//!         &mut self,
+//!         rand: u64,
+//!         _state: &mut (),
+//!         _move_taken: &Move,
+//!         _position_after: &Chess,
+//!     ) -> Result<(), InfallibleError> {
+//!         self.calls.push(("observe", rand));
+//!         Ok(())
+//!     }
+//! }
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let mut engine = Recorder::default();
+//! let request = EngineRequest::<Recorder> {
+//!     r#move: "e2e4".parse().unwrap(),
+//!     game_before: Chess::new(),
+//!     chess960_sp: None,
+//!     castling_mode: None,
+//!     engine_state: (),
+//!     observe_other_rand: Some(1),
+//!     produce_rand: Some(2),
+//!     produce_rand_streams: Vec::new(),
+//!     observe_own_rand: Some(3),
+//!     with_status_info: false,
+//!     game_id: None,
+//!     restrict_to: Vec::new(),
+//!     expected_hash_before: None,
+//!     expected_hash_after_move: None,
+//!     with_pv_positions: false,
+//!     with_position_key: false,
+//!     strength: None,
+//!     with_state_delta: false,
+//!     strict_rules: false,
+//!     position_history: Vec::new(),
+//!     protocol_version: None,
+//! };
+//!
+//! let EngineResult::Ok(response) = play_turn(&mut engine, request).await else {
+//!     panic!("the turn failed");
+//! };
+//! assert_eq!(engine.calls, [("observe", 1), ("propose", 2), ("observe", 3)]);
+//! assert_eq!(response.seeds.observe_other, Some(1));
+//! assert_eq!(response.seeds.produce, Some(2));
+//! assert_eq!(response.seeds.observe_own, Some(3));
+//!
+//! // The hash of the position is sent as a string, for clients that cannot hold it as a number.
+//! assert_eq!(response.game_after_hash, position_hash(&response.game_after));
+//! let json = serde_json::to_value(&response).unwrap();
+//! assert_eq!(json["game_after_hash"], position_hash(&response.game_after).to_string());
+//! let parsed: AnyEngineResponse = serde_json::from_value(json).unwrap();
+//! assert_eq!(parsed.game_after_hash, response.game_after_hash);
+//! # });
+//! ```
+
+use std::fmt::Debug;
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde_json::Value;
+use shakmaty::{fen::Fen, uci::Uci, Chess, Color, FromSetup, Move, Position};
+
+use crate::{
+    position::{
+        castling_rights_lost, claimable_draw, game_outcome, is_mating_line, line_positions,
+        position_hash, position_key, random_legal_move, strict_game_outcome,
+    },
+    random::RandomSource,
+    server_types::{
+        AmendRequest, CastlingRight, DesyncStep, EngineRequest, EngineRequestError, EngineResponse,
+        EngineResult, GameOutcome, HintRequest, HintResponse, MoveSource, Score, SearchTree,
+        SeedOrigin, SeedsUsed, ThreatMethod, ThreatMoveResponse, TreeRequest, TreeResponse,
+    },
+    Engine, EngineAction,
+};
+
+/// What to do when an engine proposes an illegal move, or one outside the request's `restrict_to`.
+///
+/// ```
+/// use engine_trait::{
+///     async_trait,
+///     game::{play_turn_with, IllegalMovePolicy},
+///     server_types::{EngineInfo, EngineRequest, EngineRequestError, EngineResult},
+///     shakmaty::{uci::Uci, Chess, Move},
+///     Engine, InfallibleError, NoStatus,
+/// };
+///
+/// /// Plays 1. e4 whatever the position, which is illegal once it has been played.
+/// struct OneTrick;
+///
+/// #[async_trait]
+/// impl Engine for OneTrick {
+///     type State = ();
+///     type StatusInfo = NoStatus;
+///     type Error = InfallibleError;
+///
+///     fn get_info() -> EngineInfo<Self> {
+///         EngineInfo::new("one-trick", "Always plays e2e4", ())
+///     }
+///
+///     async fn propose_move(&mut self, _: u64, _: &(), _: &Chess) -> Result<(Move, NoStatus), InfallibleError> {
+///         Ok(("e2e4".parse::<Uci>().unwrap().to_move(&Chess::new()).unwrap(), NoStatus))
+///     }
+///
+///     async fn observe_move(&mut self, _: u64, _: &mut (), _: &Move, _: &Chess) -> Result<(), InfallibleError> {
+///         Ok(())
+///     }
+/// }
+///
+/// let request = || -> EngineRequest<OneTrick> {
+///     serde_json::from_value(serde_json::json!({ "move": "e2e4", "produce_rand": 7, "with_status_info": false }))
+///         .unwrap()
+/// };
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let EngineResult::RequestError(why) = play_turn_with(&mut OneTrick, request(), IllegalMovePolicy::Reject).await else {
+///     panic!("the illegal move should fail the turn");
+/// };
+/// assert!(matches!(why, EngineRequestError::EngineSentIllegalMove { .. }));
+///
+/// let EngineResult::Ok(response) = play_turn_with(&mut OneTrick, request(), IllegalMovePolicy::RandomLegal).await else {
+///     panic!("the illegal move should be replaced");
+/// };
+/// assert!(response.substituted);
+/// assert_ne!(response.r#move.to_string(), "e2e4");
+/// # });
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IllegalMovePolicy {
+    /// Fail the turn with [`EngineRequestError::EngineSentIllegalMove`].
+    #[default]
+    Reject,
+
+    /// Play a random allowed move instead, chosen with the turn's `produce_rand`,
+    /// and set the response's `substituted` flag. This keeps casual games going despite a buggy engine.
+    RandomLegal,
+}
+
+/// Observe the other move, propose a move, and observe it, all on one engine.
+///
+/// The state is only ever mutated on the request's own copy, which is returned at the end.
+/// Illegal moves from the engine fail the turn; see [`play_turn_with`] to substitute them instead.
+pub async fn play_turn<E: Engine>(engine: &mut E, request: EngineRequest<E>) -> EngineResult<E> {
+    play_turn_with(engine, request, IllegalMovePolicy::Reject).await
+}
+
+/// Like [`play_turn`], but with a choice of what to do if the engine proposes an illegal move.
+pub async fn play_turn_with<E: Engine>(
+    engine: &mut E,
+    request: EngineRequest<E>,
+    on_illegal_move: IllegalMovePolicy,
+) -> EngineResult<E> {
+    let game_before = match request.resolved_game_before() {
+        Ok(v) => v,
+        Err(why) => return EngineResult::RequestError(why),
+    };
+    if let Err(why) = verify_hash(
+        DesyncStep::GameBefore,
+        request.expected_hash_before,
+        &game_before,
+    ) {
+        return EngineResult::RequestError(why);
+    }
+    if let Err(why) = check_supported(engine, &game_before) {
+        return EngineResult::RequestError(why);
+    }
+    let mut state = request.engine_state;
+    let state_before = request.with_state_delta.then(|| state.clone());
+    engine.normalize_state(&mut state, &game_before);
+    let castling_mode = game_before.castles().mode();
+    let mut history = request.position_history;
+
+    // If the move is a null move, skip processing it
+    let their_move = request.r#move;
+    let (game_after, observe_other_rand_used, observe_other_origin) = if their_move != Uci::Null {
+        // Try parsing the UCI into a move.
+        let user_move = match their_move.to_move(&game_before) {
+            Ok(user_move) => user_move,
+            Err(_) => {
+                return EngineResult::RequestError(EngineRequestError::PositionMoveMismatch);
+            }
+        };
+
+        // Apply the move to the board.
+        let mut game_after = game_before.clone();
+        game_after.play_unchecked(&user_move);
+        history.push(position_hash(&game_before));
+        if let Err(why) = verify_hash(
+            DesyncStep::GameAfterMove,
+            request.expected_hash_after_move,
+            &game_after,
+        ) {
+            return EngineResult::RequestError(why);
+        }
+        if let Err(why) = check_supported(engine, &game_after) {
+            return EngineResult::RequestError(why);
+        }
+
+        // The engine needs to observe this move.
+        let (observe_rand, origin) = SeedOrigin::resolve(request.observe_other_rand);
+        if let Err(why) = engine
+            .observe_move(observe_rand, &mut state, &user_move, &game_after)
+            .await
+        {
+            return EngineResult::EngineError(why);
+        }
+
+        (game_after, Some(observe_rand), Some(origin))
+    } else {
+        // If the move is a null move, there is nothing to observe.
+        if let Err(why) = verify_hash(
+            DesyncStep::GameAfterMove,
+            request.expected_hash_after_move,
+            &game_before,
+        ) {
+            return EngineResult::RequestError(why);
+        }
+        (game_before, None, None)
+    };
+
+    // If the other move ended the game, the engine has nothing to play.
+    if let Some(outcome) = outcome(&game_after, request.strict_rules, &history) {
+        let seeds = SeedsUsed {
+            observe_other: observe_other_rand_used,
+            produce: None,
+            observe_own: None,
+            observe_other_origin,
+            produce_origin: None,
+            observe_own_origin: None,
+            unobserve_reply: None,
+            unobserve_last: None,
+            unobserve_reply_origin: None,
+            unobserve_last_origin: None,
+        };
+        return EngineResult::Ok(game_over_response(
+            game_after,
+            seeds,
+            state_before.as_ref(),
+            state,
+            outcome,
+            request.with_position_key,
+        ));
+    }
+
+    // Now that the other move has been observed, we need to produce a new move.
+
+    let mut allowed = Vec::with_capacity(request.restrict_to.len());
+    for uci in request.restrict_to {
+        match uci.to_move(&game_after) {
+            Ok(m) => allowed.push(m),
+            Err(_) => {
+                return EngineResult::RequestError(EngineRequestError::IllegalRestriction {
+                    r#move: uci,
+                })
+            }
+        }
+    }
+
+    let engine_info = E::get_info();
+    let strength = match engine_info.capabilities.strength_range {
+        // A range declared backwards would make `clamp` panic, so this never does.
+        Some((min, max)) => request.strength.map(|v| v.max(min).min(max)),
+        None => request.strength,
+    };
+    engine.set_strength(strength);
+
+    let (produce_rand_used, produce_origin) = SeedOrigin::resolve(request.produce_rand);
+    let ended = match engine
+        .choose_action(produce_rand_used, &state, &game_after)
+        .await
+    {
+        Ok(EngineAction::Move) => None,
+        Ok(EngineAction::ClaimDraw) => match claimable_draw(&game_after, &history) {
+            Some(outcome) => Some(outcome),
+            None => return EngineResult::RequestError(EngineRequestError::InvalidDrawClaim),
+        },
+        Ok(EngineAction::Abort { reason }) => Some(GameOutcome::Aborted { reason }),
+        Err(why) => return EngineResult::EngineError(why),
+    };
+    if let Some(outcome) = ended {
+        let seeds = SeedsUsed {
+            observe_other: observe_other_rand_used,
+            produce: Some(produce_rand_used),
+            observe_own: None,
+            observe_other_origin,
+            produce_origin: Some(produce_origin),
+            observe_own_origin: None,
+            unobserve_reply: None,
+            unobserve_last: None,
+            unobserve_reply_origin: None,
+            unobserve_last_origin: None,
+        };
+        return EngineResult::Ok(game_over_response(
+            game_after,
+            seeds,
+            state_before.as_ref(),
+            state,
+            outcome,
+            request.with_position_key,
+        ));
+    }
+
+    // Status info that takes up no space cannot say anything, so it is never sent.
+    let with_status_info = request.with_status_info && std::mem::size_of::<E::StatusInfo>() != 0;
+    let (proposed_move, info) = if !allowed.is_empty() {
+        match engine
+            .propose_move_restricted(produce_rand_used, &state, &game_after, &allowed)
+            .await
+        {
+            Ok((a, b)) => (a, with_status_info.then_some(b)),
+            Err(why) => return EngineResult::EngineError(why),
+        }
+    } else if !request.produce_rand_streams.is_empty() {
+        let source = RandomSource::with_streams(produce_rand_used, request.produce_rand_streams);
+        match engine
+            .propose_move_with_source(&source, &state, &game_after)
+            .await
+        {
+            Ok((a, b)) => (a, with_status_info.then_some(b)),
+            Err(why) => return EngineResult::EngineError(why),
+        }
+    } else if with_status_info {
+        match engine
+            .propose_move(produce_rand_used, &state, &game_after)
+            .await
+        {
+            Ok((a, b)) => (a, Some(b)),
+            Err(why) => return EngineResult::EngineError(why),
+        }
+    } else {
+        match engine
+            .propose_move_without_info(produce_rand_used, &state, &game_after)
+            .await
+        {
+            Ok(a) => (a, None),
+            Err(why) => return EngineResult::EngineError(why),
+        }
+    };
+
+    // Finally, observe our own move.
+
+    let (observe_own_rand_used, observe_own_origin) = SeedOrigin::resolve(request.observe_own_rand);
+    let within_restriction = allowed.is_empty() || allowed.contains(&proposed_move);
+    let (proposed_move, info, substituted) =
+        if within_restriction && game_after.is_legal(&proposed_move) {
+            (proposed_move, info, false)
+        } else {
+            let illegal = EngineRequestError::EngineSentIllegalMove {
+                r#move: proposed_move.to_uci(castling_mode),
+            };
+            match on_illegal_move {
+                IllegalMovePolicy::Reject => return EngineResult::RequestError(illegal),
+                IllegalMovePolicy::RandomLegal => {
+                    // The status info explains the engine's move, not this one, so it is dropped.
+                    let substitute = if allowed.is_empty() {
+                        random_legal_move(&game_after, produce_rand_used)
+                    } else {
+                        allowed
+                            .choose(&mut StdRng::seed_from_u64(produce_rand_used))
+                            .cloned()
+                    };
+                    match substitute {
+                        Some(m) => (m, None, true),
+                        None => return EngineResult::RequestError(illegal),
+                    }
+                }
+            }
+        };
+    // What the engine says about its move is checked before the engine observes it,
+    // so that an engine that fails these checks is not left believing that its move was played.
+    let summary = info.as_ref().map(E::summarize).unwrap_or_default();
+    let mate_line = match summary.score {
+        Some(Score::Mate(moves)) => {
+            if cfg!(debug_assertions) && !is_valid_mate_line(&game_after, moves, &summary.pv) {
+                return EngineResult::RequestError(EngineRequestError::EngineSentInvalidMateLine {
+                    line: summary.pv.iter().map(|m| m.to_uci(castling_mode)).collect(),
+                });
+            }
+            summary.pv.iter().map(|m| m.to_uci(castling_mode)).collect()
+        }
+        _ => Vec::new(),
+    };
+    if let Some(wdl) = summary.wdl {
+        if cfg!(debug_assertions) && !is_valid_wdl(wdl) {
+            return EngineResult::RequestError(EngineRequestError::EngineSentInvalidWdl { wdl });
+        }
+    }
+
+    let pv_positions = if request.with_pv_positions {
+        match line_positions(&game_after, &summary.pv) {
+            Ok(v) => v,
+            Err(index) => {
+                return EngineResult::RequestError(EngineRequestError::EngineSentIllegalPv {
+                    line: summary.pv[..=index]
+                        .iter()
+                        .map(|m| m.to_uci(castling_mode))
+                        .collect(),
+                });
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    let mut search_tree = summary.search_tree;
+    SearchTree::limit_nodes(&mut search_tree, SearchTree::MAX_NODES);
+    if let Some(r#move) = SearchTree::first_illegal_move(&search_tree, &game_after) {
+        return EngineResult::RequestError(EngineRequestError::EngineSentIllegalMove { r#move });
+    }
+    let mut game_after_mine = game_after.clone();
+    game_after_mine.play_unchecked(&proposed_move);
+    if let Err(why) = engine
+        .observe_move(
+            observe_own_rand_used,
+            &mut state,
+            &proposed_move,
+            &game_after_mine,
+        )
+        .await
+    {
+        return EngineResult::EngineError(why);
+    }
+    let position_transformed = match engine.transform_position(&state, &game_after_mine) {
+        Some(setup) => {
+            let fen = Fen::from_setup(setup.clone()).to_string();
+            match Chess::from_setup(setup, castling_mode) {
+                Ok(v) => game_after_mine = v,
+                Err(why) => {
+                    return EngineResult::RequestError(
+                        EngineRequestError::EngineSentIllegalPosition {
+                            fen,
+                            reason: why.to_string(),
+                        },
+                    )
+                }
+            }
+            true
+        }
+        None => false,
+    };
+
+    let castling_lost = castling_rights_lost(&game_after, &game_after_mine)
+        .into_iter()
+        .map(|(color, side)| CastlingRight { color, side })
+        .collect();
+    history.push(position_hash(&game_after));
+    let outcome = outcome(&game_after_mine, request.strict_rules, &history);
+    let requested_extra_time = engine.requested_extra_time(&state, &game_after_mine);
+    let (engine_state, engine_state_delta) = state_or_delta::<E>(state_before.as_ref(), state);
+
+    // Now that the move was produced and observed, construct a response.
+    EngineResult::Ok(EngineResponse {
+        r#move: proposed_move.to_uci(castling_mode),
+        game_after_hash: position_hash(&game_after_mine),
+        legal_move_count: game_after_mine.legal_moves().len(),
+        position_key: request
+            .with_position_key
+            .then(|| position_key(&game_after_mine)),
+        game_after: game_after_mine,
+        status_info: info,
+        status_info_truncated: false,
+        castling_lost,
+        seeds: SeedsUsed {
+            observe_other: observe_other_rand_used,
+            produce: Some(produce_rand_used),
+            observe_own: Some(observe_own_rand_used),
+            observe_other_origin,
+            produce_origin: Some(produce_origin),
+            observe_own_origin: Some(observe_own_origin),
+            unobserve_reply: None,
+            unobserve_last: None,
+            unobserve_reply_origin: None,
+            unobserve_last_origin: None,
+        },
+        engine_state,
+        engine_state_delta,
+        outcome,
+        requested_extra_time,
+        score: summary.score,
+        mate_line,
+        confidence: summary.confidence,
+        wdl: summary.wdl,
+        reasons: summary.reasons,
+        depth: summary.depth,
+        search_tree,
+        move_source: summary.source,
+        substituted,
+        position_transformed,
+        pv_positions,
+        build_hash: engine_info.build_hash,
+    })
+}
+
+/// Take back the last move, and the engine's reply to it if there was one, then play the replacement as a turn.
+///
+/// Everything is checked before the engine is called: the moves taken back must be legal where they were played,
+/// the replacement must be legal where the last move was played, and that position must match
+/// `expected_hash_before` and be one the engine [supports](Engine::supports_position).
+/// Each move taken back gets its own seed, and the response reports both with the rest of its [`SeedsUsed`].
+/// Whether the engine supports taking moves back is up to the caller to check;
+/// see [`Capabilities::supports_unobserve`](crate::server_types::Capabilities::supports_unobserve).
+///
+/// ```
+/// use engine_trait::{
+///     game::{amend_turn, play_turn, IllegalMovePolicy},
+///     server_types::{AmendRequest, EngineRequest, EngineResult, SeedOrigin},
+/// };
+/// # use engine_trait::{async_trait, server_types::EngineInfo, Engine, InfallibleError, NoStatus};
+/// # use shakmaty::{CastlingMode, Chess, Move, Position};
+/// # /// Remembers the moves it observed, and plays the first legal move.
+/// # struct Recorder;
+/// # #[async_trait]
+/// # impl Engine for Recorder {
+/// #     type State = Vec<String>;
+/// #     type StatusInfo = NoStatus;
+/// #     type Error = InfallibleError;
+/// #     fn get_info() -> EngineInfo<Self> {
+/// #         EngineInfo::new("recorder", "Remembers the moves it observed", Vec::new())
+/// #     }
+/// #     async fn propose_move(&mut self, _: u64, _: &Vec<String>, position: &Chess) -> Result<(Move, NoStatus), InfallibleError> {
+/// #         Ok((position.legal_moves()[0].clone(), NoStatus))
+/// #     }
+/// #     async fn observe_move(&mut self, _: u64, state: &mut Vec<String>, m: &Move, _: &Chess) -> Result<(), InfallibleError> {
+/// #         state.push(m.to_uci(CastlingMode::Standard).to_string());
+/// #         Ok(())
+/// #     }
+/// #     async fn unobserve_move(&mut self, _: u64, state: &mut Vec<String>, _: &Move, _: &Chess) -> Result<(), InfallibleError> {
+/// #         state.pop();
+/// #         Ok(())
+/// #     }
+/// # }
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let request: EngineRequest<Recorder> =
+///     serde_json::from_value(serde_json::json!({ "move": "e2e4", "with_status_info": false })).unwrap();
+/// let EngineResult::Ok(response) = play_turn(&mut Recorder, request).await else { panic!() };
+/// let reply = response.r#move.to_string();
+/// let state = response.engine_state.unwrap();
+/// assert_eq!(state, ["e2e4", reply.as_str()]);
+///
+/// // The player meant d4, so e4 and the reply to it are taken back.
+/// let amend: AmendRequest<Recorder> = serde_json::from_value(serde_json::json!({
+///     "last_move": "e2e4",
+///     "engine_reply": reply,
+///     "move": "d2d4",
+///     "engine_state": state,
+///     "with_status_info": false,
+///     "unobserve_reply_rand": 7,
+/// }))
+/// .unwrap();
+/// let EngineResult::Ok(response) = amend_turn(&mut Recorder, amend, IllegalMovePolicy::Reject).await else {
+///     panic!()
+/// };
+/// assert_eq!(response.engine_state.unwrap()[0], "d2d4");
+/// assert_eq!(response.seeds.unobserve_reply, Some(7));
+/// assert_eq!(response.seeds.unobserve_reply_origin, Some(SeedOrigin::Provided));
+/// assert_eq!(response.seeds.unobserve_last_origin, Some(SeedOrigin::Generated));
+/// # });
+/// ```
+pub async fn amend_turn<E: Engine>(
+    engine: &mut E,
+    amend: AmendRequest<E>,
+    on_illegal_move: IllegalMovePolicy,
+) -> EngineResult<E> {
+    let mut request = amend.request;
+    let game_before = match request.resolved_game_before() {
+        Ok(v) => v,
+        Err(why) => return EngineResult::RequestError(why),
+    };
+    let (Ok(last_move), Ok(_)) = (
+        amend.last_move.to_move(&game_before),
+        request.r#move.to_move(&game_before),
+    ) else {
+        return EngineResult::RequestError(EngineRequestError::PositionMoveMismatch);
+    };
+    let mut after_last = game_before.clone();
+    after_last.play_unchecked(&last_move);
+    let reply = match amend.engine_reply.map(|uci| uci.to_move(&after_last)) {
+        None => None,
+        Some(Ok(m)) => Some(m),
+        Some(Err(_)) => {
+            return EngineResult::RequestError(EngineRequestError::PositionMoveMismatch);
+        }
+    };
+
+    // The engine must not be asked to take moves back to a position that it would refuse to play from.
+    if let Err(why) = verify_hash(
+        DesyncStep::GameBefore,
+        request.expected_hash_before,
+        &game_before,
+    )
+    .and_then(|()| check_supported(engine, &game_before))
+    {
+        return EngineResult::RequestError(why);
+    }
+
+    let mut unobserve_reply = None;
+    if let Some(reply) = reply {
+        let (rand, origin) = SeedOrigin::resolve(amend.unobserve_reply_rand);
+        if let Err(why) = engine
+            .unobserve_move(rand, &mut request.engine_state, &reply, &after_last)
+            .await
+        {
+            return EngineResult::EngineError(why);
+        }
+        unobserve_reply = Some((rand, origin));
+    }
+    let (unobserve_last, unobserve_last_origin) = SeedOrigin::resolve(amend.unobserve_last_rand);
+    if let Err(why) = engine
+        .unobserve_move(
+            unobserve_last,
+            &mut request.engine_state,
+            &last_move,
+            &game_before,
+        )
+        .await
+    {
+        return EngineResult::EngineError(why);
+    }
+    let mut result = play_turn_with(engine, request, on_illegal_move).await;
+    if let EngineResult::Ok(response) = &mut result {
+        response.seeds.unobserve_reply = unobserve_reply.map(|(rand, _)| rand);
+        response.seeds.unobserve_reply_origin = unobserve_reply.map(|(_, origin)| origin);
+        response.seeds.unobserve_last = Some(unobserve_last);
+        response.seeds.unobserve_last_origin = Some(unobserve_last_origin);
+    }
+    result
+}
+
+/// Whether `pv` is a legal line that ends in checkmate after exactly as many moves as `Mate(moves)` claims.
+fn is_valid_mate_line(position: &Chess, moves: i32, pv: &[Move]) -> bool {
+    let moves = i64::from(moves);
+    // The side to move mates with its own last move; otherwise, it is mated after the opponent's.
+    let plies = if moves > 0 { 2 * moves - 1 } else { -2 * moves };
+    pv.len() as i64 == plies && is_mating_line(position, pv)
+}
+
+/// Whether win, draw and loss probabilities are all non-negative and sum to 1, allowing for rounding.
+fn is_valid_wdl((win, draw, loss): (f32, f32, f32)) -> bool {
+    [win, draw, loss].iter().all(|&p| p >= 0.0) && (win + draw + loss - 1.0).abs() < 0.01
+}
+
+/// The response to a turn in which the game ended without the engine moving.
+fn game_over_response<E: Engine>(
+    game_after: Chess,
+    seeds: SeedsUsed,
+    state_before: Option<&E::State>,
+    state: E::State,
+    outcome: GameOutcome,
+    with_position_key: bool,
+) -> EngineResponse<E> {
+    let (engine_state, engine_state_delta) = state_or_delta::<E>(state_before, state);
+    EngineResponse {
+        r#move: Uci::Null,
+        game_after_hash: position_hash(&game_after),
+        legal_move_count: game_after.legal_moves().len(),
+        position_key: with_position_key.then(|| position_key(&game_after)),
+        game_after,
+        status_info: None,
+        status_info_truncated: false,
+        castling_lost: Vec::new(),
+        seeds,
+        engine_state,
+        engine_state_delta,
+        outcome: Some(outcome),
+        requested_extra_time: None,
+        score: None,
+        mate_line: Vec::new(),
+        confidence: None,
+        wdl: None,
+        reasons: Vec::new(),
+        depth: None,
+        search_tree: Vec::new(),
+        move_source: MoveSource::default(),
+        substituted: false,
+        position_transformed: false,
+        pv_positions: Vec::new(),
+        build_hash: E::get_info().build_hash,
+    }
+}
+
+/// How the game ended in `position`, if it is over, following the strict rules if asked to.
+fn outcome(position: &Chess, strict_rules: bool, history: &[u64]) -> Option<GameOutcome> {
+    if strict_rules {
+        strict_game_outcome(position, history)
+    } else {
+        game_outcome(position)
+    }
+}
+
+/// Send only the change to the state, if the client asked for it and the engine supports it.
+fn state_or_delta<E: Engine>(
+    before: Option<&E::State>,
+    after: E::State,
+) -> (Option<E::State>, Option<Value>) {
+    match before.and_then(|before| E::state_delta(before, &after)) {
+        Some(delta) => (None, Some(delta)),
+        None => (Some(after), None),
+    }
+}
+
+/// Turn the request away if the engine cannot play in `position`. See [`Engine::supports_position`].
+pub(crate) fn check_supported<E: Engine>(
+    engine: &E,
+    position: &Chess,
+) -> Result<(), EngineRequestError> {
+    engine
+        .supports_position(position)
+        .map_err(|reason| EngineRequestError::UnsupportedPosition { reason })
+}
+
+/// Check that a position has the hash the request expects, if it expects one.
+fn verify_hash(
+    step: DesyncStep,
+    expected: Option<u64>,
+    position: &Chess,
+) -> Result<(), EngineRequestError> {
+    let actual = position_hash(position);
+    match expected {
+        Some(expected) if expected != actual => Err(EngineRequestError::StateDesync {
+            step,
+            expected,
+            actual,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Ask the engine which move it would play, without playing it.
+///
+/// This only proposes a move: nothing is observed, so the engine's state and the position are left as they were.
+/// There is no hint once the game is over, and a move that is not legal is reported as the engine's bug,
+/// just as for a turn.
+///
+/// ```
+/// use engine_trait::{
+///     game::{hint, TurnError},
+///     server_types::{EngineRequestError, HintRequest, SeedOrigin},
+///     shakmaty::{fen::Fen, CastlingMode, Chess, Position},
+/// };
+/// # use engine_trait::fixtures::FirstMove;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let position: Chess = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"
+///     .parse::<Fen>()
+///     .unwrap()
+///     .into_position(CastlingMode::Standard)
+///     .unwrap();
+/// let request = HintRequest::<FirstMove> {
+///     position: position.clone(),
+///     engine_state: (),
+///     rand: Some(7),
+/// };
+/// let response = hint(&mut FirstMove, request).await.unwrap();
+/// assert_eq!(response.position, position);
+/// assert_eq!(response.position.halfmoves(), 2);
+/// assert_eq!(response.position.fullmoves().get(), 3);
+/// assert_eq!((response.rand_used, response.rand_origin), (7, Some(SeedOrigin::Provided)));
+///
+/// // Fool's mate: white is checkmated, and has nothing to play.
+/// let mated: Chess = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3"
+///     .parse::<Fen>()
+///     .unwrap()
+///     .into_position(CastlingMode::Standard)
+///     .unwrap();
+/// let request = HintRequest::<FirstMove> { position: mated, engine_state: (), rand: None };
+/// let why = hint(&mut FirstMove, request).await.unwrap_err();
+/// assert!(matches!(why, TurnError::RequestError(EngineRequestError::GameAlreadyOver { .. })));
+/// # });
+/// ```
+pub async fn hint<E: Engine>(
+    engine: &mut E,
+    request: HintRequest<E>,
+) -> Result<HintResponse, TurnError<E>> {
+    if let Some(outcome) = game_outcome(&request.position) {
+        return Err(TurnError::RequestError(
+            EngineRequestError::GameAlreadyOver { outcome },
+        ));
+    }
+    check_supported(engine, &request.position).map_err(TurnError::RequestError)?;
+    let mut state = request.engine_state;
+    engine.normalize_state(&mut state, &request.position);
+    let (rand_used, rand_origin) = SeedOrigin::resolve(request.rand);
+    let m = engine
+        .propose_move_without_info(rand_used, &state, &request.position)
+        .await
+        .map_err(TurnError::EngineError)?;
+    let castling_mode = request.position.castles().mode();
+    if !request.position.is_legal(&m) {
+        return Err(TurnError::RequestError(
+            EngineRequestError::EngineSentIllegalMove {
+                r#move: m.to_uci(castling_mode),
+            },
+        ));
+    }
+    Ok(HintResponse {
+        r#move: m.to_uci(castling_mode),
+        position: request.position,
+        rand_used,
+        rand_origin: Some(rand_origin),
+    })
+}
+
+/// Ask the engine for a small search tree of the position with [`Engine::analyze_tree`],
+/// cut down to the request's `breadth` and `depth`. Nothing about the game or the engine's state changes.
+///
+/// As with [`hint`], there is no tree once the game is over, and a move anywhere in the tree
+/// that is not legal where it is played is reported as the engine's bug.
+///
+/// ```
+/// use engine_trait::{
+///     game::{search_tree, TurnError},
+///     server_types::{EngineRequestError, SearchTree, TreeRequest},
+///     shakmaty::{fen::Fen, CastlingMode, Chess},
+/// };
+/// # use engine_trait::{async_trait, server_types::EngineInfo, Engine, InfallibleError, NoStatus};
+/// # use shakmaty::{Move, Position};
+/// # /// Answers every move with the same reply, whether or not it is legal.
+/// # struct Stubborn;
+/// # #[async_trait]
+/// # impl Engine for Stubborn {
+/// #     type State = ();
+/// #     type StatusInfo = NoStatus;
+/// #     type Error = InfallibleError;
+/// #     fn get_info() -> EngineInfo<Self> { EngineInfo::new("stubborn", "Always replies e7e5", ()) }
+/// #     async fn propose_move(&mut self, _: u64, _: &(), position: &Chess) -> Result<(Move, NoStatus), InfallibleError> {
+/// #         Ok((position.legal_moves()[0].clone(), NoStatus))
+/// #     }
+/// #     async fn observe_move(&mut self, _: u64, _: &mut (), _: &Move, _: &Chess) -> Result<(), InfallibleError> { Ok(()) }
+/// #     async fn analyze_tree(&mut self, _: u64, _: &(), _: &Chess, _: usize, _: usize) -> Result<Vec<SearchTree>, InfallibleError> {
+/// #         let leaf = |uci: &str| SearchTree { r#move: uci.parse().unwrap(), score: None, children: Vec::new() };
+/// #         Ok(vec![SearchTree { children: vec![leaf("e7e5")], ..leaf("e2e4") }, SearchTree { children: vec![leaf("e7e5")], ..leaf("e7e5") }])
+/// #     }
+/// # }
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// // The tree is cut to one move before it is checked, so the illegal second move goes unnoticed.
+/// let request = TreeRequest::<Stubborn> { position: Chess::new(), engine_state: (), rand: None, breadth: 1, depth: 2 };
+/// let response = search_tree(&mut Stubborn, request).await.unwrap();
+/// assert_eq!(response.tree[0].children[0].r#move.to_string(), "e7e5");
+///
+/// let request = TreeRequest::<Stubborn> { position: Chess::new(), engine_state: (), rand: None, breadth: 2, depth: 2 };
+/// let why = search_tree(&mut Stubborn, request).await.unwrap_err();
+/// assert!(matches!(
+///     why,
+///     TurnError::RequestError(EngineRequestError::EngineSentIllegalMove { r#move }) if r#move.to_string() == "e7e5"
+/// ));
+///
+/// // Fool's mate: white is checkmated, and has no moves to search.
+/// let mated: Chess = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3"
+///     .parse::<Fen>()
+///     .unwrap()
+///     .into_position(CastlingMode::Standard)
+///     .unwrap();
+/// let request = TreeRequest::<Stubborn> { position: mated, engine_state: (), rand: None, breadth: 2, depth: 2 };
+/// let why = search_tree(&mut Stubborn, request).await.unwrap_err();
+/// assert!(matches!(why, TurnError::RequestError(EngineRequestError::GameAlreadyOver { .. })));
+/// # });
+/// ```
+pub async fn search_tree<E: Engine>(
+    engine: &mut E,
+    request: TreeRequest<E>,
+) -> Result<TreeResponse, TurnError<E>> {
+    if let Some(outcome) = game_outcome(&request.position) {
+        return Err(TurnError::RequestError(
+            EngineRequestError::GameAlreadyOver { outcome },
+        ));
+    }
+    check_supported(engine, &request.position).map_err(TurnError::RequestError)?;
+    let mut state = request.engine_state;
+    engine.normalize_state(&mut state, &request.position);
+    let (rand_used, rand_origin) = SeedOrigin::resolve(request.rand);
+    let mut tree = engine
+        .analyze_tree(
+            rand_used,
+            &state,
+            &request.position,
+            request.breadth,
+            request.depth,
+        )
+        .await
+        .map_err(TurnError::EngineError)?;
+    SearchTree::prune(&mut tree, request.breadth, request.depth);
+    if let Some(r#move) = SearchTree::first_illegal_move(&tree, &request.position) {
+        return Err(TurnError::RequestError(
+            EngineRequestError::EngineSentIllegalMove { r#move },
+        ));
+    }
+    Ok(TreeResponse {
+        tree,
+        breadth: request.breadth,
+        depth: request.depth,
+        rand_used,
+        rand_origin: Some(rand_origin),
+    })
+}
+
+/// The move that the engine would play as the opponent of the side to move, if the turn were passed:
+/// what the opponent threatens. Nothing about the game or the engine's state changes.
+///
+/// The engine is given the position with the turn passed, and the request's state as it is,
+/// and a threat that is not legal in that position is reported as the engine's bug.
+/// As with [`hint`], there is no threat once the game is over.
+/// Passing is not legal when the side to move is in check, so then there is no threat move;
+/// see [`ThreatMethod::InCheck`].
+///
+/// ```
+/// use engine_trait::{
+///     game::{threat_move, TurnError},
+///     server_types::{EngineRequestError, HintRequest, ThreatMethod},
+///     shakmaty::{fen::Fen, CastlingMode, Chess},
+/// };
+/// # use engine_trait::{async_trait, server_types::EngineInfo, Engine, InfallibleError, NoStatus};
+/// # use shakmaty::{Move, Position};
+/// # /// Captures the most valuable piece it can, or plays its first move.
+/// # struct Greedy;
+/// # #[async_trait]
+/// # impl Engine for Greedy {
+/// #     type State = ();
+/// #     type StatusInfo = NoStatus;
+/// #     type Error = InfallibleError;
+/// #     fn get_info() -> EngineInfo<Self> { EngineInfo::new("greedy", "Captures what it can", ()) }
+/// #     async fn propose_move(&mut self, _: u64, _: &(), position: &Chess) -> Result<(Move, NoStatus), InfallibleError> {
+/// #         let moves = position.legal_moves();
+/// #         let best = moves.iter().max_by_key(|m| m.capture().map(|r| r as u8)).unwrap();
+/// #         Ok((best.clone(), NoStatus))
+/// #     }
+/// #     async fn observe_move(&mut self, _: u64, _: &mut (), _: &Move, _: &Chess) -> Result<(), InfallibleError> { Ok(()) }
+/// # }
+///
+/// let position = |fen: &str| -> Chess {
+///     fen.parse::<Fen>().unwrap().into_position(CastlingMode::Standard).unwrap()
+/// };
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// // White to move, and black's knight threatens to take the queen.
+/// let request = HintRequest::<Greedy> {
+///     position: position("4k3/8/8/2n5/8/3Q4/8/4K3 w - - 0 1"),
+///     engine_state: (),
+///     rand: None,
+/// };
+/// let response = threat_move(&mut Greedy, request).await.unwrap();
+/// assert_eq!(response.threat.unwrap().to_string(), "c5d3");
+/// assert_eq!(response.method, ThreatMethod::NullMove);
+///
+/// // In check, white cannot pass; the threat is the check itself.
+/// let request = HintRequest::<Greedy> {
+///     position: position("r3k3/8/8/8/8/5n2/8/4K3 w - - 0 1"),
+///     engine_state: (),
+///     rand: None,
+/// };
+/// let response = threat_move(&mut Greedy, request).await.unwrap();
+/// assert_eq!(response.threat, None);
+/// assert_eq!(response.method, ThreatMethod::InCheck);
+///
+/// // Stalemate: the game is over, even though black would have moves if white could pass.
+/// let request = HintRequest::<Greedy> {
+///     position: position("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"),
+///     engine_state: (),
+///     rand: None,
+/// };
+/// let why = threat_move(&mut Greedy, request).await.unwrap_err();
+/// assert!(matches!(why, TurnError::RequestError(EngineRequestError::GameAlreadyOver { .. })));
+/// # });
+/// ```
+pub async fn threat_move<E: Engine>(
+    engine: &mut E,
+    request: HintRequest<E>,
+) -> Result<ThreatMoveResponse, TurnError<E>> {
+    if let Some(outcome) = game_outcome(&request.position) {
+        return Err(TurnError::RequestError(
+            EngineRequestError::GameAlreadyOver { outcome },
+        ));
+    }
+    let mut state = request.engine_state;
+    engine.normalize_state(&mut state, &request.position);
+    let (rand_used, rand_origin) = SeedOrigin::resolve(request.rand);
+    let Ok(passed) = request.position.swap_turn() else {
+        return Ok(ThreatMoveResponse {
+            threat: None,
+            method: ThreatMethod::InCheck,
+            rand_used,
+            rand_origin: Some(rand_origin),
+        });
+    };
+    let threat = if passed.legal_moves().is_empty() {
+        None
+    } else {
+        check_supported(engine, &passed).map_err(TurnError::RequestError)?;
+        let m = engine
+            .propose_move_without_info(rand_used, &state, &passed)
+            .await
+            .map_err(TurnError::EngineError)?;
+        let uci = m.to_uci(passed.castles().mode());
+        if !passed.is_legal(&m) {
+            return Err(TurnError::RequestError(
+                EngineRequestError::EngineSentIllegalMove { r#move: uci },
+            ));
+        }
+        Some(uci)
+    };
+    Ok(ThreatMoveResponse {
+        threat,
+        method: ThreatMethod::NullMove,
+        rand_used,
+        rand_origin: Some(rand_origin),
+    })
+}
+
+/// Why a [`GameSession`] could not play a move, or the engine could not answer a question like [`hint`].
+pub enum TurnError<E: Engine> {
+    RequestError(EngineRequestError),
+    EngineError(E::Error),
+}
+
+// Deriving these would needlessly require the engine itself to implement them.
+impl<E: Engine> Clone for TurnError<E> {
+    fn clone(&self) -> Self {
+        match self {
+            TurnError::RequestError(why) => TurnError::RequestError(why.clone()),
+            TurnError::EngineError(why) => TurnError::EngineError(why.clone()),
+        }
+    }
+}
+
+impl<E: Engine> Debug for TurnError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TurnError::RequestError(why) => f.debug_tuple("RequestError").field(why).finish(),
+            TurnError::EngineError(why) => f.debug_tuple("EngineError").field(why).finish(),
+        }
+    }
+}
+
+/// A game in progress against one engine, stepped one move at a time.
+///
+/// The session owns the engine, the position and the engine's state,
+/// so a caller can inspect the game and run its own logic between moves.
+/// The engine can play either side, or both: [`GameSession::next_move`] has it move for the side to move,
+/// and [`GameSession::play_move`] plays a move for it to observe.
+///
+/// ```
+/// use engine_trait::{
+///     async_trait, game::GameSession,
+///     server_types::EngineInfo,
+///     shakmaty::{Chess, Move, Position},
+///     Engine, InfallibleError, NoStatus,
+/// };
+///
+/// /// Plays the first legal move, and counts the moves it has seen.
+/// struct FirstMove;
+///
+/// #[async_trait]
+/// impl Engine for FirstMove {
+///     type State = u32;
+///     type StatusInfo = NoStatus;
+///     type Error = InfallibleError;
+///
+///     fn get_info() -> EngineInfo<Self> {
+///         EngineInfo::new("first-move", "Plays the first legal move", 0)
+///     }
+///
+///     async fn propose_move(
+///         &mut self,
+///         _rand: u64,
+///         _state: &u32,
+///         position: &Chess,
+///     ) -> Result<(Move, NoStatus), InfallibleError> {
+///         Ok((position.legal_moves()[0].clone(), NoStatus))
+///     }
+///
+///     async fn observe_move(
+///         &mut self,
+///         _rand: u64,
+///         state: &mut u32,
+///         _move_taken: &Move,
+///         _position_after: &Chess,
+///     ) -> Result<(), InfallibleError> {
+///         *state += 1;
+///         Ok(())
+///     }
+/// }
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let mut session = GameSession::new(FirstMove, Chess::new());
+/// session.play_move(1, &"e2e4".parse().unwrap()).await.unwrap();
+/// let response = session.next_move(2).await.unwrap();
+/// assert_eq!(session.position(), &response.game_after);
+/// assert_eq!(session.position().fullmoves().get(), 2);
+/// assert_eq!(*session.state(), 2);
+///
+/// // The engine can also answer its own moves.
+/// session.next_move(3).await.unwrap();
+/// assert_eq!(*session.state(), 3);
+/// # });
+/// ```
+pub struct GameSession<E: Engine> {
+    engine: E,
+    position: Chess,
+    state: E::State,
+    strict_rules: bool,
+    /// The hashes of the positions before `position`, for detecting repetitions.
+    history: Vec<u64>,
+}
+
+impl<E: Engine> GameSession<E> {
+    /// Start a game from `position`, with the engine's initial state.
+    pub fn new(engine: E, position: Chess) -> GameSession<E> {
+        GameSession {
+            engine,
+            position,
+            state: E::get_info().initial_state,
+            strict_rules: false,
+            history: Vec::new(),
+        }
+    }
+
+    /// End the game automatically in a draw on fivefold repetition or the 75-move rule.
+    /// See [`EngineRequest::strict_rules`].
+    pub fn with_strict_rules(mut self) -> GameSession<E> {
+        self.strict_rules = true;
+        self
+    }
+
+    /// The current position.
+    pub fn position(&self) -> &Chess {
+        &self.position
+    }
+
+    /// The engine's current state.
+    pub fn state(&self) -> &E::State {
+        &self.state
+    }
+
+    /// The engine, for inspecting or adjusting it between moves.
+    pub fn engine_mut(&mut self) -> &mut E {
+        &mut self.engine
+    }
+
+    /// End the session, giving back the engine.
+    pub fn into_engine(self) -> E {
+        self.engine
+    }
+
+    /// Play a move that the engine did not choose, and have the engine observe it with the seed `rand`.
+    ///
+    /// Nothing changes if the move is illegal, the engine does not support the position after it,
+    /// or the engine fails to observe it.
+    pub async fn play_move(&mut self, rand: u64, uci: &Uci) -> Result<(), TurnError<E>> {
+        let m = uci
+            .to_move(&self.position)
+            .map_err(|_| TurnError::RequestError(EngineRequestError::PositionMoveMismatch))?;
+        let mut position = self.position.clone();
+        position.play_unchecked(&m);
+        check_supported(&self.engine, &position).map_err(TurnError::RequestError)?;
+        let mut state = self.state.clone();
+        self.engine
+            .observe_move(rand, &mut state, &m, &position)
+            .await
+            .map_err(TurnError::EngineError)?;
+        self.history.push(position_hash(&std::mem::replace(
+            &mut self.position,
+            position,
+        )));
+        self.state = state;
+        Ok(())
+    }
+
+    /// Have the engine move for the side to move, just like it would in a move request,
+    /// with `rand` as the seed for both proposing the move and observing it.
+    ///
+    /// If the game is already over, the response has a null move and says how the game ended.
+    pub async fn next_move(&mut self, rand: u64) -> Result<EngineResponse<E>, TurnError<E>> {
+        let request = EngineRequest::<E> {
+            r#move: Uci::Null,
+            game_before: self.position.clone(),
+            chess960_sp: None,
+            castling_mode: None,
+            engine_state: self.state.clone(),
+            observe_other_rand: None,
+            produce_rand: Some(rand),
+            produce_rand_streams: Vec::new(),
+            observe_own_rand: Some(rand),
+            with_status_info: true,
+            game_id: None,
+            restrict_to: Vec::new(),
+            expected_hash_before: None,
+            expected_hash_after_move: None,
+            with_pv_positions: false,
+            with_position_key: false,
+            strength: None,
+            with_state_delta: false,
+            strict_rules: self.strict_rules,
+            position_history: self.history.clone(),
+            protocol_version: None,
+        };
+        let response = match play_turn(&mut self.engine, request).await {
+            EngineResult::Ok(response) => response,
+            EngineResult::RequestError(why) => return Err(TurnError::RequestError(why)),
+            EngineResult::EngineError(why) => return Err(TurnError::EngineError(why)),
+        };
+        if response.r#move != Uci::Null {
+            self.history.push(position_hash(&self.position));
+            self.position = response.game_after.clone();
+        }
+        self.state = response
+            .engine_state
+            .clone()
+            .expect("the full state is sent when no delta is requested");
+        Ok(response)
+    }
+}
+
+/// Why [`play_game`] stopped: the turn that failed, for the side whose engine it was.
+pub enum GameError<W: Engine, B: Engine> {
+    White(TurnError<W>),
+    Black(TurnError<B>),
+}
+
+impl<W: Engine, B: Engine> Debug for GameError<W, B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameError::White(why) => f.debug_tuple("White").field(why).finish(),
+            GameError::Black(why) => f.debug_tuple("Black").field(why).finish(),
+        }
+    }
+}
+
+/// Play a game to the end between the engines of two sessions, which must be in the same position.
+///
+/// Each engine moves for its own color and observes the other's moves. The seed of ply `i`
+/// is [`RandomSource::stream_seed`](crate::random::RandomSource::stream_seed)`(i)` of `rand`.
+/// Start the sessions [`with_strict_rules`](GameSession::with_strict_rules) to be sure that the game ends,
+/// since the 75-move rule then limits its length.
+///
+/// ```
+/// use engine_trait::{
+///     game::{play_game, GameSession},
+///     lookup::LookupEngine,
+///     shakmaty::{uci::Uci, Chess},
+/// };
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let mut white = GameSession::new(LookupEngine::new(), Chess::new()).with_strict_rules();
+/// let mut black = GameSession::new(LookupEngine::new(), Chess::new()).with_strict_rules();
+/// let outcome = play_game(&mut white, &mut black, 1).await.unwrap();
+/// assert_eq!(white.position(), black.position());
+///
+/// // Either engine now only reports how the game ended.
+/// let response = white.next_move(2).await.unwrap();
+/// assert_eq!(response.r#move, Uci::Null);
+/// assert_eq!(response.outcome, Some(outcome));
+/// # });
+/// ```
+pub async fn play_game<W: Engine, B: Engine>(
+    white: &mut GameSession<W>,
+    black: &mut GameSession<B>,
+    rand: u64,
+) -> Result<GameOutcome, GameError<W, B>> {
+    let seeds = RandomSource::new(rand);
+    let mut ply = 0;
+    loop {
+        let rand = seeds.stream_seed(ply);
+        ply += 1;
+        let outcome = match white.position().turn() {
+            Color::White => {
+                let response = white.next_move(rand).await.map_err(GameError::White)?;
+                if response.r#move != Uci::Null {
+                    black
+                        .play_move(rand, &response.r#move)
+                        .await
+                        .map_err(GameError::Black)?;
+                }
+                response.outcome
+            }
+            Color::Black => {
+                let response = black.next_move(rand).await.map_err(GameError::Black)?;
+                if response.r#move != Uci::Null {
+                    white
+                        .play_move(rand, &response.r#move)
+                        .await
+                        .map_err(GameError::White)?;
+                }
+                response.outcome
+            }
+        };
+        if let Some(outcome) = outcome {
+            return Ok(outcome);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! An audit log that writes every completed game to a PGN file.
+//!
+//! The server does not keep games between requests, so the log follows each game by its `game_id`,
+//! and appends it to the file once the game is over.
+//! Files are written on a thread of their own, so that a slow disk does not hold up the server's turns.
+
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex},
+    time::Instant,
+};
+
+use shakmaty::{
+    fen::Fen, san::SanPlus, uci::Uci, CastlingMode, Chess, Color, EnPassantMode, Position,
+};
+
+use crate::server_types::GameOutcome;
+
+/// A game that is still in progress.
+struct LoggedGame {
+    start: Chess,
+    current: Chess,
+    sans: Vec<SanPlus>,
+    engine_color: Option<Color>,
+    last_turn: Instant,
+}
+
+/// How many unfinished games the log follows at once.
+/// Past this, the game that has gone the longest without a turn is dropped, as clients often abandon games.
+pub(crate) const MAX_LOGGED_GAMES: usize = 10_000;
+
+pub(crate) struct GameLog {
+    games: Mutex<HashMap<String, LoggedGame>>,
+    writer: mpsc::Sender<String>,
+}
+
+/// Whether `game_id` can be logged: it ends up in a PGN header, which cannot hold control characters.
+pub(crate) fn valid_game_id(game_id: &str) -> bool {
+    !game_id.chars().any(char::is_control)
+}
+
+impl GameLog {
+    pub(crate) fn new(path: PathBuf) -> GameLog {
+        let (writer, pgns) = mpsc::channel::<String>();
+        std::thread::Builder::new()
+            .name("game-log".to_string())
+            .spawn(move || {
+                for pgn in pgns {
+                    // The audit log must never take a game down with it, so failing to write is not reported to the client.
+                    let _ = append(&path, &pgn);
+                }
+            })
+            .expect("cannot start the game log writer");
+        GameLog {
+            games: Mutex::new(HashMap::new()),
+            writer,
+        }
+    }
+
+    /// Record one turn of a game: the other side's move, and the engine's reply.
+    ///
+    /// If `game_before` is not where the logged game currently stands, either the client amended a move,
+    /// and the game is taken back to `game_before`, or the client restarted it, and logging starts over from there.
+    pub(crate) fn record_turn(
+        &self,
+        engine_id: &str,
+        game_id: &str,
+        game_before: &Chess,
+        their_move: &Uci,
+        engine_move: &Uci,
+        outcome: Option<&GameOutcome>,
+    ) {
+        let mut games = self.games.lock().unwrap_or_else(|e| e.into_inner());
+        if games.len() >= MAX_LOGGED_GAMES && !games.contains_key(game_id) {
+            let stalest = games
+                .iter()
+                .min_by_key(|(_, game)| game.last_turn)
+                .map(|(id, _)| id.clone());
+            if let Some(id) = stalest {
+                games.remove(&id);
+            }
+        }
+        let game = games
+            .entry(game_id.to_string())
+            .and_modify(|game| {
+                if !same_position(&game.current, game_before) && !game.rewind_to(game_before) {
+                    *game = LoggedGame::starting_at(game_before);
+                }
+            })
+            .or_insert_with(|| LoggedGame::starting_at(game_before));
+
+        game.last_turn = Instant::now();
+        game.play(their_move);
+        if *engine_move != Uci::Null {
+            game.engine_color = Some(game.current.turn());
+            game.play(engine_move);
+        }
+
+        if let Some(outcome) = outcome {
+            let game = games.remove(game_id).expect("the game was just inserted");
+            let _ = self.writer.send(game.to_pgn(engine_id, game_id, outcome));
+        }
+    }
+}
+
+fn append(path: &Path, pgn: &str) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(pgn.as_bytes())
+}
+
+impl LoggedGame {
+    fn starting_at(position: &Chess) -> LoggedGame {
+        LoggedGame {
+            start: position.clone(),
+            current: position.clone(),
+            sans: Vec::new(),
+            engine_color: None,
+            last_turn: Instant::now(),
+        }
+    }
+
+    fn play(&mut self, uci: &Uci) {
+        if let Ok(m) = uci.to_move(&self.current) {
+            self.sans
+                .push(SanPlus::from_move_and_play_unchecked(&mut self.current, &m));
+        }
+    }
+
+    /// Take moves back until the game stands at `position`. False if the game never reached it.
+    fn rewind_to(&mut self, position: &Chess) -> bool {
+        let mut replayed = self.start.clone();
+        for ply in 0..=self.sans.len() {
+            if same_position(&replayed, position) {
+                self.sans.truncate(ply);
+                self.current = replayed;
+                return true;
+            }
+            match self.sans.get(ply).map(|san| san.san.to_move(&replayed)) {
+                Some(Ok(m)) => replayed.play_unchecked(&m),
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    fn to_pgn(&self, engine_id: &str, game_id: &str, outcome: &GameOutcome) -> String {
+        let result = outcome
+            .result()
+            .map_or_else(|| "*".to_string(), |v| v.to_string());
+        let game_id = escape_tag(game_id);
+        let player = |color| {
+            if self.engine_color == Some(color) {
+                engine_id
+            } else {
+                "?"
+            }
+        };
+
+        let mut pgn = format!(
+            "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n[White \"{}\"]\n[Black \"{}\"]\n[Result \"{result}\"]\n[GameId \"{game_id}\"]\n",
+            player(Color::White),
+            player(Color::Black),
+        );
+        if !same_position(&self.start, &Chess::new()) {
+            let fen = Fen::from_position(self.start.clone(), EnPassantMode::Legal);
+            pgn.push_str(&format!("[SetUp \"1\"]\n[FEN \"{fen}\"]\n"));
+            if self.start.castles().mode() == CastlingMode::Chess960 {
+                pgn.push_str("[Variant \"Chess960\"]\n");
+            }
+        }
+        pgn.push('\n');
+
+        let mut turn = self.start.turn();
+        let mut fullmoves = self.start.fullmoves().get();
+        for (i, san) in self.sans.iter().enumerate() {
+            match turn {
+                Color::White => pgn.push_str(&format!("{fullmoves}. ")),
+                Color::Black if i == 0 => pgn.push_str(&format!("{fullmoves}... ")),
+                Color::Black => {}
+            }
+            pgn.push_str(&format!("{san} "));
+            if turn == Color::Black {
+                fullmoves += 1;
+            }
+            turn = !turn;
+        }
+        pgn.push_str(&format!("{result}\n\n"));
+        pgn
+    }
+}
+
+/// Escape a PGN tag value, which is quoted, and takes backslashes as escapes.
+fn escape_tag(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn same_position(a: &Chess, b: &Chess) -> bool {
+    Fen::from_position(a.clone(), EnPassantMode::Legal)
+        == Fen::from_position(b.clone(), EnPassantMode::Legal)
+}
@@ -0,0 +1,140 @@
+//! Playing one engine against several opponents in-process, the usual way of testing an engine's strength.
+//!
+//! ```
+//! use engine_trait::{gauntlet::run_gauntlet, lookup::LookupEngine};
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let opponents = vec![LookupEngine::new(), LookupEngine::new()];
+//! let table = run_gauntlet(LookupEngine::new(), opponents, 2, 7).await.unwrap();
+//! assert_eq!(table.len(), 2);
+//! for line in &table {
+//!     assert_eq!(line.games(), 2);
+//!     assert!((0.0..=2.0).contains(&line.score()));
+//! }
+//! # });
+//! ```
+
+use std::fmt::Debug;
+
+use serde::{Deserialize, Serialize};
+use shakmaty::{Chess, Color, Outcome};
+
+use crate::{
+    game::{play_game, GameError, GameSession},
+    random::RandomSource,
+    Engine,
+};
+
+/// The results of the primary engine against one opponent, from the primary engine's point of view.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GauntletLine {
+    /// The opponent's index in the list given to [`run_gauntlet`].
+    pub opponent: usize,
+
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+
+    /// Games that an engine aborted, which count for neither side.
+    pub aborted: u32,
+}
+
+impl GauntletLine {
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws + self.aborted
+    }
+
+    /// Points scored: one for each win, and half for each draw. Aborted games score nothing.
+    pub fn score(&self) -> f64 {
+        f64::from(self.wins) + f64::from(self.draws) / 2.0
+    }
+}
+
+/// Why [`run_gauntlet`] stopped: a game that could not be finished.
+pub struct GauntletError<E: Engine, O: Engine> {
+    /// The opponent's index in the list given to [`run_gauntlet`].
+    pub opponent: usize,
+
+    /// The game's index among the games against that opponent.
+    pub game: usize,
+
+    /// The failed turn. The primary engine played white in even-numbered games.
+    pub error: GauntletTurnError<E, O>,
+}
+
+/// The failed turn of a [`GauntletError`], with the primary engine as white or as black.
+pub enum GauntletTurnError<E: Engine, O: Engine> {
+    AsWhite(GameError<E, O>),
+    AsBlack(GameError<O, E>),
+}
+
+impl<E: Engine, O: Engine> Debug for GauntletError<E, O> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut s = f.debug_struct("GauntletError");
+        s.field("opponent", &self.opponent)
+            .field("game", &self.game);
+        match &self.error {
+            GauntletTurnError::AsWhite(why) => s.field("error", why),
+            GauntletTurnError::AsBlack(why) => s.field("error", why),
+        };
+        s.finish()
+    }
+}
+
+/// Play `engine` against each of `opponents` for `games_per` games, alternating colors
+/// with the primary engine as white first, and count the results.
+///
+/// Every game starts from the standard position and follows the strict rules, so that it ends.
+/// The seed of game `i` against opponent `j` is
+/// [`RandomSource::stream_seed`](crate::random::RandomSource::stream_seed)`(j * games_per + i)` of `seed`,
+/// which [`play_game`] derives each ply's seed from.
+pub async fn run_gauntlet<E: Engine, O: Engine>(
+    mut engine: E,
+    opponents: Vec<O>,
+    games_per: usize,
+    seed: u64,
+) -> Result<Vec<GauntletLine>, GauntletError<E, O>> {
+    let seeds = RandomSource::new(seed);
+    let mut table = Vec::with_capacity(opponents.len());
+    for (opponent_index, mut opponent) in opponents.into_iter().enumerate() {
+        let mut line = GauntletLine {
+            opponent: opponent_index,
+            ..GauntletLine::default()
+        };
+        for game in 0..games_per {
+            let rand = seeds.stream_seed(opponent_index * games_per + game);
+            let primary = GameSession::new(engine, Chess::new()).with_strict_rules();
+            let other = GameSession::new(opponent, Chess::new()).with_strict_rules();
+            let fail = |error| GauntletError {
+                opponent: opponent_index,
+                game,
+                error,
+            };
+            let (outcome, primary_color) = if game % 2 == 0 {
+                let (mut white, mut black) = (primary, other);
+                let outcome = play_game(&mut white, &mut black, rand).await;
+                (engine, opponent) = (white.into_engine(), black.into_engine());
+                (
+                    outcome.map_err(|why| fail(GauntletTurnError::AsWhite(why)))?,
+                    Color::White,
+                )
+            } else {
+                let (mut white, mut black) = (other, primary);
+                let outcome = play_game(&mut white, &mut black, rand).await;
+                (opponent, engine) = (white.into_engine(), black.into_engine());
+                (
+                    outcome.map_err(|why| fail(GauntletTurnError::AsBlack(why)))?,
+                    Color::Black,
+                )
+            };
+            match outcome.result() {
+                Some(Outcome::Decisive { winner }) if winner == primary_color => line.wins += 1,
+                Some(Outcome::Decisive { .. }) => line.losses += 1,
+                Some(Outcome::Draw) => line.draws += 1,
+                None => line.aborted += 1,
+            }
+        }
+        table.push(line);
+    }
+    Ok(table)
+}
@@ -0,0 +1,153 @@
+//! Serving a router over HTTP/3 (QUIC), as an alternative to HTTP/1.1.
+//!
+//! The routers built by [`crate::server`] work over either protocol; this module only adds the transport.
+//! HTTP/3 avoids head-of-line blocking between streams, which helps long-lived responses
+//! such as `/selfplay/stream` share a connection with ordinary requests.
+
+use std::{io, net::SocketAddr, sync::Arc};
+
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    http::{Request, Response},
+    Router,
+};
+use bytes::Buf;
+use futures_util::stream;
+use h3::server::RequestStream;
+use tower::ServiceExt;
+
+pub use rustls;
+
+/// Serve `router` over HTTP/3 on `addr`, until the endpoint is closed.
+///
+/// QUIC always uses TLS, so a certificate is needed even for local testing.
+/// The `h3` ALPN protocol is added to `tls`, so it does not need to be set beforehand.
+///
+/// Request bodies are passed to the router as they arrive, so its extractors limit their size
+/// just as they do over HTTP/1.1: a JSON body over axum's default 2 MiB is refused
+/// with `413 Payload Too Large` instead of being buffered.
+///
+/// Failed connections and requests are dropped without affecting the others,
+/// since there is no client left to report them to.
+///
+/// ```
+/// use axum::{body::Bytes, http::StatusCode, routing::post, Router};
+/// use engine_trait::http3::{rustls, serve_http3};
+/// # use std::{future::poll_fn, net::SocketAddr, sync::Arc};
+///
+/// let router = Router::new().route("/echo", post(|body: Bytes| async move { body }));
+///
+/// // A self-signed certificate for `localhost`, which the client below trusts.
+/// let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
+/// let cert = rustls::Certificate(std::fs::read(format!("{dir}/localhost.cert.der")).unwrap());
+/// let key = rustls::PrivateKey(std::fs::read(format!("{dir}/localhost.key.der")).unwrap());
+/// let tls = rustls::ServerConfig::builder()
+///     .with_safe_defaults()
+///     .with_no_client_auth()
+///     .with_single_cert(vec![cert.clone()], key)
+///     .unwrap();
+/// # let addr = std::net::UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+///
+/// # tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+/// tokio::spawn(serve_http3(router, addr, tls));
+/// # let mut roots = rustls::RootCertStore::empty();
+/// # roots.add(&cert).unwrap();
+/// # let mut client_tls = rustls::ClientConfig::builder()
+/// #     .with_safe_defaults()
+/// #     .with_root_certificates(roots)
+/// #     .with_no_client_auth();
+/// # client_tls.alpn_protocols = vec![b"h3".to_vec()];
+/// # let mut endpoint = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+/// # endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(client_tls)));
+/// # let connection = endpoint.connect(addr, "localhost").unwrap().await.unwrap();
+/// # let (mut driver, mut client) = h3::client::new(h3_quinn::Connection::new(connection)).await.unwrap();
+/// # tokio::spawn(async move { poll_fn(|cx| driver.poll_close(cx)).await });
+/// # let mut post = |body: Vec<u8>, finish: bool| {
+/// #     let mut client = client.clone();
+/// #     async move {
+/// #         let request = axum::http::Request::post("https://localhost/echo").body(()).unwrap();
+/// #         let mut stream = client.send_request(request).await.unwrap();
+/// #         stream.send_data(Bytes::from(body)).await.unwrap();
+/// #         if finish {
+/// #             stream.finish().await.unwrap();
+/// #         }
+/// #         let response = stream.recv_response().await.unwrap();
+/// #         let mut len = 0;
+/// #         while let Some(chunk) = stream.recv_data().await.unwrap() {
+/// #             len += bytes::Buf::remaining(&chunk);
+/// #         }
+/// #         (response.status(), len)
+/// #     }
+/// # };
+/// // `post` sends a body to `/echo` over HTTP/3, optionally leaving the stream open for more,
+/// // and returns the status and the length of the response body.
+/// assert_eq!(post(vec![0; 1000], true).await, (StatusCode::OK, 1000));
+///
+/// // A body over the limit is refused as soon as the limit is passed, without waiting for the rest.
+/// let (status, _) = post(vec![0; 2 * 1024 * 1024 + 64 * 1024], false).await;
+/// assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+/// # });
+/// ```
+pub async fn serve_http3(
+    router: Router,
+    addr: SocketAddr,
+    mut tls: rustls::ServerConfig,
+) -> io::Result<()> {
+    tls.alpn_protocols = vec![b"h3".to_vec()];
+    let endpoint = quinn::Endpoint::server(quinn::ServerConfig::with_crypto(Arc::new(tls)), addr)?;
+
+    while let Some(connecting) = endpoint.accept().await {
+        let router = router.clone();
+        tokio::spawn(async move {
+            let Ok(connection) = connecting.await else {
+                return;
+            };
+            let Ok(mut connection) =
+                h3::server::Connection::<_, Bytes>::new(h3_quinn::Connection::new(connection))
+                    .await
+            else {
+                return;
+            };
+            while let Ok(Some((request, stream))) = connection.accept().await {
+                let router = router.clone();
+                tokio::spawn(async move {
+                    let _ = handle_request(router, request, stream).await;
+                });
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Pass one HTTP/3 request through the router, streaming both bodies as they are produced.
+async fn handle_request<S>(
+    router: Router,
+    request: Request<()>,
+    stream: RequestStream<S, Bytes>,
+) -> Result<(), h3::Error>
+where
+    S: h3::quic::BidiStream<Bytes>,
+    S::RecvStream: Send + 'static,
+{
+    let (mut stream, recv) = stream.split();
+    let body = stream::try_unfold(recv, |mut recv| async move {
+        let Some(mut chunk) = recv.recv_data().await? else {
+            return Ok(None);
+        };
+        Ok::<_, h3::Error>(Some((chunk.copy_to_bytes(chunk.remaining()), recv)))
+    });
+    let request = request.map(|()| Body::wrap_stream(body));
+
+    let response = match router.oneshot(request).await {
+        Ok(v) => v,
+        Err(infallible) => match infallible {},
+    };
+    let (parts, mut body) = response.into_parts();
+    stream
+        .send_response(Response::from_parts(parts, ()))
+        .await?;
+    while let Some(Ok(chunk)) = body.data().await {
+        stream.send_data(chunk).await?;
+    }
+    stream.finish().await
+}
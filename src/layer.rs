@@ -0,0 +1,898 @@
+//! Combinators for wrapping an [`Engine`] to change part of its behavior.
+//!
+//! Every combinator is itself an [`Engine`], so they can be stacked freely.
+//! The wrapped engine keeps its `State`, so states can be exchanged between an engine and its wrapped versions.
+//!
+//! ```
+//! use engine_trait::{
+//!     async_trait, layer::EngineExt, server_types::EngineInfo, shakmaty::{Chess, Move, Position},
+//!     Engine, InfallibleError,
+//! };
+//!
+//! struct FirstMove;
+//!
+//! #[async_trait]
+//! impl Engine for FirstMove {
+//!     type State = ();
+//!     type StatusInfo = usize;
+//!     type Error = InfallibleError;
+//!
+//!     fn get_info() -> EngineInfo<Self> {
+//!         EngineInfo::new("first-move", "Plays the first legal move", ())
+//!     }
+//!
+//!     async fn propose_move(
+//!         &mut self,
+//!         _rand: u64,
+//!         _state: &(),
+//!         position: &Chess,
+//!     ) -> Result<(Move, usize), InfallibleError> {
+//!         let moves = position.legal_moves();
+//!         Ok((moves[0].clone(), moves.len()))
+//!     }
+//!
+//!     async fn observe_move(
+//!         &mut self,
+//!         _rand: u64,
+//!         _state: &mut (),
+//!         _move_taken: &Move,
+//!         _position_after: &Chess,
+//!     ) -> Result<(), InfallibleError> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! // Describe the status info in words, and print every move the engine proposes.
+//! let engine = FirstMove
+//!     .map_status(|moves| format!("chose among {moves} moves"))
+//!     .inspect(|position, m| println!("{} in {:?}", m, position.board()));
+//! ```
+
+use std::{marker::PhantomData, time::Duration};
+
+use serde_json::Value;
+use shakmaty::{Chess, Move, Setup};
+
+use crate::{
+    async_trait,
+    random::RandomSource,
+    server_types::{EngineInfo, Score, SearchTree},
+    Engine, EngineAction, MoveSummary,
+};
+
+/// A reusable piece of behavior that can be wrapped around any engine.
+///
+/// Layers are applied with [`EngineExt::with_layer`], innermost first:
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+///
+/// use engine_trait::{
+///     layer::{EngineExt, InspectLayer, MapMoveLayer},
+///     shakmaty::{Chess, Move, Position},
+///     Engine,
+/// };
+/// # use engine_trait::fixtures::FirstMove;
+///
+/// let seen = Arc::new(Mutex::new(Vec::new()));
+/// let log = seen.clone();
+/// // Play the last legal move instead of the first, and record the moves as they come out.
+/// let mut engine = FirstMove
+///     .with_layer(MapMoveLayer::new(|position: &Chess, _: Move| position.legal_moves().last().unwrap().clone()))
+///     .with_layer(InspectLayer::new(move |_: &Chess, m: &Move| log.lock().unwrap().push(m.clone())));
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let position = Chess::new();
+/// let (m, _) = engine.propose_move(7, &(), &position).await.unwrap();
+/// assert_eq!(&m, position.legal_moves().last().unwrap());
+/// assert_eq!(*seen.lock().unwrap(), [m]);
+/// # });
+/// ```
+pub trait EngineLayer<E: Engine> {
+    /// The engine that results from wrapping `E`.
+    type Engine: Engine<State = E::State>;
+
+    fn layer(self, engine: E) -> Self::Engine;
+}
+
+/// Combinators available on every [`Engine`].
+pub trait EngineExt: Engine {
+    /// Wrap this engine in an [`EngineLayer`].
+    fn with_layer<L: EngineLayer<Self>>(self, layer: L) -> L::Engine {
+        layer.layer(self)
+    }
+
+    /// Transform the engine's status info.
+    ///
+    /// The wrapped engine's [`Engine::summarize`] needs the original status info, so the mapped engine reports no summary.
+    fn map_status<S, F>(self, f: F) -> MapStatus<Self, F, S>
+    where
+        F: Fn(Self::StatusInfo) -> S + Send + Sync,
+    {
+        MapStatus {
+            inner: self,
+            f,
+            _status: PhantomData,
+        }
+    }
+
+    /// Transform the engine's errors.
+    fn map_error<Er, F>(self, f: F) -> MapError<Self, F, Er>
+    where
+        F: Fn(Self::Error) -> Er + Send + Sync,
+    {
+        MapError {
+            inner: self,
+            f,
+            _error: PhantomData,
+        }
+    }
+
+    /// Replace the engine's proposed moves with `f(position, proposed)`, e.g. for handicap or teaching modes.
+    ///
+    /// The server checks the replacement like any other move, so an illegal replacement is reported
+    /// as [`EngineRequestError::EngineSentIllegalMove`](crate::server_types::EngineRequestError::EngineSentIllegalMove).
+    /// The wrapped engine's summary is kept, so its principal variation may not start with the replaced move.
+    fn map_move<F>(self, f: F) -> MapMove<Self, F>
+    where
+        F: Fn(&Chess, Move) -> Move + Send + Sync,
+    {
+        MapMove { inner: self, f }
+    }
+
+    /// Call `f` with the position and the move every time the engine proposes a move.
+    fn inspect<F>(self, f: F) -> Inspect<Self, F>
+    where
+        F: Fn(&Chess, &Move) + Send + Sync,
+    {
+        Inspect { inner: self, f }
+    }
+}
+
+impl<E: Engine> EngineExt for E {}
+
+/// Carry over an inner engine's info to a wrapper with the same state.
+fn rewrap_info<E: Engine, W: Engine<State = E::State>>(info: EngineInfo<E>) -> EngineInfo<W> {
+    EngineInfo {
+        display_name: info.display_name,
+        author: info.author,
+        icon_url: info.icon_url,
+        version: info.version,
+        build_hash: info.build_hash,
+        capabilities: info.capabilities,
+        protocol_versions: info.protocol_versions,
+        options: info.options,
+        ..EngineInfo::new(info.id, info.description, info.initial_state)
+    }
+}
+
+/// An engine whose status info is transformed. See [`EngineExt::map_status`].
+pub struct MapStatus<E, F, S> {
+    inner: E,
+    f: F,
+    _status: PhantomData<fn() -> S>,
+}
+
+#[async_trait]
+impl<E, F, S> Engine for MapStatus<E, F, S>
+where
+    E: Engine,
+    F: Fn(E::StatusInfo) -> S + Send + Sync,
+    S: std::fmt::Debug + serde::Serialize + serde::de::DeserializeOwned + Clone + Send + Sync,
+{
+    type State = E::State;
+    type StatusInfo = S;
+    type Error = E::Error;
+
+    fn get_info() -> EngineInfo<Self> {
+        rewrap_info(E::get_info())
+    }
+
+    fn state_delta(old: &Self::State, new: &Self::State) -> Option<Value> {
+        E::state_delta(old, new)
+    }
+
+    fn apply_state_delta(old: &Self::State, delta: &Value) -> Option<Self::State> {
+        E::apply_state_delta(old, delta)
+    }
+
+    async fn propose_move(
+        &mut self,
+        rand: u64,
+        current_state: &Self::State,
+        current_position: &Chess,
+    ) -> Result<(Move, S), Self::Error> {
+        let (m, info) = self
+            .inner
+            .propose_move(rand, current_state, current_position)
+            .await?;
+        Ok((m, (self.f)(info)))
+    }
+
+    async fn propose_move_without_info(
+        &mut self,
+        rand: u64,
+        current_state: &Self::State,
+        current_position: &Chess,
+    ) -> Result<Move, Self::Error> {
+        self.inner
+            .propose_move_without_info(rand, current_state, current_position)
+            .await
+    }
+
+    async fn propose_move_with_source(
+        &mut self,
+        rand: &RandomSource,
+        current_state: &Self::State,
+        current_position: &Chess,
+    ) -> Result<(Move, S), Self::Error> {
+        let (m, info) = self
+            .inner
+            .propose_move_with_source(rand, current_state, current_position)
+            .await?;
+        Ok((m, (self.f)(info)))
+    }
+
+    async fn propose_move_restricted(
+        &mut self,
+        rand: u64,
+        current_state: &Self::State,
+        current_position: &Chess,
+        allowed: &[Move],
+    ) -> Result<(Move, S), Self::Error> {
+        let (m, info) = self
+            .inner
+            .propose_move_restricted(rand, current_state, current_position, allowed)
+            .await?;
+        Ok((m, (self.f)(info)))
+    }
+
+    async fn observe_move(
+        &mut self,
+        rand: u64,
+        state: &mut Self::State,
+        move_taken: &Move,
+        position_after: &Chess,
+    ) -> Result<(), Self::Error> {
+        self.inner
+            .observe_move(rand, state, move_taken, position_after)
+            .await
+    }
+
+    async fn unobserve_move(
+        &mut self,
+        rand: u64,
+        state: &mut Self::State,
+        move_taken: &Move,
+        position_before: &Chess,
+    ) -> Result<(), Self::Error> {
+        self.inner
+            .unobserve_move(rand, state, move_taken, position_before)
+            .await
+    }
+
+    async fn evaluate(
+        &mut self,
+        rand: u64,
+        state: &Self::State,
+        position: &Chess,
+    ) -> Result<Option<Score>, Self::Error> {
+        self.inner.evaluate(rand, state, position).await
+    }
+
+    async fn analyze_tree(
+        &mut self,
+        rand: u64,
+        state: &Self::State,
+        position: &Chess,
+        breadth: usize,
+        depth: usize,
+    ) -> Result<Vec<SearchTree>, Self::Error> {
+        self.inner
+            .analyze_tree(rand, state, position, breadth, depth)
+            .await
+    }
+
+    fn normalize_state(&self, state: &mut Self::State, position: &Chess) {
+        self.inner.normalize_state(state, position)
+    }
+
+    fn supports_position(&self, position: &Chess) -> Result<(), String> {
+        self.inner.supports_position(position)
+    }
+
+    fn requested_extra_time(&self, state: &Self::State, position: &Chess) -> Option<Duration> {
+        self.inner.requested_extra_time(state, position)
+    }
+
+    fn transform_position(&self, state: &Self::State, position: &Chess) -> Option<Setup> {
+        self.inner.transform_position(state, position)
+    }
+
+    fn estimate_complexity(&self, position: &Chess) -> u8 {
+        self.inner.estimate_complexity(position)
+    }
+
+    fn fresh_state(rand: u64) -> Self::State {
+        E::fresh_state(rand)
+    }
+
+    fn set_strength(&mut self, elo: Option<u16>) {
+        self.inner.set_strength(elo)
+    }
+
+    async fn choose_action(
+        &mut self,
+        rand: u64,
+        state: &Self::State,
+        position: &Chess,
+    ) -> Result<EngineAction, Self::Error> {
+        self.inner.choose_action(rand, state, position).await
+    }
+}
+
+/// An engine whose errors are transformed. See [`EngineExt::map_error`].
+pub struct MapError<E, F, Er> {
+    inner: E,
+    f: F,
+    _error: PhantomData<fn() -> Er>,
+}
+
+#[async_trait]
+impl<E, F, Er> Engine for MapError<E, F, Er>
+where
+    E: Engine,
+    F: Fn(E::Error) -> Er + Send + Sync,
+    Er: std::fmt::Debug + std::fmt::Display + Clone + Send + Sync,
+{
+    type State = E::State;
+    type StatusInfo = E::StatusInfo;
+    type Error = Er;
+
+    fn get_info() -> EngineInfo<Self> {
+        rewrap_info(E::get_info())
+    }
+
+    fn summarize(status_info: &Self::StatusInfo) -> MoveSummary {
+        E::summarize(status_info)
+    }
+
+    fn state_delta(old: &Self::State, new: &Self::State) -> Option<Value> {
+        E::state_delta(old, new)
+    }
+
+    fn apply_state_delta(old: &Self::State, delta: &Value) -> Option<Self::State> {
+        E::apply_state_delta(old, delta)
+    }
+
+    async fn propose_move(
+        &mut self,
+        rand: u64,
+        current_state: &Self::State,
+        current_position: &Chess,
+    ) -> Result<(Move, Self::StatusInfo), Er> {
+        self.inner
+            .propose_move(rand, current_state, current_position)
+            .await
+            .map_err(&self.f)
+    }
+
+    async fn propose_move_without_info(
+        &mut self,
+        rand: u64,
+        current_state: &Self::State,
+        current_position: &Chess,
+    ) -> Result<Move, Er> {
+        self.inner
+            .propose_move_without_info(rand, current_state, current_position)
+            .await
+            .map_err(&self.f)
+    }
+
+    async fn propose_move_with_source(
+        &mut self,
+        rand: &RandomSource,
+        current_state: &Self::State,
+        current_position: &Chess,
+    ) -> Result<(Move, Self::StatusInfo), Er> {
+        self.inner
+            .propose_move_with_source(rand, current_state, current_position)
+            .await
+            .map_err(&self.f)
+    }
+
+    async fn propose_move_restricted(
+        &mut self,
+        rand: u64,
+        current_state: &Self::State,
+        current_position: &Chess,
+        allowed: &[Move],
+    ) -> Result<(Move, Self::StatusInfo), Er> {
+        self.inner
+            .propose_move_restricted(rand, current_state, current_position, allowed)
+            .await
+            .map_err(&self.f)
+    }
+
+    async fn observe_move(
+        &mut self,
+        rand: u64,
+        state: &mut Self::State,
+        move_taken: &Move,
+        position_after: &Chess,
+    ) -> Result<(), Er> {
+        self.inner
+            .observe_move(rand, state, move_taken, position_after)
+            .await
+            .map_err(&self.f)
+    }
+
+    async fn unobserve_move(
+        &mut self,
+        rand: u64,
+        state: &mut Self::State,
+        move_taken: &Move,
+        position_before: &Chess,
+    ) -> Result<(), Er> {
+        self.inner
+            .unobserve_move(rand, state, move_taken, position_before)
+            .await
+            .map_err(&self.f)
+    }
+
+    async fn evaluate(
+        &mut self,
+        rand: u64,
+        state: &Self::State,
+        position: &Chess,
+    ) -> Result<Option<Score>, Er> {
+        self.inner
+            .evaluate(rand, state, position)
+            .await
+            .map_err(&self.f)
+    }
+
+    async fn analyze_tree(
+        &mut self,
+        rand: u64,
+        state: &Self::State,
+        position: &Chess,
+        breadth: usize,
+        depth: usize,
+    ) -> Result<Vec<SearchTree>, Er> {
+        self.inner
+            .analyze_tree(rand, state, position, breadth, depth)
+            .await
+            .map_err(&self.f)
+    }
+
+    fn normalize_state(&self, state: &mut Self::State, position: &Chess) {
+        self.inner.normalize_state(state, position)
+    }
+
+    fn supports_position(&self, position: &Chess) -> Result<(), String> {
+        self.inner.supports_position(position)
+    }
+
+    fn requested_extra_time(&self, state: &Self::State, position: &Chess) -> Option<Duration> {
+        self.inner.requested_extra_time(state, position)
+    }
+
+    fn transform_position(&self, state: &Self::State, position: &Chess) -> Option<Setup> {
+        self.inner.transform_position(state, position)
+    }
+
+    fn estimate_complexity(&self, position: &Chess) -> u8 {
+        self.inner.estimate_complexity(position)
+    }
+
+    fn fresh_state(rand: u64) -> Self::State {
+        E::fresh_state(rand)
+    }
+
+    fn set_strength(&mut self, elo: Option<u16>) {
+        self.inner.set_strength(elo)
+    }
+
+    async fn choose_action(
+        &mut self,
+        rand: u64,
+        state: &Self::State,
+        position: &Chess,
+    ) -> Result<EngineAction, Er> {
+        self.inner
+            .choose_action(rand, state, position)
+            .await
+            .map_err(&self.f)
+    }
+}
+
+/// An engine whose proposed moves are passed to a callback. See [`EngineExt::inspect`].
+pub struct Inspect<E, F> {
+    inner: E,
+    f: F,
+}
+
+/// The [`EngineLayer`] form of [`EngineExt::inspect`].
+#[derive(Clone)]
+pub struct InspectLayer<F> {
+    f: F,
+}
+
+impl<F> InspectLayer<F> {
+    pub fn new(f: F) -> Self {
+        InspectLayer { f }
+    }
+}
+
+impl<E, F> EngineLayer<E> for InspectLayer<F>
+where
+    E: Engine,
+    F: Fn(&Chess, &Move) + Send + Sync,
+{
+    type Engine = Inspect<E, F>;
+
+    fn layer(self, engine: E) -> Inspect<E, F> {
+        engine.inspect(self.f)
+    }
+}
+
+#[async_trait]
+impl<E, F> Engine for Inspect<E, F>
+where
+    E: Engine,
+    F: Fn(&Chess, &Move) + Send + Sync,
+{
+    type State = E::State;
+    type StatusInfo = E::StatusInfo;
+    type Error = E::Error;
+
+    fn get_info() -> EngineInfo<Self> {
+        rewrap_info(E::get_info())
+    }
+
+    fn summarize(status_info: &Self::StatusInfo) -> MoveSummary {
+        E::summarize(status_info)
+    }
+
+    fn state_delta(old: &Self::State, new: &Self::State) -> Option<Value> {
+        E::state_delta(old, new)
+    }
+
+    fn apply_state_delta(old: &Self::State, delta: &Value) -> Option<Self::State> {
+        E::apply_state_delta(old, delta)
+    }
+
+    async fn propose_move(
+        &mut self,
+        rand: u64,
+        current_state: &Self::State,
+        current_position: &Chess,
+    ) -> Result<(Move, Self::StatusInfo), Self::Error> {
+        let result = self
+            .inner
+            .propose_move(rand, current_state, current_position)
+            .await?;
+        (self.f)(current_position, &result.0);
+        Ok(result)
+    }
+
+    async fn propose_move_without_info(
+        &mut self,
+        rand: u64,
+        current_state: &Self::State,
+        current_position: &Chess,
+    ) -> Result<Move, Self::Error> {
+        let m = self
+            .inner
+            .propose_move_without_info(rand, current_state, current_position)
+            .await?;
+        (self.f)(current_position, &m);
+        Ok(m)
+    }
+
+    async fn propose_move_with_source(
+        &mut self,
+        rand: &RandomSource,
+        current_state: &Self::State,
+        current_position: &Chess,
+    ) -> Result<(Move, Self::StatusInfo), Self::Error> {
+        let result = self
+            .inner
+            .propose_move_with_source(rand, current_state, current_position)
+            .await?;
+        (self.f)(current_position, &result.0);
+        Ok(result)
+    }
+
+    async fn propose_move_restricted(
+        &mut self,
+        rand: u64,
+        current_state: &Self::State,
+        current_position: &Chess,
+        allowed: &[Move],
+    ) -> Result<(Move, Self::StatusInfo), Self::Error> {
+        let result = self
+            .inner
+            .propose_move_restricted(rand, current_state, current_position, allowed)
+            .await?;
+        (self.f)(current_position, &result.0);
+        Ok(result)
+    }
+
+    async fn observe_move(
+        &mut self,
+        rand: u64,
+        state: &mut Self::State,
+        move_taken: &Move,
+        position_after: &Chess,
+    ) -> Result<(), Self::Error> {
+        self.inner
+            .observe_move(rand, state, move_taken, position_after)
+            .await
+    }
+
+    async fn unobserve_move(
+        &mut self,
+        rand: u64,
+        state: &mut Self::State,
+        move_taken: &Move,
+        position_before: &Chess,
+    ) -> Result<(), Self::Error> {
+        self.inner
+            .unobserve_move(rand, state, move_taken, position_before)
+            .await
+    }
+
+    async fn evaluate(
+        &mut self,
+        rand: u64,
+        state: &Self::State,
+        position: &Chess,
+    ) -> Result<Option<Score>, Self::Error> {
+        self.inner.evaluate(rand, state, position).await
+    }
+
+    async fn analyze_tree(
+        &mut self,
+        rand: u64,
+        state: &Self::State,
+        position: &Chess,
+        breadth: usize,
+        depth: usize,
+    ) -> Result<Vec<SearchTree>, Self::Error> {
+        self.inner
+            .analyze_tree(rand, state, position, breadth, depth)
+            .await
+    }
+
+    fn normalize_state(&self, state: &mut Self::State, position: &Chess) {
+        self.inner.normalize_state(state, position)
+    }
+
+    fn supports_position(&self, position: &Chess) -> Result<(), String> {
+        self.inner.supports_position(position)
+    }
+
+    fn requested_extra_time(&self, state: &Self::State, position: &Chess) -> Option<Duration> {
+        self.inner.requested_extra_time(state, position)
+    }
+
+    fn transform_position(&self, state: &Self::State, position: &Chess) -> Option<Setup> {
+        self.inner.transform_position(state, position)
+    }
+
+    fn estimate_complexity(&self, position: &Chess) -> u8 {
+        self.inner.estimate_complexity(position)
+    }
+
+    fn fresh_state(rand: u64) -> Self::State {
+        E::fresh_state(rand)
+    }
+
+    fn set_strength(&mut self, elo: Option<u16>) {
+        self.inner.set_strength(elo)
+    }
+
+    async fn choose_action(
+        &mut self,
+        rand: u64,
+        state: &Self::State,
+        position: &Chess,
+    ) -> Result<EngineAction, Self::Error> {
+        self.inner.choose_action(rand, state, position).await
+    }
+}
+
+/// An engine whose proposed moves are replaced. See [`EngineExt::map_move`].
+pub struct MapMove<E, F> {
+    inner: E,
+    f: F,
+}
+
+/// The [`EngineLayer`] form of [`EngineExt::map_move`].
+#[derive(Clone)]
+pub struct MapMoveLayer<F> {
+    f: F,
+}
+
+impl<F> MapMoveLayer<F> {
+    pub fn new(f: F) -> Self {
+        MapMoveLayer { f }
+    }
+}
+
+impl<E, F> EngineLayer<E> for MapMoveLayer<F>
+where
+    E: Engine,
+    F: Fn(&Chess, Move) -> Move + Send + Sync,
+{
+    type Engine = MapMove<E, F>;
+
+    fn layer(self, engine: E) -> MapMove<E, F> {
+        engine.map_move(self.f)
+    }
+}
+
+#[async_trait]
+impl<E, F> Engine for MapMove<E, F>
+where
+    E: Engine,
+    F: Fn(&Chess, Move) -> Move + Send + Sync,
+{
+    type State = E::State;
+    type StatusInfo = E::StatusInfo;
+    type Error = E::Error;
+
+    fn get_info() -> EngineInfo<Self> {
+        rewrap_info(E::get_info())
+    }
+
+    fn summarize(status_info: &Self::StatusInfo) -> MoveSummary {
+        E::summarize(status_info)
+    }
+
+    fn state_delta(old: &Self::State, new: &Self::State) -> Option<Value> {
+        E::state_delta(old, new)
+    }
+
+    fn apply_state_delta(old: &Self::State, delta: &Value) -> Option<Self::State> {
+        E::apply_state_delta(old, delta)
+    }
+
+    async fn propose_move(
+        &mut self,
+        rand: u64,
+        current_state: &Self::State,
+        current_position: &Chess,
+    ) -> Result<(Move, Self::StatusInfo), Self::Error> {
+        let (m, info) = self
+            .inner
+            .propose_move(rand, current_state, current_position)
+            .await?;
+        Ok(((self.f)(current_position, m), info))
+    }
+
+    async fn propose_move_without_info(
+        &mut self,
+        rand: u64,
+        current_state: &Self::State,
+        current_position: &Chess,
+    ) -> Result<Move, Self::Error> {
+        let m = self
+            .inner
+            .propose_move_without_info(rand, current_state, current_position)
+            .await?;
+        Ok((self.f)(current_position, m))
+    }
+
+    async fn propose_move_with_source(
+        &mut self,
+        rand: &RandomSource,
+        current_state: &Self::State,
+        current_position: &Chess,
+    ) -> Result<(Move, Self::StatusInfo), Self::Error> {
+        let (m, info) = self
+            .inner
+            .propose_move_with_source(rand, current_state, current_position)
+            .await?;
+        Ok(((self.f)(current_position, m), info))
+    }
+
+    async fn propose_move_restricted(
+        &mut self,
+        rand: u64,
+        current_state: &Self::State,
+        current_position: &Chess,
+        allowed: &[Move],
+    ) -> Result<(Move, Self::StatusInfo), Self::Error> {
+        let (m, info) = self
+            .inner
+            .propose_move_restricted(rand, current_state, current_position, allowed)
+            .await?;
+        Ok(((self.f)(current_position, m), info))
+    }
+
+    async fn observe_move(
+        &mut self,
+        rand: u64,
+        state: &mut Self::State,
+        move_taken: &Move,
+        position_after: &Chess,
+    ) -> Result<(), Self::Error> {
+        self.inner
+            .observe_move(rand, state, move_taken, position_after)
+            .await
+    }
+
+    async fn unobserve_move(
+        &mut self,
+        rand: u64,
+        state: &mut Self::State,
+        move_taken: &Move,
+        position_before: &Chess,
+    ) -> Result<(), Self::Error> {
+        self.inner
+            .unobserve_move(rand, state, move_taken, position_before)
+            .await
+    }
+
+    async fn evaluate(
+        &mut self,
+        rand: u64,
+        state: &Self::State,
+        position: &Chess,
+    ) -> Result<Option<Score>, Self::Error> {
+        self.inner.evaluate(rand, state, position).await
+    }
+
+    async fn analyze_tree(
+        &mut self,
+        rand: u64,
+        state: &Self::State,
+        position: &Chess,
+        breadth: usize,
+        depth: usize,
+    ) -> Result<Vec<SearchTree>, Self::Error> {
+        self.inner
+            .analyze_tree(rand, state, position, breadth, depth)
+            .await
+    }
+
+    fn normalize_state(&self, state: &mut Self::State, position: &Chess) {
+        self.inner.normalize_state(state, position)
+    }
+
+    fn supports_position(&self, position: &Chess) -> Result<(), String> {
+        self.inner.supports_position(position)
+    }
+
+    fn requested_extra_time(&self, state: &Self::State, position: &Chess) -> Option<Duration> {
+        self.inner.requested_extra_time(state, position)
+    }
+
+    fn transform_position(&self, state: &Self::State, position: &Chess) -> Option<Setup> {
+        self.inner.transform_position(state, position)
+    }
+
+    fn estimate_complexity(&self, position: &Chess) -> u8 {
+        self.inner.estimate_complexity(position)
+    }
+
+    fn fresh_state(rand: u64) -> Self::State {
+        E::fresh_state(rand)
+    }
+
+    fn set_strength(&mut self, elo: Option<u16>) {
+        self.inner.set_strength(elo)
+    }
+
+    async fn choose_action(
+        &mut self,
+        rand: u64,
+        state: &Self::State,
+        position: &Chess,
+    ) -> Result<EngineAction, Self::Error> {
+        self.inner.choose_action(rand, state, position).await
+    }
+}
@@ -1,11 +1,39 @@
+pub mod bench;
+#[cfg(feature = "server")]
+mod breaker;
 pub mod chess_serde;
+pub mod cooperative;
+pub mod epd;
+pub mod features;
+#[doc(hidden)]
+pub mod fixtures;
+pub mod game;
+#[cfg(feature = "server")]
+mod game_log;
+pub mod gauntlet;
+#[cfg(feature = "http3")]
+pub mod http3;
+pub mod layer;
+pub mod lookup;
+#[cfg(feature = "pgn")]
+pub mod pgn;
+pub mod position;
+pub mod random;
 #[cfg(feature = "server")]
 pub mod server;
 pub mod server_types;
+#[cfg(feature = "syzygy")]
+pub mod syzygy;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+
+use std::time::Duration;
 
-use serde::{de::DeserializeOwned, Serialize};
-use server_types::EngineInfo;
-use shakmaty::{Chess, Move};
+use random::RandomSource;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use server_types::{EngineInfo, MoveReason, MoveSource, Score, SearchTree};
+use shakmaty::{Chess, Move, Position, Setup};
 
 pub use async_trait::async_trait;
 pub use shakmaty;
@@ -34,6 +62,211 @@ pub use shakmaty;
 /// any state it needs for move correlation must be in the `State`, which can be round-tripped to the user.
 /// In particular, the engine is supposed to make the same moves whether used multiple times or re-created,
 /// as long as the `State`` is the same.
+///
+/// ## Stateless engines
+/// An engine that needs nothing beyond the position should use `()` as its `State`,
+/// and leave it untouched in [`Engine::observe_move`].
+/// The unit state costs nothing to clone, is sent as `null`,
+/// and clients can leave it out of requests altogether:
+///
+/// ```
+/// use engine_trait::{
+///     async_trait, game::play_turn,
+///     server_types::{EngineInfo, EngineRequest, EngineResult},
+///     shakmaty::{Chess, Move, Position},
+///     Engine, InfallibleError,
+/// };
+///
+/// struct LastMove;
+///
+/// #[async_trait]
+/// impl Engine for LastMove {
+///     type State = ();
+///     type StatusInfo = ();
+///     type Error = InfallibleError;
+///
+///     fn get_info() -> EngineInfo<Self> {
+///         EngineInfo::new("last-move", "Plays the last legal move", ())
+///     }
+///
+///     async fn propose_move(
+///         &mut self,
+///         _rand: u64,
+///         _state: &(),
+///         position: &Chess,
+///     ) -> Result<(Move, ()), InfallibleError> {
+///         Ok((position.legal_moves().last().unwrap().clone(), ()))
+///     }
+///
+///     async fn observe_move(
+///         &mut self,
+///         _rand: u64,
+///         _state: &mut (),
+///         _move_taken: &Move,
+///         _position_after: &Chess,
+///     ) -> Result<(), InfallibleError> {
+///         Ok(())
+///     }
+/// }
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let request: EngineRequest<LastMove> = serde_json::from_str(
+///     r#"{"move": "e2e4", "observe_other_rand": null, "produce_rand": null, "observe_own_rand": null, "with_status_info": false}"#,
+/// ).unwrap();
+/// let EngineResult::Ok(response) = play_turn(&mut LastMove, request).await else {
+///     panic!("the turn failed");
+/// };
+/// assert_eq!(serde_json::to_value(&response).unwrap()["engine_state"], serde_json::Value::Null);
+/// # });
+/// ```
+///
+/// ## Thread safety
+/// The server moves engines, their states and their calls between threads,
+/// so an engine and its associated types must be `Send` and `Sync`,
+/// and the futures of its `async` methods must be `Send`, which [`async_trait`] requires on its own.
+/// In practice this rules out types like [`Rc`](std::rc::Rc) and [`RefCell`](std::cell::RefCell):
+/// use [`Arc`](std::sync::Arc) and locks instead, or keep such values out of the state.
+///
+/// A state that cannot be shared between threads is rejected where the state type is named:
+///
+/// ```compile_fail,E0277
+/// use std::cell::RefCell;
+///
+/// use engine_trait::{
+///     async_trait, server_types::EngineInfo,
+///     shakmaty::{Chess, Move, Position},
+///     Engine, InfallibleError, NoStatus,
+/// };
+///
+/// struct FirstMove;
+///
+/// #[async_trait]
+/// impl Engine for FirstMove {
+///     type State = RefCell<Vec<String>>;
+///     type StatusInfo = NoStatus;
+///     type Error = InfallibleError;
+///
+///     fn get_info() -> EngineInfo<Self> {
+///         EngineInfo::new("first-move", "Plays the first legal move", Default::default())
+///     }
+///
+///     async fn propose_move(
+///         &mut self,
+///         _rand: u64,
+///         _state: &Self::State,
+///         position: &Chess,
+///     ) -> Result<(Move, NoStatus), InfallibleError> {
+///         Ok((position.legal_moves()[0].clone(), NoStatus))
+///     }
+///
+///     async fn observe_move(
+///         &mut self,
+///         _rand: u64,
+///         _state: &mut Self::State,
+///         _move_taken: &Move,
+///         _position_after: &Chess,
+///     ) -> Result<(), InfallibleError> {
+///         Ok(())
+///     }
+/// }
+/// ```
+///
+/// So is a method that keeps a value that is not `Send` alive across an `.await`,
+/// since that makes its future not `Send` either. The compiler gives this error no code to check for,
+/// so the example after it is the same engine with only that mistake fixed, to show that nothing else fails:
+///
+/// ```compile_fail
+/// use std::rc::Rc;
+///
+/// use engine_trait::{
+///     async_trait, server_types::EngineInfo,
+///     shakmaty::{Chess, Move, Position},
+///     Engine, InfallibleError, NoStatus,
+/// };
+///
+/// struct FirstMove;
+///
+/// #[async_trait]
+/// impl Engine for FirstMove {
+///     type State = ();
+///     type StatusInfo = NoStatus;
+///     type Error = InfallibleError;
+///
+///     fn get_info() -> EngineInfo<Self> {
+///         EngineInfo::new("first-move", "Plays the first legal move", Default::default())
+///     }
+///
+///     async fn propose_move(
+///         &mut self,
+///         _rand: u64,
+///         _state: &(),
+///         position: &Chess,
+///     ) -> Result<(Move, NoStatus), InfallibleError> {
+///         let moves = Rc::new(position.legal_moves());
+///         std::future::ready(()).await;
+///         Ok((moves[0].clone(), NoStatus))
+///     }
+///
+///     async fn observe_move(
+///         &mut self,
+///         _rand: u64,
+///         _state: &mut Self::State,
+///         _move_taken: &Move,
+///         _position_after: &Chess,
+///     ) -> Result<(), InfallibleError> {
+///         Ok(())
+///     }
+/// }
+/// ```
+///
+/// Dropping such values before the next `.await`, e.g. by moving them into a block that ends first, avoids this:
+///
+/// ```
+/// use std::rc::Rc;
+///
+/// use engine_trait::{
+///     async_trait, server_types::EngineInfo,
+///     shakmaty::{Chess, Move, Position},
+///     Engine, InfallibleError, NoStatus,
+/// };
+///
+/// struct FirstMove;
+///
+/// #[async_trait]
+/// impl Engine for FirstMove {
+///     type State = ();
+///     type StatusInfo = NoStatus;
+///     type Error = InfallibleError;
+///
+///     fn get_info() -> EngineInfo<Self> {
+///         EngineInfo::new("first-move", "Plays the first legal move", Default::default())
+///     }
+///
+///     async fn propose_move(
+///         &mut self,
+///         _rand: u64,
+///         _state: &(),
+///         position: &Chess,
+///     ) -> Result<(Move, NoStatus), InfallibleError> {
+///         let first = {
+///             let moves = Rc::new(position.legal_moves());
+///             moves[0].clone()
+///         };
+///         std::future::ready(()).await;
+///         Ok((first, NoStatus))
+///     }
+///
+///     async fn observe_move(
+///         &mut self,
+///         _rand: u64,
+///         _state: &mut Self::State,
+///         _move_taken: &Move,
+///         _position_after: &Chess,
+///     ) -> Result<(), InfallibleError> {
+///         Ok(())
+///     }
+/// }
+/// ```
 #[async_trait]
 pub trait Engine: Send + Sync + Sized {
     /// An engine's state is the information it needs in order to produce moves.
@@ -41,9 +274,18 @@ pub trait Engine: Send + Sync + Sized {
     /// but it will be stored externally.
     ///
     /// The Default implementation should correspond to a game state of the initial position, with white to move.
+    ///
+    /// Requests may leave their `engine_state` out, and then get this default, so that stateless engines need none.
+    /// For an engine whose state matters, this means that a client which forgets the state in the middle of a game
+    /// is not refused: the engine silently plays on from a fresh state. Such engines should keep enough
+    /// in their state to notice that it does not fit the position, for example the position's hash,
+    /// and fail the turn when it does not.
     type State: Serialize + DeserializeOwned + Default + Clone + Send + Sync + std::fmt::Debug;
 
     /// An engine may produce some kind of status information that explains its thinking process.
+    ///
+    /// Engines that have nothing to explain should use [`NoStatus`].
+    /// The server never sends status info of a zero-sized type, since it carries no information.
     type StatusInfo: std::fmt::Debug + Serialize + DeserializeOwned + Clone + Send + Sync;
 
     /// If an engine's thinking can fail, this type should explain how.
@@ -79,13 +321,81 @@ pub trait Engine: Send + Sync + Sized {
     ) -> Result<Move, Self::Error> {
         self.propose_move(rand, current_state, current_position)
             .await
-            .and_then(|v| Ok(v.0))
+            .map(|v| v.0)
+    }
+
+    /// Calculate a move, using a [`RandomSource`] that can provide several independent random streams.
+    ///
+    /// This is called instead of [`Self::propose_move`] when the request supplies `produce_rand_streams`.
+    /// The default implementation forwards the source's single seed to [`Self::propose_move`].
+    async fn propose_move_with_source(
+        &mut self,
+        rand: &RandomSource,
+        current_state: &Self::State,
+        current_position: &Chess,
+    ) -> Result<(Move, Self::StatusInfo), Self::Error> {
+        self.propose_move(rand.seed(), current_state, current_position)
+            .await
+    }
+
+    /// Calculate a move, choosing only among the `allowed` moves.
+    ///
+    /// The `allowed` moves are all legal in `current_position`, and there is at least one.
+    /// The default implementation calls [`Self::propose_move`], and if that move is not allowed,
+    /// snaps it to an allowed move between the same squares, then to one onto the same square,
+    /// and otherwise picks an allowed move using `rand`.
+    async fn propose_move_restricted(
+        &mut self,
+        rand: u64,
+        current_state: &Self::State,
+        current_position: &Chess,
+        allowed: &[Move],
+    ) -> Result<(Move, Self::StatusInfo), Self::Error> {
+        let (proposed, info) = self
+            .propose_move(rand, current_state, current_position)
+            .await?;
+        let snapped = allowed
+            .iter()
+            .find(|m| **m == proposed)
+            .or_else(|| {
+                allowed
+                    .iter()
+                    .find(|m| m.from() == proposed.from() && m.to() == proposed.to())
+            })
+            .or_else(|| allowed.iter().find(|m| m.to() == proposed.to()))
+            .unwrap_or(&allowed[(rand % allowed.len() as u64) as usize]);
+        Ok((snapped.clone(), info))
+    }
+
+    /// Limit how strongly the engine plays its next move, to roughly this Elo rating.
+    ///
+    /// This is called before every move the engine proposes; None means full strength.
+    /// Engines limit their skill however they like, e.g. with depth caps or randomization,
+    /// and should declare the ratings they support in [`Capabilities::strength_range`](server_types::Capabilities::strength_range).
+    /// The default implementation ignores it.
+    fn set_strength(&mut self, elo: Option<u16>) {
+        let _ = elo;
+    }
+
+    /// Decide whether to move at all on this turn, or to end the game instead.
+    ///
+    /// This is called before the engine is asked for a move, with the same random number and position.
+    /// The default implementation always moves.
+    async fn choose_action(
+        &mut self,
+        rand: u64,
+        state: &Self::State,
+        position: &Chess,
+    ) -> Result<EngineAction, Self::Error> {
+        let _ = (rand, state, position);
+        Ok(EngineAction::Move)
     }
 
     /// Observe that a move has occurred.
     /// This is called both for my own moves and for the opponent's moves.
     ///
     /// The provided [`Position`] already has the move applied to it.
+    /// Heavy work here should yield to the runtime now and then; see [`cooperative`].
     async fn observe_move(
         &mut self,
         rand: u64,
@@ -93,12 +403,546 @@ pub trait Engine: Send + Sync + Sized {
         move_taken: &Move,
         position_after: &Chess,
     ) -> Result<(), Self::Error>;
+
+    /// Undo [`Engine::observe_move`], for taking a move back.
+    ///
+    /// `position_before` is the position the move was played in, which is where the game continues from.
+    /// Engines that support this must say so with [`Capabilities::supports_unobserve`](server_types::Capabilities),
+    /// since the server refuses takebacks otherwise.
+    /// The default implementation leaves the state as it is, which is right for engines whose state
+    /// does not depend on the moves they observe.
+    async fn unobserve_move(
+        &mut self,
+        rand: u64,
+        state: &mut Self::State,
+        move_taken: &Move,
+        position_before: &Chess,
+    ) -> Result<(), Self::Error> {
+        let _ = (rand, state, move_taken, position_before);
+        Ok(())
+    }
+
+    /// Evaluate a position without choosing a move.
+    ///
+    /// The score must be from the perspective of the side to move in `position`.
+    /// The `state` must correspond to the `position`, just like in [`Engine::propose_move`].
+    /// The default implementation returns None, meaning that the engine does not evaluate positions.
+    async fn evaluate(
+        &mut self,
+        rand: u64,
+        state: &Self::State,
+        position: &Chess,
+    ) -> Result<Option<Score>, Self::Error> {
+        let _ = (rand, state, position);
+        Ok(None)
+    }
+
+    /// Search a small tree: the `breadth` best moves in `position`, each with its `breadth` best replies,
+    /// and so on, `depth` plies deep.
+    ///
+    /// The server bounds `breadth` and `depth`, and trims trees that exceed them.
+    /// The default implementation returns only the move [`Engine::propose_move_without_info`] picks, without a score.
+    async fn analyze_tree(
+        &mut self,
+        rand: u64,
+        state: &Self::State,
+        position: &Chess,
+        breadth: usize,
+        depth: usize,
+    ) -> Result<Vec<SearchTree>, Self::Error> {
+        let _ = (breadth, depth);
+        let m = self
+            .propose_move_without_info(rand, state, position)
+            .await?;
+        Ok(vec![SearchTree {
+            r#move: m.to_uci(position.castles().mode()),
+            score: None,
+            children: Vec::new(),
+        }])
+    }
+
+    /// Extract the standardized parts of the status info, which the server reports in their own response fields.
+    ///
+    /// The default implementation reports nothing.
+    fn summarize(status_info: &Self::StatusInfo) -> MoveSummary {
+        let _ = status_info;
+        MoveSummary::default()
+    }
+
+    /// Describe how the state changed from `old` to `new`, so that a response can carry only the change.
+    ///
+    /// This is an optimization for engines with large states; see [`Engine::apply_state_delta`] for the other half.
+    /// The default implementation returns None, meaning that the full state is always sent.
+    fn state_delta(old: &Self::State, new: &Self::State) -> Option<Value> {
+        let _ = (old, new);
+        None
+    }
+
+    /// Reconstruct the new state from the old state and a delta made by [`Engine::state_delta`].
+    ///
+    /// Returns None if the delta does not apply to `old`.
+    /// Engines that override `state_delta` must override this too.
+    fn apply_state_delta(old: &Self::State, delta: &Value) -> Option<Self::State> {
+        let _ = (old, delta);
+        None
+    }
+
+    /// Repair or normalize a state received with a request, e.g. by recomputing a cache,
+    /// so that it agrees with the authoritative position.
+    ///
+    /// This is called once per turn, right after the request is read, with the position before the other side's move.
+    /// It is also called on the state of every other request that reaches the engine, such as a [`hint`](game::hint),
+    /// with the request's position, and on the initial state of the games that the server analyzes.
+    /// Unlike rejecting a bad state, this fixes it up; state deltas in the response are still against the state as sent.
+    /// The default implementation leaves the state as it is.
+    ///
+    /// ```
+    /// use engine_trait::{
+    ///     game::{hint, play_turn},
+    ///     server_types::{EngineRequest, EngineResult, HintRequest},
+    ///     shakmaty::{Chess, Position},
+    /// };
+    /// # use engine_trait::{async_trait, server_types::EngineInfo, Engine, InfallibleError, NoStatus};
+    /// # use shakmaty::Move;
+    /// /// Caches the number of legal moves in its state, and plays the last of them.
+    /// struct Cached;
+    ///
+    /// #[async_trait]
+    /// impl Engine for Cached {
+    ///     type State = Option<usize>;
+    /// #   type StatusInfo = NoStatus;
+    /// #   type Error = InfallibleError;
+    /// #   fn get_info() -> EngineInfo<Self> { EngineInfo::new("cached", "Plays the last legal move", None) }
+    ///     // ...
+    ///
+    ///     fn normalize_state(&self, state: &mut Option<usize>, position: &Chess) {
+    ///         *state = Some(position.legal_moves().len());
+    ///     }
+    ///
+    ///     async fn propose_move(&mut self, _: u64, state: &Option<usize>, position: &Chess) -> Result<(Move, NoStatus), InfallibleError> {
+    ///         let count = state.expect("the state was normalized");
+    ///         Ok((position.legal_moves()[count - 1].clone(), NoStatus))
+    ///     }
+    /// #   async fn observe_move(&mut self, _: u64, _: &mut Option<usize>, _: &Move, _: &Chess) -> Result<(), InfallibleError> { Ok(()) }
+    /// }
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// // Neither request carries the cache, so both only work because the state is normalized first.
+    /// let request = HintRequest::<Cached> { position: Chess::new(), engine_state: None, rand: None };
+    /// let response = hint(&mut Cached, request).await.unwrap();
+    /// assert_eq!(response.r#move, Chess::new().legal_moves().last().unwrap().to_uci(shakmaty::CastlingMode::Standard));
+    ///
+    /// let request: EngineRequest<Cached> = serde_json::from_value(serde_json::json!({
+    ///     "move": "0000",
+    ///     "observe_other_rand": null,
+    ///     "produce_rand": null,
+    ///     "observe_own_rand": null,
+    ///     "with_status_info": false,
+    /// }))
+    /// .unwrap();
+    /// assert!(matches!(play_turn(&mut Cached, request).await, EngineResult::Ok(_)));
+    /// # });
+    /// ```
+    fn normalize_state(&self, state: &mut Self::State, position: &Chess) {
+        let _ = (state, position);
+    }
+
+    /// Reject positions that the engine cannot handle, such as ones with more material than it was built for,
+    /// before it sees them. Returning an error gives the reason.
+    ///
+    /// This is called on every turn with the position before the other side's move, and the position after it,
+    /// before the engine observes that move. A rejected turn fails with
+    /// [`EngineRequestError::UnsupportedPosition`](server_types::EngineRequestError::UnsupportedPosition),
+    /// and the engine is not called at all.
+    /// Every other way of reaching the engine checks the positions it would see in the same way:
+    /// hints, search trees, threats, [`GameSession::play_move`](game::GameSession::play_move),
+    /// and every position of the games that the server analyzes or reviews.
+    /// The default implementation accepts every position.
+    ///
+    /// ```
+    /// use engine_trait::{
+    ///     async_trait,
+    ///     game::{hint, play_turn, TurnError},
+    ///     server_types::{EngineInfo, EngineRequest, EngineRequestError, EngineResult, HintRequest},
+    ///     shakmaty::{fen::Fen, CastlingMode, Chess, Color, Move, Position, Role},
+    ///     Engine, InfallibleError, NoStatus,
+    /// };
+    ///
+    /// /// Was only trained on games with at most one queen per side.
+    /// struct OneQueen;
+    ///
+    /// #[async_trait]
+    /// impl Engine for OneQueen {
+    ///     type State = ();
+    ///     type StatusInfo = NoStatus;
+    ///     type Error = InfallibleError;
+    ///
+    ///     fn get_info() -> EngineInfo<Self> {
+    ///         EngineInfo::new("one-queen", "Plays the first legal move, with at most one queen per side", ())
+    ///     }
+    ///
+    ///     fn supports_position(&self, position: &Chess) -> Result<(), String> {
+    ///         let board = position.board();
+    ///         for color in Color::ALL {
+    ///             if (board.by_color(color) & board.by_role(Role::Queen)).count() > 1 {
+    ///                 return Err(format!("{color} has more than one queen"));
+    ///             }
+    ///         }
+    ///         Ok(())
+    ///     }
+    ///
+    ///     async fn propose_move(&mut self, _: u64, _: &(), position: &Chess) -> Result<(Move, NoStatus), InfallibleError> {
+    ///         Ok((position.legal_moves()[0].clone(), NoStatus))
+    ///     }
+    ///
+    ///     async fn observe_move(&mut self, _: u64, _: &mut (), _: &Move, _: &Chess) -> Result<(), InfallibleError> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let request = |fen: &str, uci: &str| -> EngineRequest<OneQueen> {
+    ///     serde_json::from_value(serde_json::json!({
+    ///         "move": uci,
+    ///         "game_before": fen,
+    ///         "with_status_info": false,
+    ///     }))
+    ///     .unwrap()
+    /// };
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let EngineResult::Ok(_) = play_turn(&mut OneQueen, request("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q")).await else {
+    ///     panic!("one queen is fine");
+    /// };
+    /// // Promoting to a second queen leads to a position the engine cannot play.
+    /// let EngineResult::RequestError(why) = play_turn(&mut OneQueen, request("4k3/1P6/8/8/8/8/8/3QK3 w - - 0 1", "b7b8q")).await else {
+    ///     panic!("two queens are not");
+    /// };
+    /// assert!(matches!(why, EngineRequestError::UnsupportedPosition { reason } if reason == "white has more than one queen"));
+    ///
+    /// // Nor does it give hints there.
+    /// let position: Chess = "4k3/8/8/8/8/8/8/2QQK3 w - - 0 1".parse::<Fen>().unwrap().into_position(CastlingMode::Standard).unwrap();
+    /// let why = hint(&mut OneQueen, HintRequest { position, engine_state: (), rand: None }).await.unwrap_err();
+    /// assert!(matches!(why, TurnError::RequestError(EngineRequestError::UnsupportedPosition { .. })));
+    /// # });
+    /// ```
+    fn supports_position(&self, position: &Chess) -> Result<(), String> {
+        let _ = position;
+        Ok(())
+    }
+
+    /// Ask for more thinking time on the next move.
+    ///
+    /// This is called after the engine has observed its own move, with the resulting state and position.
+    /// It is only advisory: clients that manage time may honor it, and others will ignore it.
+    /// The default implementation never asks for more time.
+    fn requested_extra_time(&self, state: &Self::State, position: &Chess) -> Option<Duration> {
+        let _ = (state, position);
+        None
+    }
+
+    /// Change the board after the engine's move, for experimental modes such as puzzles that add pieces between moves.
+    ///
+    /// This is called after the engine has observed its own move, with the resulting state and position.
+    /// If it returns a setup, that replaces the response's `game_after`, and so becomes the next turn's `game_before`.
+    /// The setup must be a legal position in the game's castling mode, or the turn fails.
+    /// The default implementation returns None, which keeps the position the move naturally led to.
+    ///
+    /// ```
+    /// use engine_trait::{
+    ///     async_trait,
+    ///     game::play_turn,
+    ///     server_types::{EngineInfo, EngineRequest, EngineRequestError, EngineResult},
+    ///     shakmaty::{Chess, Color, EnPassantMode, Move, Piece, Position, Role, Setup, Square},
+    ///     Engine, InfallibleError, NoStatus,
+    /// };
+    ///
+    /// /// Plays the first legal move, then puts a piece on a square, replacing whatever was there.
+    /// struct Dropper(Square, Piece);
+    ///
+    /// #[async_trait]
+    /// impl Engine for Dropper {
+    ///     type State = ();
+    ///     type StatusInfo = NoStatus;
+    ///     type Error = InfallibleError;
+    ///
+    ///     fn get_info() -> EngineInfo<Self> {
+    ///         EngineInfo::new("dropper", "Drops a piece after every move", ())
+    ///     }
+    ///
+    ///     async fn propose_move(&mut self, _: u64, _: &(), position: &Chess) -> Result<(Move, NoStatus), InfallibleError> {
+    ///         Ok((position.legal_moves()[0].clone(), NoStatus))
+    ///     }
+    ///
+    ///     async fn observe_move(&mut self, _: u64, _: &mut (), _: &Move, _: &Chess) -> Result<(), InfallibleError> {
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn transform_position(&self, _: &(), position: &Chess) -> Option<Setup> {
+    ///         let mut setup = position.clone().into_setup(EnPassantMode::Legal);
+    ///         setup.board.set_piece_at(self.0, self.1);
+    ///         Some(setup)
+    ///     }
+    /// }
+    ///
+    /// let request = || -> EngineRequest<Dropper> {
+    ///     serde_json::from_value(serde_json::json!({ "move": "e2e4", "with_status_info": false })).unwrap()
+    /// };
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let knight = Role::Knight.of(Color::Black);
+    /// let EngineResult::Ok(response) = play_turn(&mut Dropper(Square::H7, knight), request()).await else {
+    ///     panic!("a knight instead of the h7 pawn is a legal position");
+    /// };
+    /// assert!(response.position_transformed);
+    /// assert_eq!(response.game_after.board().piece_at(Square::H7), Some(knight));
+    ///
+    /// // A second white king makes the position illegal, which fails the turn.
+    /// let mut dropper = Dropper(Square::D4, Role::King.of(Color::White));
+    /// let EngineResult::RequestError(why) = play_turn(&mut dropper, request()).await else {
+    ///     panic!("two white kings are not a legal position");
+    /// };
+    /// assert!(matches!(why, EngineRequestError::EngineSentIllegalPosition { .. }));
+    /// # });
+    /// ```
+    fn transform_position(&self, state: &Self::State, position: &Chess) -> Option<Setup> {
+        let _ = (state, position);
+        None
+    }
+
+    /// Estimate how complex a position is, from 0 (trivial) to 100 (very complex).
+    ///
+    /// This is a cheap heuristic, meant for frontends that pace their "thinking" animations; it is not a search.
+    /// Values above 100 are treated as 100.
+    /// The default implementation says every position is of medium complexity.
+    fn estimate_complexity(&self, position: &Chess) -> u8 {
+        let _ = position;
+        50
+    }
+
+    /// The state to start a new game with, for engines that vary their play from game to game,
+    /// e.g. by picking a random opening.
+    ///
+    /// The default implementation always starts from [`EngineInfo::initial_state`], ignoring `rand`.
+    fn fresh_state(rand: u64) -> Self::State {
+        let _ = rand;
+        Self::get_info().initial_state
+    }
 }
 
+/// What an engine does on its turn, as decided by [`Engine::choose_action`].
+///
+/// ```
+/// use engine_trait::{
+///     async_trait, game::play_turn,
+///     position::position_hash,
+///     server_types::{EngineInfo, EngineRequest, EngineRequestError, EngineResult, GameOutcome},
+///     shakmaty::{fen::Fen, uci::Uci, CastlingMode, Chess, EnPassantMode, Move, Position},
+///     Engine, EngineAction, InfallibleError, NoStatus,
+/// };
+///
+/// /// Claims a draw whenever it is asked to move.
+/// struct Claimer;
+///
+/// #[async_trait]
+/// impl Engine for Claimer {
+///     type State = ();
+///     type StatusInfo = NoStatus;
+///     type Error = InfallibleError;
+///
+///     fn get_info() -> EngineInfo<Self> {
+///         EngineInfo::new("claimer", "Claims a draw instead of moving", ())
+///     }
+///
+///     async fn choose_action(
+///         &mut self,
+///         _rand: u64,
+///         _state: &(),
+///         _position: &Chess,
+///     ) -> Result<EngineAction, InfallibleError> {
+///         Ok(EngineAction::ClaimDraw)
+///     }
+///
+///     async fn propose_move(
+///         &mut self,
+///         _rand: u64,
+///         _state: &(),
+///         position: &Chess,
+///     ) -> Result<(Move, NoStatus), InfallibleError> {
+///         Ok((position.legal_moves()[0].clone(), NoStatus))
+///     }
+///
+///     async fn observe_move(
+///         &mut self,
+///         _rand: u64,
+///         _state: &mut (),
+///         _move_taken: &Move,
+///         _position_after: &Chess,
+///     ) -> Result<(), InfallibleError> {
+///         Ok(())
+///     }
+/// }
+///
+/// let request = |game_before: Chess, position_history: Vec<u64>| -> EngineRequest<Claimer> {
+///     serde_json::from_value(serde_json::json!({
+///         "move": "0000",
+///         "game_before": Fen::from_position(game_before, EnPassantMode::Legal).to_string(),
+///         "position_history": position_history,
+///         "with_status_info": false,
+///     }))
+///     .unwrap()
+/// };
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// // Nothing has happened yet at the start of the game, so the claim is rejected.
+/// let EngineResult::RequestError(why) = play_turn(&mut Claimer, request(Chess::new(), vec![])).await else {
+///     panic!("the claim should fail");
+/// };
+/// assert!(matches!(why, EngineRequestError::InvalidDrawClaim));
+///
+/// // The start position has been seen twice before.
+/// let start = position_hash(&Chess::new());
+/// let EngineResult::Ok(response) = play_turn(&mut Claimer, request(Chess::new(), vec![start, start])).await else {
+///     panic!("the claim should succeed");
+/// };
+/// assert_eq!(response.outcome, Some(GameOutcome::ThreefoldRepetition));
+/// assert_eq!(response.r#move, Uci::Null);
+///
+/// // 50 moves have passed without a capture or pawn move.
+/// let quiet: Chess = "4k3/8/8/8/8/8/8/R3K3 w - - 100 80"
+///     .parse::<Fen>()
+///     .unwrap()
+///     .into_position(CastlingMode::Standard)
+///     .unwrap();
+/// let EngineResult::Ok(response) = play_turn(&mut Claimer, request(quiet, vec![])).await else {
+///     panic!("the claim should succeed");
+/// };
+/// assert_eq!(response.outcome, Some(GameOutcome::FiftyMoveRule));
+/// # });
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EngineAction {
+    /// Propose a move as usual.
+    Move,
+
+    /// Claim a draw by threefold repetition or the 50-move rule, instead of moving.
+    /// If the claim is not valid, the turn fails with
+    /// [`EngineRequestError::InvalidDrawClaim`](server_types::EngineRequestError::InvalidDrawClaim).
+    ClaimDraw,
+
+    /// End the game without a result, instead of moving, e.g. because the engine found that its state
+    /// no longer matches the game. The response has a null move and [`GameOutcome::Aborted`](server_types::GameOutcome::Aborted)
+    /// with this reason, and the engine's state as it was.
+    ///
+    /// Unlike resigning, this scores the game for neither side; unlike returning an error,
+    /// the turn succeeds, so clients should not retry it or count the game as forfeited.
+    Abort { reason: String },
+}
+
+/// The standardized information about how an engine chose its move, as reported by [`Engine::summarize`].
+///
+/// Some of it is checked in debug builds, as a bug in the engine would otherwise reach clients unnoticed:
+///
+/// ```
+/// use engine_trait::{
+///     async_trait,
+///     game::play_turn,
+///     server_types::{EngineInfo, EngineRequest, EngineRequestError, EngineResult},
+///     shakmaty::{Chess, Move, Position},
+///     Engine, InfallibleError, MoveSummary,
+/// };
+///
+/// /// Plays the first legal move, and reports the same chances of winning, drawing and losing every time.
+/// struct Forecaster((f32, f32, f32));
+///
+/// #[async_trait]
+/// impl Engine for Forecaster {
+///     type State = ();
+///     type StatusInfo = (f32, f32, f32);
+///     type Error = InfallibleError;
+///
+///     fn get_info() -> EngineInfo<Self> {
+///         EngineInfo::new("forecaster", "Always gives the same odds", ())
+///     }
+///
+///     async fn propose_move(&mut self, _: u64, _: &(), position: &Chess) -> Result<(Move, (f32, f32, f32)), InfallibleError> {
+///         Ok((position.legal_moves()[0].clone(), self.0))
+///     }
+///
+///     async fn observe_move(&mut self, _: u64, _: &mut (), _: &Move, _: &Chess) -> Result<(), InfallibleError> {
+///         Ok(())
+///     }
+///
+///     fn summarize(wdl: &(f32, f32, f32)) -> MoveSummary {
+///         MoveSummary { wdl: Some(*wdl), ..MoveSummary::default() }
+///     }
+/// }
+///
+/// let request = || -> EngineRequest<Forecaster> {
+///     serde_json::from_value(serde_json::json!({ "move": "e2e4", "with_status_info": true })).unwrap()
+/// };
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let EngineResult::Ok(response) = play_turn(&mut Forecaster((0.3, 0.5, 0.2)), request()).await else {
+///     panic!("the odds add up");
+/// };
+/// assert_eq!(response.wdl, Some((0.3, 0.5, 0.2)));
+///
+/// let result = play_turn(&mut Forecaster((0.6, 0.5, 0.2)), request()).await;
+/// if cfg!(debug_assertions) {
+///     assert!(matches!(
+///         result,
+///         EngineResult::RequestError(EngineRequestError::EngineSentInvalidWdl { wdl: (0.6, 0.5, 0.2) })
+///     ));
+/// } else {
+///     assert!(matches!(result, EngineResult::Ok(_)));
+/// }
+/// # });
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MoveSummary {
+    /// The engine's evaluation of the position it moved in, from its own perspective.
+    pub score: Option<Score>,
+
+    /// The principal variation: the line the engine expects, starting with the move it chose.
+    pub pv: Vec<Move>,
+
+    /// How clearly the chosen move stood out, from 0 (a coin toss) to 1 (the only sensible move).
+    /// The scale is up to the engine; it is meant for frontends that show when the engine is unsure.
+    pub confidence: Option<f32>,
+
+    /// The chances of winning, drawing and losing from the position the engine moved in, from its own perspective.
+    /// They should sum to 1; in debug builds, a turn whose probabilities do not fails.
+    pub wdl: Option<(f32, f32, f32)>,
+
+    /// How many nodes the engine searched for the move, for engines that count them.
+    /// See [`bench::run_bench`].
+    pub nodes: Option<u64>,
+
+    /// How many plies deep the engine searched, for engines that search to a depth.
+    pub depth: Option<u32>,
+
+    /// Why the engine chose the move, for engines that explain their moves.
+    pub reasons: Vec<MoveReason>,
+
+    /// The part of its search that the engine wants to show, for tree viewers: the moves it considered
+    /// in the position it moved in, best first. Only the first [`SearchTree::MAX_NODES`] nodes,
+    /// in breadth-first order, are sent, and the turn fails if any of them is not legal where it is played.
+    /// See [`Capabilities::supports_search_tree`](server_types::Capabilities::supports_search_tree).
+    pub search_tree: Vec<SearchTree>,
+
+    /// Where the move came from. Engines with an opening book or tablebase should report it here.
+    pub source: MoveSource,
+}
+
+/// This can be used as the status info type for engines that do not explain their moves.
+/// Responses from such engines never include status info, even if it is requested.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NoStatus;
+
 /// This can be used as the error type for infallible engines.
 /// It is just like [`std::convert::Infallible`], but it implements [`std::fmt::Debug`] + [`std::fmt::Display`],
 /// and so it can be used in the `Engine::Error` type definition.
-#[derive(Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub enum InfallibleError {}
 
 impl std::fmt::Display for InfallibleError {
@@ -0,0 +1,192 @@
+//! A ready-made engine that only looks moves up, for setups that need no search.
+//!
+//! [`LookupEngine`] plays from an [`OpeningBook`] while it can, then from Syzygy tablebases
+//! if the `syzygy` feature is enabled and tables are given, and otherwise a random legal move.
+//! Every move reports where it came from as its status info.
+//!
+//! ```
+//! use engine_trait::{
+//!     lookup::{LookupEngine, OpeningBook},
+//!     server_types::MoveSource,
+//!     shakmaty::{fen::Fen, uci::Uci, CastlingMode, Chess},
+//!     Engine,
+//! };
+//!
+//! let start = Chess::new();
+//! let mut book = OpeningBook::new();
+//! for (uci, weight) in [("e2e4", 3.0), ("d2d4", 1.0)] {
+//!     book.add(&start, &uci.parse::<Uci>().unwrap().to_move(&start).unwrap(), weight);
+//! }
+//! let mut engine = LookupEngine::new().with_book(book);
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let (m, source) = engine.propose_move(7, &(), &start).await.unwrap();
+//! assert!(["e2e4", "d2d4"].contains(&m.to_uci(CastlingMode::Standard).to_string().as_str()));
+//! assert_eq!(source, MoveSource::Book);
+//!
+//! // Out of book, the engine falls back on a random legal move.
+//! let endgame: Chess = "4k3/8/8/8/8/8/8/4K2R w K - 0 1"
+//!     .parse::<Fen>()
+//!     .unwrap()
+//!     .into_position(CastlingMode::Standard)
+//!     .unwrap();
+//! let (_, source) = engine.propose_move(7, &(), &endgame).await.unwrap();
+//! assert_eq!(source, MoveSource::Search);
+//!
+//! // With no legal move to fall back on, the engine reports an error instead.
+//! let mated: Chess = "R3k3/8/4K3/8/8/8/8/8 b - - 0 1"
+//!     .parse::<Fen>()
+//!     .unwrap()
+//!     .into_position(CastlingMode::Standard)
+//!     .unwrap();
+//! assert!(engine.propose_move(7, &(), &mated).await.is_err());
+//! # });
+//! ```
+
+use std::collections::BTreeMap;
+
+use shakmaty::{uci::Uci, Chess, Move, Position};
+
+use crate::{
+    async_trait,
+    position::{position_hash, random_legal_move},
+    random::weighted_choice,
+    server_types::{EngineInfo, MoveSource},
+    Engine, MoveSummary,
+};
+
+/// Weighted moves for known positions, looked up by [`position_hash`], so transpositions share their moves.
+#[derive(Clone, Debug, Default)]
+pub struct OpeningBook {
+    entries: BTreeMap<u64, Vec<(Uci, f64)>>,
+}
+
+impl OpeningBook {
+    /// An empty book.
+    pub fn new() -> OpeningBook {
+        OpeningBook::default()
+    }
+
+    /// Add `m` as a book move in `position`, to be picked in proportion to `weight`.
+    pub fn add(&mut self, position: &Chess, m: &Move, weight: f64) {
+        let uci = m.to_uci(position.castles().mode());
+        self.entries
+            .entry(position_hash(position))
+            .or_default()
+            .push((uci, weight));
+    }
+
+    /// How many positions the book has moves for.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Pick one of the book moves in `position` by weight, using only `rand`, as in [`weighted_choice`].
+    ///
+    /// Returns None if the position is not in the book, or none of its moves is legal there.
+    pub fn lookup(&self, position: &Chess, rand: u64) -> Option<Move> {
+        let moves: Vec<_> = self
+            .entries
+            .get(&position_hash(position))?
+            .iter()
+            .filter_map(|(uci, weight)| Some((uci.to_move(position).ok()?, *weight)))
+            .collect();
+        weighted_choice(rand, &moves).cloned()
+    }
+}
+
+/// The error of a [`LookupEngine`] asked to move in a position that has no legal moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NoLegalMove;
+
+impl std::fmt::Display for NoLegalMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "there is no legal move to play")
+    }
+}
+
+/// An engine made of lookups: a book, then tablebases, then a random legal move.
+///
+/// Its status info is the [`MoveSource`] of each move, which is also reported through [`Engine::summarize`].
+/// Random fallback moves count as [`MoveSource::Search`].
+#[derive(Debug, Default)]
+pub struct LookupEngine {
+    book: OpeningBook,
+    #[cfg(feature = "syzygy")]
+    tablebase: Option<crate::syzygy::Tablebase>,
+}
+
+impl LookupEngine {
+    /// An engine with an empty book and no tablebases, which only plays random legal moves.
+    pub fn new() -> LookupEngine {
+        LookupEngine::default()
+    }
+
+    pub fn with_book(mut self, book: OpeningBook) -> Self {
+        self.book = book;
+        self
+    }
+
+    #[cfg(feature = "syzygy")]
+    pub fn with_tablebase(mut self, tablebase: crate::syzygy::Tablebase) -> Self {
+        self.tablebase = Some(tablebase);
+        self
+    }
+
+    /// The move to play in `position`, and where it came from. None if the game is over.
+    fn lookup(&self, rand: u64, position: &Chess) -> Option<(Move, MoveSource)> {
+        if let Some(m) = self.book.lookup(position, rand) {
+            return Some((m, MoveSource::Book));
+        }
+        #[cfg(feature = "syzygy")]
+        if let Some((m, _)) = self.tablebase.as_ref().and_then(|v| v.probe_best(position)) {
+            return Some((m, MoveSource::Tablebase));
+        }
+        Some((random_legal_move(position, rand)?, MoveSource::Search))
+    }
+}
+
+#[async_trait]
+impl Engine for LookupEngine {
+    type State = ();
+    type StatusInfo = MoveSource;
+    type Error = NoLegalMove;
+
+    fn get_info() -> EngineInfo<Self> {
+        EngineInfo::new(
+            "lookup",
+            "Plays book and tablebase moves, and random legal moves otherwise",
+            (),
+        )
+    }
+
+    async fn propose_move(
+        &mut self,
+        rand: u64,
+        _state: &(),
+        position: &Chess,
+    ) -> Result<(Move, MoveSource), NoLegalMove> {
+        self.lookup(rand, position).ok_or(NoLegalMove)
+    }
+
+    async fn observe_move(
+        &mut self,
+        _rand: u64,
+        _state: &mut (),
+        _move_taken: &Move,
+        _position_after: &Chess,
+    ) -> Result<(), NoLegalMove> {
+        Ok(())
+    }
+
+    fn summarize(source: &MoveSource) -> MoveSummary {
+        MoveSummary {
+            source: *source,
+            ..Default::default()
+        }
+    }
+}
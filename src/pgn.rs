@@ -0,0 +1,105 @@
+//! Reading games from PGN, using the `pgn-reader` crate.
+
+use std::str::FromStr;
+
+use pgn_reader::{BufferedReader, RawHeader, SanPlus, Skip, Visitor};
+use shakmaty::{fen::Fen, CastlingMode, Chess, Move, Position};
+
+/// A game read from PGN: the position it started from, and the moves of its main line.
+#[derive(Clone, Debug)]
+pub struct PgnGame {
+    pub start: Chess,
+    pub moves: Vec<Move>,
+}
+
+/// Ways in which reading a PGN game can fail.
+#[derive(Clone, Debug)]
+pub enum PgnError {
+    /// The text does not contain any game.
+    NoGame,
+
+    /// The game's `FEN` header is not a valid position.
+    InvalidFen(String),
+
+    /// The move at this ply (counting from 1) is not legal.
+    IllegalMove { ply: usize, san: String },
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnError::NoGame => write!(f, "no game found in PGN"),
+            PgnError::InvalidFen(fen) => write!(f, "invalid FEN header: {fen}"),
+            PgnError::IllegalMove { ply, san } => write!(f, "illegal move at ply {ply}: {san}"),
+        }
+    }
+}
+
+/// Collects the FEN header and the main line's moves, skipping variations.
+#[derive(Default)]
+struct GameCollector {
+    fen: Option<String>,
+    moves: Vec<SanPlus>,
+}
+
+impl Visitor for GameCollector {
+    type Result = (Option<String>, Vec<SanPlus>);
+
+    fn begin_game(&mut self) {
+        *self = GameCollector::default();
+    }
+
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        if key == b"FEN" {
+            self.fen = Some(value.decode_utf8_lossy().into_owned());
+        }
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        self.moves.push(san_plus);
+    }
+
+    fn begin_variation(&mut self) -> Skip {
+        Skip(true)
+    }
+
+    fn end_game(&mut self) -> Self::Result {
+        (self.fen.take(), std::mem::take(&mut self.moves))
+    }
+}
+
+/// Read the first game in a PGN text, and check that its moves are legal.
+pub fn read_pgn_game(pgn: &str) -> Result<PgnGame, PgnError> {
+    let (fen, sans) = BufferedReader::new_cursor(pgn)
+        .read_game(&mut GameCollector::default())
+        .ok()
+        .flatten()
+        .ok_or(PgnError::NoGame)?;
+
+    let start = match fen {
+        Some(fen) => Fen::from_str(&fen)
+            .ok()
+            .and_then(|v| {
+                let mode = CastlingMode::detect(v.as_setup());
+                v.into_position(mode).ok()
+            })
+            .ok_or(PgnError::InvalidFen(fen))?,
+        None => Chess::new(),
+    };
+
+    let mut position = start.clone();
+    let mut moves = Vec::with_capacity(sans.len());
+    for (i, san_plus) in sans.into_iter().enumerate() {
+        let m = san_plus
+            .san
+            .to_move(&position)
+            .map_err(|_| PgnError::IllegalMove {
+                ply: i + 1,
+                san: san_plus.to_string(),
+            })?;
+        position.play_unchecked(&m);
+        moves.push(m);
+    }
+
+    Ok(PgnGame { start, moves })
+}
@@ -0,0 +1,695 @@
+//! Engine-independent helpers for working with positions.
+
+use std::{num::NonZeroU32, str::FromStr};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Serialize};
+use shakmaty::{
+    fen::{Epd, Fen},
+    san::{San, SanPlus},
+    uci::Uci,
+    zobrist::{Zobrist64, ZobristHash},
+    Bitboard, CastlingMode, CastlingSide, Chess, Color, EnPassantMode, FromSetup, Move, Outcome,
+    Position, Role, Setup, Square,
+};
+
+use crate::server_types::GameOutcome;
+
+/// The Chess960 start position with the given Scharnagl number (0 to 959).
+///
+/// Number 518 is the standard chess start position, although it is returned in Chess960 castling mode.
+/// Returns None if the number is out of range.
+pub fn chess960_start(sp: u16) -> Option<Chess> {
+    if sp >= 960 {
+        return None;
+    }
+
+    let mut back_rank = [None; 8];
+    let mut n = sp as usize;
+
+    // The bishops go on squares of opposite colors.
+    back_rank[n % 4 * 2 + 1] = Some('b');
+    n /= 4;
+    back_rank[n % 4 * 2] = Some('b');
+    n /= 4;
+
+    // The remaining pieces go on the empty squares, in order.
+    fn place(back_rank: &mut [Option<char>; 8], nth_empty: usize, piece: char) {
+        let file = (0..8)
+            .filter(|&f| back_rank[f].is_none())
+            .nth(nth_empty)
+            .expect("there are enough empty squares on the back rank");
+        back_rank[file] = Some(piece);
+    }
+
+    place(&mut back_rank, n % 6, 'q');
+    n /= 6;
+
+    const KNIGHTS: [(usize, usize); 10] = [
+        (0, 1),
+        (0, 2),
+        (0, 3),
+        (0, 4),
+        (1, 2),
+        (1, 3),
+        (1, 4),
+        (2, 3),
+        (2, 4),
+        (3, 4),
+    ];
+    let (first_knight, second_knight) = KNIGHTS[n];
+    // Place the later knight first, so that it does not shift the index of the earlier one.
+    place(&mut back_rank, second_knight, 'n');
+    place(&mut back_rank, first_knight, 'n');
+
+    // The king goes between the two rooks.
+    place(&mut back_rank, 0, 'r');
+    place(&mut back_rank, 0, 'k');
+    place(&mut back_rank, 0, 'r');
+
+    let black: String = back_rank
+        .iter()
+        .map(|p| p.expect("all squares filled"))
+        .collect();
+    let rook_files: String = (0..8)
+        .filter(|&f| back_rank[f] == Some('r'))
+        .map(|f| (b'a' + f as u8) as char)
+        .collect();
+    let fen = format!(
+        "{black}/pppppppp/8/8/8/8/PPPPPPPP/{} w {}{rook_files} - 0 1",
+        black.to_uppercase(),
+        rook_files.to_uppercase(),
+    );
+
+    Some(
+        Fen::from_str(&fen)
+            .expect("generated FEN is well-formed")
+            .into_position(CastlingMode::Chess960)
+            .expect("generated position is legal"),
+    )
+}
+
+/// The castling rights that `before` has, but `after` does not.
+pub fn castling_rights_lost(before: &Chess, after: &Chess) -> Vec<(Color, CastlingSide)> {
+    Color::ALL
+        .into_iter()
+        .flat_map(|color| CastlingSide::ALL.into_iter().map(move |side| (color, side)))
+        .filter(|&(color, side)| {
+            before.castles().has(color, side) && !after.castles().has(color, side)
+        })
+        .collect()
+}
+
+/// How the game ended in this position, if it is over.
+///
+/// Variant-specific endings are checked first, so this works for any variant's position.
+///
+/// ```
+/// use engine_trait::{
+///     position::game_outcome,
+///     server_types::GameOutcome,
+///     shakmaty::{
+///         fen::Fen,
+///         uci::Uci,
+///         variant::{KingOfTheHill, ThreeCheck},
+///         CastlingMode, Color, Position,
+///     },
+/// };
+///
+/// // The white king reaches the center.
+/// let hill: KingOfTheHill = "4k3/8/8/8/8/4K3/8/8 w - - 0 1"
+///     .parse::<Fen>()
+///     .unwrap()
+///     .into_position(CastlingMode::Standard)
+///     .unwrap();
+/// assert_eq!(game_outcome(&hill), None);
+/// let m = "e3e4".parse::<Uci>().unwrap().to_move(&hill).unwrap();
+/// let hill = hill.play(&m).unwrap();
+/// assert_eq!(game_outcome(&hill), Some(GameOutcome::VariantWin { winner: Color::White }));
+///
+/// // White gives its third check.
+/// let checks: ThreeCheck = "4k3/8/8/8/8/8/8/4K2R w - - 1+3 0 1"
+///     .parse::<Fen>()
+///     .unwrap()
+///     .into_position(CastlingMode::Standard)
+///     .unwrap();
+/// let m = "h1h8".parse::<Uci>().unwrap().to_move(&checks).unwrap();
+/// let checks = checks.play(&m).unwrap();
+/// assert_eq!(game_outcome(&checks), Some(GameOutcome::VariantWin { winner: Color::White }));
+/// ```
+pub fn game_outcome<P: Position>(position: &P) -> Option<GameOutcome> {
+    if position.is_variant_end() {
+        return match position.variant_outcome()? {
+            Outcome::Decisive { winner } => Some(GameOutcome::VariantWin { winner }),
+            Outcome::Draw => Some(GameOutcome::VariantDraw),
+        };
+    }
+    if position.is_checkmate() {
+        Some(GameOutcome::Checkmate {
+            winner: !position.turn(),
+        })
+    } else if position.is_stalemate() {
+        Some(GameOutcome::Stalemate)
+    } else if position.is_insufficient_material() {
+        Some(GameOutcome::InsufficientMaterial)
+    } else {
+        None
+    }
+}
+
+/// How the game ended in this position under the FIDE rules, given the positions before it, if it is over.
+///
+/// On top of [`game_outcome`], this detects the draws that end the game without being claimed:
+/// fivefold repetition and the 75-move rule. `history` holds the [`position_hash`] of every earlier position
+/// of the game, oldest first.
+///
+/// ```
+/// use engine_trait::{
+///     position::{position_hash, strict_game_outcome},
+///     server_types::GameOutcome,
+///     shakmaty::{fen::Fen, uci::Uci, CastlingMode, Chess, Position},
+/// };
+///
+/// // Both sides shuffle their knights out and back, returning to the start position every four plies.
+/// let mut position = Chess::new();
+/// let mut history = Vec::new();
+/// for uci in ["g1f3", "g8f6", "f3g1", "f6g8"].iter().cycle().take(16) {
+///     assert_eq!(strict_game_outcome(&position, &history), None);
+///     history.push(position_hash(&position));
+///     let m = uci.parse::<Uci>().unwrap().to_move(&position).unwrap();
+///     position.play_unchecked(&m);
+/// }
+/// assert_eq!(strict_game_outcome(&position, &history), Some(GameOutcome::FivefoldRepetition));
+///
+/// let position: Chess = "4k3/8/8/8/8/8/8/4K2R b - - 150 100"
+///     .parse::<Fen>()
+///     .unwrap()
+///     .into_position(CastlingMode::Standard)
+///     .unwrap();
+/// assert_eq!(strict_game_outcome(&position, &[]), Some(GameOutcome::SeventyFiveMoveRule));
+/// ```
+pub fn strict_game_outcome(position: &Chess, history: &[u64]) -> Option<GameOutcome> {
+    if let Some(outcome) = game_outcome(position) {
+        // Checkmate takes precedence, even on the 150th half-move.
+        return Some(outcome);
+    }
+    if position.halfmoves() >= 150 {
+        return Some(GameOutcome::SeventyFiveMoveRule);
+    }
+    let hash = position_hash(position);
+    if history.iter().filter(|&&h| h == hash).count() + 1 >= 5 {
+        return Some(GameOutcome::FivefoldRepetition);
+    }
+    None
+}
+
+/// The draw that the side to move may claim in this position, if any: threefold repetition or the 50-move rule.
+///
+/// Unlike the endings of [`strict_game_outcome`], these draws only happen if a player claims them.
+/// `history` holds the [`position_hash`] of every earlier position of the game, oldest first.
+pub fn claimable_draw(position: &Chess, history: &[u64]) -> Option<GameOutcome> {
+    if position.halfmoves() >= 100 {
+        return Some(GameOutcome::FiftyMoveRule);
+    }
+    let hash = position_hash(position);
+    if history.iter().filter(|&&h| h == hash).count() + 1 >= 3 {
+        return Some(GameOutcome::ThreefoldRepetition);
+    }
+    None
+}
+
+/// The legal moves in `position`, sorted by their UCI notation.
+///
+/// The order of [`Position::legal_moves`] is not guaranteed to stay the same across shakmaty versions,
+/// so anything that lists moves to clients should use this instead, to keep its output reproducible.
+/// UCI is written in the position's own castling mode, so in Chess960 castling sorts as king-takes-rook.
+pub fn sorted_legal_moves(position: &Chess) -> Vec<Move> {
+    let mode = position.castles().mode();
+    let mut moves: Vec<(String, Move)> = position
+        .legal_moves()
+        .into_iter()
+        .map(|m| (m.to_uci(mode).to_string(), m))
+        .collect();
+    moves.sort_by(|a, b| a.0.cmp(&b.0));
+    moves.into_iter().map(|(_, m)| m).collect()
+}
+
+/// A random legal move in `position`, or None if there are no legal moves.
+///
+/// The same `rand` always picks the same move in the same position, since the moves are chosen from
+/// [`sorted_legal_moves`].
+pub fn random_legal_move(position: &Chess, rand: u64) -> Option<Move> {
+    sorted_legal_moves(position)
+        .choose(&mut StdRng::seed_from_u64(rand))
+        .cloned()
+}
+
+/// The number of leaf positions `depth` plies below `position`, split by the first move, for debugging move generation.
+///
+/// The moves are in the order of [`sorted_legal_moves`]. Their counts add up to `perft(position, depth)`.
+/// At depth 0 there are no moves to split by, so the result is empty.
+///
+/// ```
+/// use engine_trait::{position::perft_divide, shakmaty::Chess};
+///
+/// let divide = perft_divide(&Chess::new(), 3);
+/// assert_eq!(divide.len(), 20);
+/// assert_eq!(divide[0].0.to_string(), "a2a3");
+/// assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+/// ```
+pub fn perft_divide(position: &Chess, depth: u32) -> Vec<(Uci, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    let mode = position.castles().mode();
+    sorted_legal_moves(position)
+        .into_iter()
+        .map(|m| {
+            let mut after = position.clone();
+            after.play_unchecked(&m);
+            (m.to_uci(mode), shakmaty::perft(&after, depth - 1))
+        })
+        .collect()
+}
+
+/// Why [`is_legal`] rejects a move.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IllegalMoveReason {
+    /// A null move is never legal, even though requests use it to mean "no move".
+    NullMove,
+
+    /// The game is already over, so no move is legal.
+    GameOver,
+
+    /// There is no piece on the move's origin square.
+    EmptySquare,
+
+    /// The piece on the origin square belongs to the side not to move.
+    NotYourPiece,
+
+    /// The move breaks the rules in some other way, e.g. it leaves the king in check.
+    NotLegal,
+}
+
+/// Check whether `uci` is a legal move in `position`, and if not, why.
+pub fn is_legal(position: &Chess, uci: &Uci) -> Result<Move, IllegalMoveReason> {
+    if *uci == Uci::Null {
+        return Err(IllegalMoveReason::NullMove);
+    }
+    if let Ok(m) = uci.to_move(position) {
+        return Ok(m);
+    }
+
+    if game_outcome(position).is_some() {
+        return Err(IllegalMoveReason::GameOver);
+    }
+    if let Uci::Normal { from, .. } = uci {
+        match position.board().color_at(*from) {
+            None => return Err(IllegalMoveReason::EmptySquare),
+            Some(color) if color != position.turn() => return Err(IllegalMoveReason::NotYourPiece),
+            Some(_) => {}
+        }
+    }
+    Err(IllegalMoveReason::NotLegal)
+}
+
+/// A hash of the position, for checking cheaply that two parties agree on it.
+///
+/// It covers the board, side to move, castling rights and en passant square, but not the move counters.
+pub fn position_hash(position: &Chess) -> u64 {
+    let hash: Zobrist64 = position.zobrist_hash(EnPassantMode::Legal);
+    hash.0
+}
+
+/// The position as a FEN without the halfmove clock and fullmove number, which is the same for transpositions,
+/// so that it can serve as a key for caches and opening books. The en passant square is only written
+/// if a capture en passant is legal.
+///
+/// ```
+/// use engine_trait::{position::{position_key, replay}, shakmaty::Chess};
+///
+/// let via_knights = replay(&Chess::default(), &["g1f3".parse().unwrap(), "g8f6".parse().unwrap()]).unwrap();
+/// let via_pawns = replay(&Chess::default(), &["e2e3".parse().unwrap(), "e7e6".parse().unwrap()]).unwrap();
+/// let back_again = replay(
+///     &via_knights,
+///     &["f3g1", "f6g8", "g1f3", "g8f6"].map(|uci| uci.parse().unwrap()),
+/// )
+/// .unwrap();
+/// assert_eq!(position_key(&via_knights), "rnbqkb1r/pppppppp/5n2/8/8/5N2/PPPPPPPP/RNBQKB1R w KQkq -");
+/// assert_eq!(position_key(&back_again), position_key(&via_knights));
+/// assert_ne!(position_key(&via_pawns), position_key(&via_knights));
+/// ```
+pub fn position_key(position: &Chess) -> String {
+    Epd::from_position(position.clone(), EnPassantMode::Legal).to_string()
+}
+
+/// Encode a position in a compact binary form, for databases and datasets where FEN takes too much room.
+///
+/// The encoding is the occupied squares as a big-endian bitboard, then a 4-bit code for each occupied square,
+/// from a1 to h8, two to a byte with the first in the low bits. Codes 0 to 5 are White's pawn to king,
+/// 6 to 11 Black's, 12 and 13 a White or Black rook with castling rights, and 14 a pawn that can be taken en passant.
+/// Then comes a byte for the side to move (0 for White) in its low bit, with the next bit set
+/// for positions in [`CastlingMode::Chess960`], and the halfmove clock and fullmove number as LEB128.
+/// A position takes at most 35 bytes; the starting position takes 27.
+///
+/// ```
+/// use engine_trait::{
+///     bench::bench_positions,
+///     position::{chess960_start, decode_position, encode_position, random_legal_move},
+///     shakmaty::{CastlingMode, Chess, Position},
+/// };
+///
+/// let mut positions: Vec<Chess> = bench_positions().into_iter().map(|v| v.position).collect();
+/// // Random games reach castling, en passant and promotions too.
+/// for seed in 0..20 {
+///     let mut position = Chess::default();
+///     for ply in 0..300 {
+///         let Some(m) = random_legal_move(&position, seed * 1000 + ply) else {
+///             break;
+///         };
+///         position.play_unchecked(&m);
+///         positions.push(position.clone());
+///     }
+/// }
+/// // Chess960 positions keep their castling mode, even when their rooks start where standard chess has them.
+/// positions.extend([0, 518, 959].map(|sp| chess960_start(sp).unwrap()));
+/// for position in positions {
+///     let encoded = encode_position(&position);
+///     assert!(encoded.len() <= 35);
+///     let decoded = decode_position(&encoded).unwrap();
+///     assert_eq!(decoded.castles().mode(), position.castles().mode());
+///     assert_eq!(decoded, position);
+/// }
+/// assert_eq!(decode_position(&[0xff; 3]), None);
+/// ```
+pub fn encode_position(position: &Chess) -> Vec<u8> {
+    let board = position.board();
+    let castling_rooks = position.castles().castling_rights();
+    let ep_pawn = position
+        .ep_square(EnPassantMode::Legal)
+        .map(|target| target.xor(Square::A2));
+    let mut encoded = board.occupied().0.to_be_bytes().to_vec();
+    let codes: Vec<u8> = board
+        .occupied()
+        .into_iter()
+        .map(|square| {
+            let piece = board.piece_at(square).expect("the square is occupied");
+            if castling_rooks.contains(square) {
+                12 + piece.color.fold_wb(0, 1)
+            } else if Some(square) == ep_pawn {
+                14
+            } else {
+                piece.role as u8 - 1 + piece.color.fold_wb(0, 6)
+            }
+        })
+        .collect();
+    encoded.extend(
+        codes
+            .chunks(2)
+            .map(|pair| pair[0] | pair.get(1).map_or(0, |v| v << 4)),
+    );
+    let chess960 = position.castles().mode() == CastlingMode::Chess960;
+    encoded.push(position.turn().fold_wb(0, 1) | u8::from(chess960) << 1);
+    write_leb128(&mut encoded, position.halfmoves());
+    write_leb128(&mut encoded, position.fullmoves().get());
+    encoded
+}
+
+/// Decode a position written by [`encode_position`].
+///
+/// Returns None if the bytes are not a valid encoding of a legal position.
+pub fn decode_position(encoded: &[u8]) -> Option<Chess> {
+    let occupied = Bitboard(u64::from_be_bytes(encoded.get(..8)?.try_into().ok()?));
+    let mut rest = &encoded[8..];
+    let code_bytes = occupied.count().div_ceil(2);
+    let codes = rest.get(..code_bytes)?;
+    rest = &rest[code_bytes..];
+    let flags = *rest.first()?;
+    if flags > 3 {
+        return None;
+    }
+    let turn = if flags & 1 == 0 {
+        Color::White
+    } else {
+        Color::Black
+    };
+    rest = &rest[1..];
+
+    let mut setup = Setup::empty();
+    setup.turn = turn;
+    for (i, square) in occupied.into_iter().enumerate() {
+        let code = (codes[i / 2] >> (4 * (i % 2))) & 0x0f;
+        let piece = match code {
+            0..=11 => {
+                Role::ALL[code as usize % 6].of(if code < 6 { Color::White } else { Color::Black })
+            }
+            12 | 13 => {
+                setup.castling_rights.add(square);
+                Role::Rook.of(if code == 12 {
+                    Color::White
+                } else {
+                    Color::Black
+                })
+            }
+            14 => {
+                setup.ep_square = Some(square.xor(Square::A2));
+                Role::Pawn.of(!turn)
+            }
+            _ => return None,
+        };
+        setup.board.set_piece_at(square, piece);
+    }
+    setup.halfmoves = read_leb128(&mut rest)?;
+    setup.fullmoves = NonZeroU32::new(read_leb128(&mut rest)?)?;
+    if !rest.is_empty() {
+        return None;
+    }
+    // Encodings from before the castling mode was written have the bit clear, and detecting the mode suits them.
+    let mode = if flags & 2 != 0 {
+        CastlingMode::Chess960
+    } else {
+        CastlingMode::detect(&setup)
+    };
+    Chess::from_setup(setup, mode).ok()
+}
+
+fn write_leb128(out: &mut Vec<u8>, mut v: u32) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn read_leb128(input: &mut &[u8]) -> Option<u32> {
+    let mut v = 0u32;
+    for shift in (0..35).step_by(7) {
+        let (&byte, rest) = input.split_first()?;
+        *input = rest;
+        v |= u32::from(byte & 0x7f).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(v);
+        }
+    }
+    None
+}
+
+/// Play `moves` in order, starting from `start`.
+///
+/// If a move is not legal where it is played, returns its index in `moves`.
+pub fn replay(start: &Chess, moves: &[Uci]) -> Result<Chess, usize> {
+    let mut position = start.clone();
+    for (i, uci) in moves.iter().enumerate() {
+        let m = uci.to_move(&position).map_err(|_| i)?;
+        position.play_unchecked(&m);
+    }
+    Ok(position)
+}
+
+/// The positions reached by playing each move of `line` from `start`, in order.
+///
+/// If a move is not legal where it is played, returns its index in `line`.
+pub fn line_positions(start: &Chess, line: &[Move]) -> Result<Vec<Chess>, usize> {
+    let mut position = start.clone();
+    let mut positions = Vec::with_capacity(line.len());
+    for (i, m) in line.iter().enumerate() {
+        if !position.is_legal(m) {
+            return Err(i);
+        }
+        position.play_unchecked(m);
+        positions.push(position.clone());
+    }
+    Ok(positions)
+}
+
+/// Whether playing `line` from `position` is legal and ends in checkmate.
+pub fn is_mating_line(position: &Chess, line: &[Move]) -> bool {
+    let mut position = position.clone();
+    for m in line {
+        if !position.is_legal(m) {
+            return false;
+        }
+        position.play_unchecked(m);
+    }
+    position.is_checkmate()
+}
+
+/// Write `pv` in SAN, playing it from `start` so that each move is disambiguated and suffixed in its own position.
+///
+/// The line stops before the first move that is not legal where it is played.
+///
+/// ```
+/// use engine_trait::{
+///     position::pv_to_san,
+///     shakmaty::{uci::Uci, Chess, Position},
+/// };
+///
+/// let mut position = Chess::default();
+/// let mut pv = Vec::new();
+/// for uci in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+///     let m = uci.parse::<Uci>().unwrap().to_move(&position).unwrap();
+///     position.play_unchecked(&m);
+///     pv.push(m);
+/// }
+/// assert_eq!(pv_to_san(&Chess::default(), &pv), ["f3", "e5", "g4", "Qh4#"]);
+///
+/// // Nothing can be played after mate, so a move past it is dropped.
+/// pv.push(pv[0].clone());
+/// assert_eq!(pv_to_san(&Chess::default(), &pv).len(), 4);
+/// ```
+pub fn pv_to_san(start: &Chess, pv: &[Move]) -> Vec<String> {
+    let mut position = start.clone();
+    let mut sans = Vec::with_capacity(pv.len());
+    for m in pv {
+        if !position.is_legal(m) {
+            break;
+        }
+        sans.push(SanPlus::from_move_and_play_unchecked(&mut position, m).to_string());
+    }
+    sans
+}
+
+/// How much of its origin square a piece move needs in SAN to tell it apart from other moves to the same square.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Disambiguation {
+    /// No other piece of the same kind can move there, as in `Nf3`.
+    None,
+
+    /// The origin file, as in `Rad1`.
+    File,
+
+    /// The origin rank, as in `R1a4`.
+    Rank,
+
+    /// The whole origin square, as in `Qa1b2`.
+    Square,
+}
+
+/// The least disambiguation that `m` needs in SAN in `position`, as shakmaty writes it.
+///
+/// Pawn captures always name their origin file, but that is part of how pawn moves are written,
+/// not disambiguation, so pawn moves, like castling and null moves, never need any.
+///
+/// ```
+/// use engine_trait::{
+///     position::{san_disambiguation, Disambiguation},
+///     shakmaty::{fen::Fen, uci::Uci, CastlingMode, Chess},
+/// };
+///
+/// let check = |fen: &str, uci: &str, expected: Disambiguation| {
+///     let position: Chess = fen.parse::<Fen>().unwrap().into_position(CastlingMode::Standard).unwrap();
+///     let m = uci.parse::<Uci>().unwrap().to_move(&position).unwrap();
+///     assert_eq!(san_disambiguation(&position, &m), expected);
+/// };
+/// check("4k3/8/8/8/8/8/4K3/R6R w - - 0 1", "a1d1", Disambiguation::File);
+/// check("R7/7k/8/8/8/8/4K3/R7 w - - 0 1", "a1a4", Disambiguation::Rank);
+/// check("4k3/8/8/8/8/Q7/4K3/Q1Q5 w - - 0 1", "a1b2", Disambiguation::Square);
+/// check("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1d1", Disambiguation::None);
+/// check("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", "e4d5", Disambiguation::None);
+/// ```
+pub fn san_disambiguation(position: &Chess, m: &Move) -> Disambiguation {
+    match San::from_move(position, m) {
+        San::Normal {
+            role: Role::Pawn, ..
+        } => Disambiguation::None,
+        San::Normal { file, rank, .. } => match (file, rank) {
+            (None, None) => Disambiguation::None,
+            (Some(_), None) => Disambiguation::File,
+            (None, Some(_)) => Disambiguation::Rank,
+            (Some(_), Some(_)) => Disambiguation::Square,
+        },
+        _ => Disambiguation::None,
+    }
+}
+
+/// Write a move in ICCF numeric notation, as used in correspondence chess.
+///
+/// Each square is its file and rank as digits (`a1` is `11`, `h8` is `88`),
+/// castling is written as the king's move to its destination, also in Chess960,
+/// and a promotion adds a digit: 1 queen, 2 rook, 3 bishop, 4 knight.
+/// Drops and null moves have no ICCF form, so they give None.
+///
+/// ```
+/// use engine_trait::{
+///     position::{move_from_iccf, move_to_iccf},
+///     shakmaty::{fen::Fen, CastlingMode, Chess, Move, Square},
+/// };
+///
+/// let position: Chess = "r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1"
+///     .parse::<Fen>()
+///     .unwrap()
+///     .into_position(CastlingMode::Standard)
+///     .unwrap();
+/// for (iccf, uci) in [("5171", "e1g1"), ("5131", "e1c1"), ("27184", "b7a8n"), ("27281", "b7b8q")] {
+///     let m = move_from_iccf(&position, iccf).unwrap();
+///     assert_eq!(m.to_uci(CastlingMode::Standard).to_string(), uci);
+///     assert_eq!(move_to_iccf(&m).unwrap(), iccf);
+/// }
+/// assert_eq!(move_from_iccf(&position, "5155"), None);
+/// assert_eq!(move_to_iccf(&Move::Put { role: shakmaty::Role::Pawn, to: Square::E4 }), None);
+///
+/// // In Chess960, the king on b1 castles short to g1, not onto its rook on h1.
+/// let chess960: Chess = "1k6/8/8/8/8/8/8/RK5R w HA - 0 1"
+///     .parse::<Fen>()
+///     .unwrap()
+///     .into_position(CastlingMode::Chess960)
+///     .unwrap();
+/// let castle = move_from_iccf(&chess960, "2171").unwrap();
+/// assert_eq!(castle, Move::Castle { king: Square::B1, rook: Square::H1 });
+/// assert_eq!(move_to_iccf(&castle).unwrap(), "2171");
+/// ```
+pub fn move_to_iccf(m: &Move) -> Option<String> {
+    let square = |sq: Square| format!("{}{}", u32::from(sq.file()) + 1, u32::from(sq.rank()) + 1);
+    // The standard form writes castling as the king's move to its destination, whatever the castling mode.
+    match Uci::from_standard(m) {
+        Uci::Normal {
+            from,
+            to,
+            promotion,
+        } => {
+            let promotion = match promotion {
+                Some(Role::Queen) => "1",
+                Some(Role::Rook) => "2",
+                Some(Role::Bishop) => "3",
+                Some(Role::Knight) => "4",
+                _ => "",
+            };
+            Some(format!("{}{}{promotion}", square(from), square(to)))
+        }
+        // Drops and null moves do not exist in correspondence chess.
+        Uci::Put { .. } | Uci::Null => None,
+    }
+}
+
+/// Read a move in ICCF numeric notation (see [`move_to_iccf`]), if it is legal in `position`.
+///
+/// In Chess960, castling can move the king one square or not at all, and so read like a plain king move;
+/// when both are legal, the plain king move is returned.
+pub fn move_from_iccf(position: &Chess, iccf: &str) -> Option<Move> {
+    position
+        .legal_moves()
+        .into_iter()
+        .filter(|m| move_to_iccf(m).as_deref() == Some(iccf))
+        .min_by_key(Move::is_castle)
+}
@@ -0,0 +1,109 @@
+//! Independent random streams for engines that need more than one `u64`.
+//!
+//! Engines are given a single random number per call.
+//! [`RandomSource`] expands it into any number of independent, reproducible streams,
+//! so that e.g. move ordering and tie-breaking do not share one RNG.
+//!
+//! ```
+//! use engine_trait::random::RandomSource;
+//! use rand::RngCore;
+//!
+//! let source = RandomSource::new(42);
+//! let mut ordering = source.stream(0);
+//! let mut tie_breaks = source.stream(1);
+//! assert_ne!(ordering.next_u64(), tie_breaks.next_u64());
+//!
+//! // The same seed always gives the same streams.
+//! assert_eq!(source.stream(1).next_u64(), RandomSource::new(42).stream(1).next_u64());
+//! ```
+
+use rand::{rngs::StdRng, SeedableRng};
+
+/// A seed, and optionally explicit seeds for the first few streams.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RandomSource {
+    seed: u64,
+    streams: Vec<u64>,
+}
+
+impl RandomSource {
+    /// A source whose streams are all derived from `seed`.
+    pub fn new(seed: u64) -> RandomSource {
+        RandomSource {
+            seed,
+            streams: Vec::new(),
+        }
+    }
+
+    /// A source whose first streams are seeded with `streams`, and whose later streams are derived from `seed`.
+    pub fn with_streams(seed: u64, streams: Vec<u64>) -> RandomSource {
+        RandomSource { seed, streams }
+    }
+
+    /// The single seed, as given to [`Engine::propose_move`](crate::Engine::propose_move).
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The seed of the stream with this index.
+    pub fn stream_seed(&self, index: usize) -> u64 {
+        match self.streams.get(index) {
+            Some(seed) => *seed,
+            None => splitmix64(self.seed ^ splitmix64(index as u64)),
+        }
+    }
+
+    /// An RNG for the stream with this index.
+    pub fn stream(&self, index: usize) -> StdRng {
+        StdRng::seed_from_u64(self.stream_seed(index))
+    }
+}
+
+/// Pick one of `candidates` with a probability proportional to its weight, using only `rand`.
+///
+/// This is how an engine can vary its openings without breaking reproducibility:
+/// the same state, position and seed still give the same move, but different seeds spread over the good moves.
+/// Candidates with weights that are not positive, or not finite, are never picked.
+/// Returns None if no candidate can be picked.
+///
+/// ```
+/// use engine_trait::random::weighted_choice;
+///
+/// let openings = [("e2e4", 5.0), ("d2d4", 4.0), ("c2c4", 1.0), ("g2g4", 0.0)];
+/// let picks: Vec<_> = (0..1000).map(|seed| *weighted_choice(seed, &openings).unwrap()).collect();
+/// assert!(picks.iter().filter(|&&m| m == "e2e4").count() > picks.iter().filter(|&&m| m == "c2c4").count());
+/// assert!(!picks.contains(&"g2g4"));
+/// assert_eq!(weighted_choice(7, &openings), weighted_choice(7, &openings));
+/// assert_eq!(weighted_choice::<&str>(7, &[]), None);
+/// ```
+pub fn weighted_choice<T>(rand: u64, candidates: &[(T, f64)]) -> Option<&T> {
+    let usable = |weight: f64| weight.is_finite() && weight > 0.0;
+    let total: f64 = candidates
+        .iter()
+        .map(|&(_, weight)| weight)
+        .filter(|&weight| usable(weight))
+        .sum();
+    // The top 53 bits make a uniform float in [0, 1).
+    let mut target = (splitmix64(rand) >> 11) as f64 / (1u64 << 53) as f64 * total;
+    let mut last = None;
+    for (candidate, weight) in candidates {
+        if !usable(*weight) {
+            continue;
+        }
+        if target < *weight {
+            return Some(candidate);
+        }
+        target -= weight;
+        last = Some(candidate);
+    }
+    // Rounding can leave a sliver past the last weight.
+    last
+}
+
+/// One step of the SplitMix64 generator, which scrambles similar inputs into unrelated outputs.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
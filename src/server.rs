@@ -1,131 +1,1578 @@
-use std::sync::Arc;
+use std::{
+    fmt::Debug,
+    future::Future,
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use axum::{extract::State, routing::get, Json, Router};
-use shakmaty::{uci::Uci, Position};
-use tokio::sync::Mutex;
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{FromRequest, Query, State},
+    handler::Handler,
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+        Method, Request, StatusCode,
+    },
+    middleware::{self, Next},
+    response::{
+        sse::{Event, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use futures_util::{future, stream, Stream, StreamExt};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use serde_json::Value;
+use shakmaty::{fen::Fen, uci::Uci, Chess, EnPassantMode, Position};
+use tokio::{
+    runtime::Handle,
+    sync::{Mutex, Semaphore},
+};
+use tower::ServiceExt;
 
+#[cfg(feature = "syzygy")]
+use crate::server_types::{TablebaseRequest, TablebaseResponse};
 use crate::{
-    server_types::{EngineInfo, EngineRequest, EngineRequestError, EngineResponse, EngineResult},
+    breaker::CircuitBreaker,
+    features::{features, hanging_pieces, PositionFeatures},
+    game::{
+        amend_turn, check_supported, hint, play_turn_with, search_tree, threat_move,
+        IllegalMovePolicy, TurnError,
+    },
+    game_log::{valid_game_id, GameLog},
+    pgn::read_pgn_game,
+    position::{
+        decode_position, encode_position, game_outcome, is_legal, perft_divide, position_hash,
+        replay, sorted_legal_moves,
+    },
+    random::RandomSource,
+    server_types::{
+        AmendRequest, AnalyzePgnRequest, AnalyzePgnResponse, AnalyzedPly, ApplyMovesRequest,
+        ApplyMovesResponse, ClaimWinRequest, ClaimWinResponse, CompareResponse, CompareSide,
+        ComplexityRequest, ComplexityResponse, DecodePositionRequest, DecodePositionResponse,
+        EncodePositionRequest, EncodePositionResponse, EngineInfo, EngineInternalError,
+        EngineRequest, EngineRequestError, EngineResponse, EngineResult, FeaturesRequest,
+        GameOutcome, HintRequest, HintResponse, IsLegalRequest, IsLegalResponse, LegalMovesRequest,
+        LegalMovesResponse, NewGamesRequest, NewGamesResponse, PerftDivideRequest,
+        PerftDivideResponse, PremoveCheckRequest, PremoveCheckResponse, ReviewRequest, ReviewStart,
+        ReviewedPly, SeedOrigin, SelfplayMove, SelfplayQuery, ThreatMoveResponse, ThreatsRequest,
+        ThreatsResponse, TreeRequest, TreeResponse, VersionInfo, SUPPORTED_PROTOCOL_VERSIONS,
+    },
     Engine,
 };
 
+/// Decides whether a position is acceptable as a request's `game_before`.
+pub type PositionFilter = Arc<dyn Fn(&Chess) -> bool + Send + Sync>;
+
+/// Options for how [`serve_engine_with`] serves an engine.
+#[derive(Clone, Default)]
+pub struct ServerConfig {
+    /// If set, status info that serializes to more than this many bytes is dropped from responses,
+    /// and the response's `status_info_truncated` flag is set instead.
+    pub max_status_info_bytes: Option<usize>,
+
+    /// If set, requests whose `game_before` does not pass this filter are rejected
+    /// with [`EngineRequestError::PositionNotAllowed`].
+    pub allowed_positions: Option<PositionFilter>,
+
+    /// If set, every completed game is appended to this file in PGN format.
+    /// Only requests with a `game_id` are logged, since that is how turns are grouped into games.
+    /// Requests whose `game_id` has control characters are rejected with [`EngineRequestError::InvalidGameId`],
+    /// and at most 10,000 unfinished games are followed at once; past that, the stalest one is dropped.
+    ///
+    /// Games without a result, such as aborted ones, are logged with the result `*`:
+    ///
+    /// ```
+    /// use axum::{body::Body, http::{header::CONTENT_TYPE, Request}};
+    /// use engine_trait::server::{serve_engine_with, ServerConfig};
+    /// use tower::ServiceExt;
+    /// # use engine_trait::fixtures::Aborter;
+    ///
+    /// let path = std::env::temp_dir().join(format!("engine-trait-log-{}.pgn", std::process::id()));
+    /// let config = ServerConfig { game_log_path: Some(path.clone()), ..ServerConfig::default() };
+    /// # tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap().block_on(async {
+    /// let router = serve_engine_with(Aborter, config).await;
+    /// let body = r#"{"move": "e2e4", "with_status_info": false, "game_id": "say \"hi\""}"#;
+    /// let request = Request::post("/").header(CONTENT_TYPE, "application/json").body(Body::from(body)).unwrap();
+    /// assert!(router.oneshot(request).await.unwrap().status().is_success());
+    ///
+    /// // The log is written in the background.
+    /// let mut pgn = String::new();
+    /// for _ in 0..500 {
+    ///     pgn = std::fs::read_to_string(&path).unwrap_or_default();
+    ///     if !pgn.is_empty() {
+    ///         break;
+    ///     }
+    ///     tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    /// }
+    /// std::fs::remove_file(&path).unwrap();
+    /// assert!(pgn.contains("[Result \"*\"]\n[GameId \"say \\\"hi\\\"\"]\n"));
+    /// assert!(pgn.ends_with("1. e4 *\n\n"));
+    /// # });
+    /// ```
+    pub game_log_path: Option<PathBuf>,
+
+    /// If set, move requests are turned away with `503 Service Unavailable` for a while
+    /// once the engine has failed too many times in a row, and `/ready` reports the server as not ready.
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+
+    /// If set, the engine's turns for move requests and self-play run on this runtime
+    /// instead of the one serving HTTP, so that operators can bound the threads an engine uses.
+    pub runtime: Option<Handle>,
+
+    /// If set, every move request and self-play game follows the strict rules,
+    /// ending automatically in a draw on fivefold repetition or the 75-move rule.
+    /// See [`EngineRequest::strict_rules`]; clients still need to send the position history.
+    pub strict_rules: bool,
+
+    /// What to do when the engine proposes an illegal move. By default, the move request fails.
+    pub on_illegal_move: IllegalMovePolicy,
+
+    /// If set, move requests wait while the engine states of the requests already in flight
+    /// add up to more than this many bytes, so that many large states at once cannot exhaust memory.
+    ///
+    /// A request's state is estimated by the size of its body, from its `Content-Length` header,
+    /// since the state makes up most of it and takes about as much memory once read.
+    /// Requests without a `Content-Length` count as the whole budget, and so run alone.
+    /// This is separate from the engine lock, which only bounds how many turns compute at once.
+    ///
+    /// A request that is still sending its body holds its share, so the next one is not even read:
+    ///
+    /// ```
+    /// use std::{sync::{atomic::{AtomicBool, Ordering}, Arc}, time::Duration};
+    ///
+    /// use axum::{body::Body, http::{header::{CONTENT_LENGTH, CONTENT_TYPE}, Request}, Router};
+    /// use engine_trait::server::{serve_engine_with, ServerConfig};
+    /// use futures_util::stream;
+    /// use tower::ServiceExt;
+    /// # use engine_trait::fixtures::FirstMove;
+    ///
+    /// const MOVE: &str = r#"{"move": "e2e4", "with_status_info": false}"#;
+    ///
+    /// fn post(body: Body) -> Request<Body> {
+    ///     Request::post("/")
+    ///         .header(CONTENT_TYPE, "application/json")
+    ///         .header(CONTENT_LENGTH, MOVE.len())
+    ///         .body(body)
+    ///         .unwrap()
+    /// }
+    ///
+    /// /// Whether the second of two requests is read while the first is still sending its body.
+    /// async fn read_together(router: Router) -> bool {
+    ///     let (send, sent) = tokio::sync::oneshot::channel::<()>();
+    ///     let slow = Body::wrap_stream(stream::once(async move {
+    ///         sent.await.unwrap();
+    ///         Ok::<_, std::io::Error>(MOVE)
+    ///     }));
+    ///     let read = Arc::new(AtomicBool::new(false));
+    ///     let flag = read.clone();
+    ///     let fast = Body::wrap_stream(stream::once(async move {
+    ///         flag.store(true, Ordering::SeqCst);
+    ///         Ok::<_, std::io::Error>(MOVE)
+    ///     }));
+    ///
+    ///     let first = tokio::spawn(router.clone().oneshot(post(slow)));
+    ///     tokio::time::sleep(Duration::from_millis(20)).await;
+    ///     let second = tokio::spawn(router.oneshot(post(fast)));
+    ///     tokio::time::sleep(Duration::from_millis(20)).await;
+    ///     let together = read.load(Ordering::SeqCst);
+    ///     send.send(()).unwrap();
+    ///     assert!(first.await.unwrap().unwrap().status().is_success());
+    ///     assert!(second.await.unwrap().unwrap().status().is_success());
+    ///     together
+    /// }
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap().block_on(async {
+    /// assert!(read_together(serve_engine_with(FirstMove, ServerConfig::default()).await).await);
+    ///
+    /// // Each request is estimated at a whole KiB, which is all of this budget.
+    /// let config = ServerConfig { max_in_flight_state_bytes: Some(1024), ..ServerConfig::default() };
+    /// assert!(!read_together(serve_engine_with(FirstMove, config).await).await);
+    /// # });
+    /// ```
+    pub max_in_flight_state_bytes: Option<usize>,
+
+    /// If set, a streaming connection such as `/selfplay/stream` is closed when no new event is ready
+    /// within this long, after a final `timeout` event, so that a stuck engine cannot hold it open forever.
+    /// The engine's turn is not interrupted; it still runs to completion in the background.
+    pub stream_idle_timeout: Option<Duration>,
+
+    /// If set, a warning is logged through `tracing` whenever the engine takes longer than this for a turn,
+    /// with the FEN of the position it moved in and how long it took, to find pathological positions.
+    /// The time is mostly [`Engine::propose_move`], but observing the moves on either side counts too;
+    /// waiting for the engine lock does not.
+    pub slow_move_threshold: Option<Duration>,
+
+    /// If set, positions can be looked up in the Syzygy tables in this directory with `/tablebase`.
+    /// This needs the `syzygy` feature; without it, the path is ignored with a warning.
+    /// The field exists either way, so that configs build the same with and without the feature.
+    pub tablebase_path: Option<PathBuf>,
+}
+
+impl Debug for ServerConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServerConfig")
+            .field("max_status_info_bytes", &self.max_status_info_bytes)
+            .field("allowed_positions", &self.allowed_positions.is_some())
+            .field("game_log_path", &self.game_log_path)
+            .field("circuit_breaker", &self.circuit_breaker)
+            .field("runtime", &self.runtime)
+            .field("strict_rules", &self.strict_rules)
+            .field("on_illegal_move", &self.on_illegal_move)
+            .field("max_in_flight_state_bytes", &self.max_in_flight_state_bytes)
+            .field("stream_idle_timeout", &self.stream_idle_timeout)
+            .field("slow_move_threshold", &self.slow_move_threshold)
+            .field("tablebase_path", &self.tablebase_path)
+            .finish()
+    }
+}
+
+/// When to stop calling a failing engine. See [`ServerConfig::circuit_breaker`].
+#[derive(Clone, Debug)]
+pub struct CircuitBreakerConfig {
+    /// How many engine errors in a row open the breaker.
+    pub failure_threshold: u32,
+
+    /// How long the breaker stays open before the engine is tried again.
+    pub cooldown: Duration,
+}
+
+struct ServerState<E> {
+    engine: Mutex<E>,
+    config: ServerConfig,
+    game_log: Option<GameLog>,
+    breaker: Option<CircuitBreaker>,
+    state_memory: Option<MemoryBudget>,
+    /// The tables from [`ServerConfig::tablebase_path`], or why they could not be opened.
+    #[cfg(feature = "syzygy")]
+    tablebase: Option<Result<crate::syzygy::Tablebase, String>>,
+}
+
+/// The memory for engine states that move requests share, counted in KiB.
+/// See [`ServerConfig::max_in_flight_state_bytes`].
+struct MemoryBudget {
+    semaphore: Semaphore,
+    kib: u32,
+}
+
+impl MemoryBudget {
+    fn new(bytes: usize) -> MemoryBudget {
+        let kib = u32::try_from(bytes / 1024)
+            .unwrap_or(u32::MAX)
+            .clamp(1, Semaphore::MAX_PERMITS as u32);
+        MemoryBudget {
+            semaphore: Semaphore::new(kib as usize),
+            kib,
+        }
+    }
+}
+
+type SharedState<E> = Arc<ServerState<E>>;
+
+/// Serve an engine with the default [`ServerConfig`].
+///
+/// Every route answers methods it does not support with `405 Method Not Allowed`,
+/// and an `Allow` header listing the methods it does support:
+///
+/// ```
+/// use axum::{body::Body, http::{header::ALLOW, Method, Request, StatusCode}};
+/// use engine_trait::server::serve_engine;
+/// # use engine_trait::fixtures::FirstMove;
+/// use tower::ServiceExt;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let router = serve_engine(FirstMove).await;
+/// let request = Request::builder().method(Method::PUT).uri("/").body(Body::empty()).unwrap();
+/// let response = router.oneshot(request).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+/// assert_eq!(response.headers()[ALLOW], "GET,HEAD,POST");
+/// # });
+/// ```
 pub async fn serve_engine<E: Engine + 'static>(engine: E) -> Router {
-    Router::new()
-        .route("/", get(get_info).post(handle_move))
-        .with_state(Arc::new(Mutex::new(engine)))
+    serve_engine_with(engine, ServerConfig::default()).await
 }
 
-async fn get_info<E: Engine>(State(_): State<Arc<Mutex<E>>>) -> Json<EngineInfo<E>> {
-    Json(E::get_info())
+/// Serve an engine with the default [`ServerConfig`] under `prefix`, such as `/api/engine`,
+/// for mounting it behind a reverse proxy or next to other routes.
+///
+/// Every route, `/health` and `/ready` included, moves under the prefix, and `prefix` itself answers like `/`.
+/// Leading and trailing slashes are optional. An empty prefix, or `/`, serves the engine at the root,
+/// just like [`serve_engine`].
+///
+/// ```
+/// use axum::{body::Body, http::{Request, StatusCode}};
+/// use engine_trait::{lookup::LookupEngine, server::serve_engine_nested};
+/// use tower::ServiceExt;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let router = serve_engine_nested("/api/engine", LookupEngine::new()).await;
+/// for (uri, status) in [
+///     ("/api/engine", StatusCode::OK),
+///     ("/api/engine/health", StatusCode::OK),
+///     ("/health", StatusCode::NOT_FOUND),
+/// ] {
+///     let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+///     assert_eq!(router.clone().oneshot(request).await.unwrap().status(), status, "{uri}");
+/// }
+/// # });
+/// ```
+pub async fn serve_engine_nested<E: Engine + 'static>(prefix: &str, engine: E) -> Router {
+    let router = serve_engine(engine).await;
+    match prefix.trim_matches('/') {
+        "" => router,
+        prefix => Router::new().nest(&format!("/{prefix}"), router),
+    }
 }
 
-async fn handle_move<E: Engine>(
-    State(e): State<Arc<Mutex<E>>>,
-    Json(request): Json<EngineRequest<E>>,
-) -> EngineResult<E> {
-    let observe_other_rand_used;
-    let produce_rand_used;
-    let observe_mine_rand_used;
-
-    let mut state = request.engine_state;
-
-    // If the move is a null move, skip processing it
-    let their_move = request.r#move;
-    let game_after = if their_move != Uci::Null {
-        // Try parsing the UCI into a move.
-        let user_move = match their_move.to_move(&request.game_before) {
-            Ok(user_move) => user_move,
+/// Serve an engine with the default [`ServerConfig`] on `listener`, in the background,
+/// and return the address it is listening on.
+///
+/// This is meant for tests: bind the listener to port 0, and the operating system picks a free port.
+/// The server keeps running until the runtime shuts down.
+///
+/// ```
+/// use std::{io::{Read, Write}, net::{TcpListener, TcpStream}};
+///
+/// use engine_trait::server::serve_engine_on;
+/// # use engine_trait::fixtures::FirstMove;
+///
+/// # tokio::runtime::Builder::new_current_thread().enable_io().build().unwrap().block_on(async {
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let addr = serve_engine_on(FirstMove, listener).await.unwrap();
+/// assert_ne!(addr.port(), 0);
+///
+/// // The client blocks, so it runs off the runtime that serves the engine.
+/// let response = tokio::task::spawn_blocking(move || {
+///     let mut stream = TcpStream::connect(addr).unwrap();
+///     stream.write_all(b"GET /health HTTP/1.0\r\n\r\n").unwrap();
+///     let mut response = String::new();
+///     stream.read_to_string(&mut response).unwrap();
+///     response
+/// })
+/// .await
+/// .unwrap();
+/// assert!(response.starts_with("HTTP/1.0 200 OK"));
+/// # });
+/// ```
+pub async fn serve_engine_on<E: Engine + 'static>(
+    engine: E,
+    listener: TcpListener,
+) -> std::io::Result<SocketAddr> {
+    let addr = listener.local_addr()?;
+    let router = serve_engine(engine).await;
+    let server = axum::Server::from_tcp(listener)
+        .map_err(std::io::Error::other)?
+        .serve(router.into_make_service());
+    tokio::spawn(server);
+    Ok(addr)
+}
+
+/// Serve two engines side by side under `/a` and `/b`, for comparing them on identical requests.
+///
+/// Each engine gets all the routes of [`serve_engine_with`], with its own configuration.
+/// `/health` and `/ready` cover both engines, and `POST /compare` sends a move request to both,
+/// answering with both of their responses in a [`CompareResponse`].
+///
+/// ```
+/// use axum::{body::{Body, HttpBody}, http::{header::CONTENT_TYPE, Request, StatusCode}};
+/// use engine_trait::{lookup::LookupEngine, server::{serve_ab, ServerConfig}, server_types::CompareResponse};
+/// use tower::ServiceExt;
+/// # use engine_trait::fixtures::FirstMove;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let router = serve_ab(FirstMove, ServerConfig::default(), LookupEngine::new(), ServerConfig::default()).await;
+/// let ready = router.clone().oneshot(Request::get("/ready").body(Body::empty()).unwrap()).await.unwrap();
+/// assert_eq!(ready.status(), StatusCode::OK);
+///
+/// let body = r#"{"move": "e2e4", "with_status_info": false}"#;
+/// let request = Request::post("/compare").header(CONTENT_TYPE, "application/json").body(Body::from(body)).unwrap();
+/// let mut body = router.oneshot(request).await.unwrap().into_body();
+/// let mut bytes = Vec::new();
+/// while let Some(chunk) = body.data().await {
+///     bytes.extend_from_slice(&chunk.unwrap());
+/// }
+/// let compared: CompareResponse = serde_json::from_slice(&bytes).unwrap();
+/// assert_eq!((compared.a.status, compared.b.status), (200, 200));
+/// assert!(compared.a.body["move"].is_string() && compared.b.body["move"].is_string());
+/// # });
+/// ```
+pub async fn serve_ab<A: Engine + 'static, B: Engine + 'static>(
+    a: A,
+    a_config: ServerConfig,
+    b: B,
+    b_config: ServerConfig,
+) -> Router {
+    let a = serve_engine_with(a, a_config).await;
+    let b = serve_engine_with(b, b_config).await;
+    // Routers are not `Sync`, so they cannot be shared state; each handler owns the pair, and clones it for every call.
+    let ready = {
+        let (a, b) = (a.clone(), b.clone());
+        move || get_ab_ready(a, b)
+    };
+    let compare = {
+        let (a, b) = (a.clone(), b.clone());
+        move |body: Bytes| compare(a, b, body)
+    };
+    Router::new()
+        .route("/health", get(|| async { StatusCode::OK }))
+        .route("/ready", get(ready))
+        .route("/compare", post(compare))
+        .nest("/a", a)
+        .nest("/b", b)
+}
+
+/// Send a request to one engine's router, and read its whole JSON answer.
+/// An answer that cannot be read or parsed is reported as a null body, with the engine's status.
+async fn call_router(router: Router, method: Method, uri: &str, body: Bytes) -> CompareSide {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .expect("the request is well-formed");
+    let response = match router.oneshot(request).await {
+        Ok(v) => v,
+        Err(infallible) => match infallible {},
+    };
+    let status = response.status().as_u16();
+    let mut body = response.into_body();
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        match chunk {
+            Ok(chunk) => bytes.extend_from_slice(&chunk),
             Err(_) => {
-                return EngineResult::RequestError(EngineRequestError::PositionMoveMismatch);
+                return CompareSide {
+                    status,
+                    body: Value::Null,
+                }
             }
-        };
+        }
+    }
+    CompareSide {
+        status,
+        body: serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    }
+}
+
+/// Both engines are ready.
+async fn get_ab_ready(a: Router, b: Router) -> StatusCode {
+    let (a, b) = future::join(
+        call_router(a, Method::GET, "/ready", Bytes::new()),
+        call_router(b, Method::GET, "/ready", Bytes::new()),
+    )
+    .await;
+    if a.status == StatusCode::OK.as_u16() && b.status == StatusCode::OK.as_u16() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
 
-        // Apply the move to the board.
-        let mut game_after = request.game_before.clone();
-        game_after.play_unchecked(&user_move);
+/// Send the same move request to both engines.
+async fn compare(a: Router, b: Router, body: Bytes) -> Json<CompareResponse> {
+    let (a, b) = future::join(
+        call_router(a, Method::POST, "/", body.clone()),
+        call_router(b, Method::POST, "/", body),
+    )
+    .await;
+    Json(CompareResponse { a, b })
+}
 
-        // The engine needs to observe this move.
-        {
-            let observe_rand = if let Some(v) = request.observe_mine_rand {
-                v
-            } else {
-                rand::random()
-            };
-            observe_other_rand_used = Some(observe_rand);
-            let mut engine = e.lock().await;
-            if let Err(why) = engine
-                .observe_move(observe_rand, &mut state, &user_move, &game_after)
-                .await
-            {
-                return EngineResult::EngineError(why);
-            }
+/// Serve an engine with the given configuration.
+///
+/// Requests that reach the engine are turned away if they declare a `protocol_version` that the server cannot read:
+///
+/// ```
+/// use axum::{body::{Body, HttpBody}, http::{header::CONTENT_TYPE, Request, StatusCode}};
+/// use engine_trait::{lookup::LookupEngine, server::{serve_engine_with, ServerConfig}};
+/// use tower::ServiceExt;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let router = serve_engine_with(LookupEngine::new(), ServerConfig::default()).await;
+/// for uri in ["/", "/amend", "/hint"] {
+///     let body = r#"{"move": "e2e4", "last_move": "e2e4", "position": null, "protocol_version": 99}"#;
+///     let request = Request::post(uri).header(CONTENT_TYPE, "application/json").body(Body::from(body)).unwrap();
+///     let response = router.clone().oneshot(request).await.unwrap();
+///     assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+///     let body = response.into_body().data().await.unwrap().unwrap();
+///     let why: serde_json::Value = serde_json::from_slice(&body).unwrap();
+///     assert_eq!(why["UnsupportedProtocolVersion"]["version"], 99, "{uri}");
+/// }
+/// # });
+/// ```
+pub async fn serve_engine_with<E: Engine + 'static>(engine: E, config: ServerConfig) -> Router {
+    #[cfg(not(feature = "syzygy"))]
+    if let Some(path) = &config.tablebase_path {
+        tracing::warn!(
+            path = %path.display(),
+            "tablebase_path is set, but the syzygy feature is not enabled, so no tables are served"
+        );
+    }
+    let state = Arc::new(ServerState {
+        engine: Mutex::new(engine),
+        game_log: config.game_log_path.clone().map(GameLog::new),
+        breaker: config.circuit_breaker.as_ref().map(CircuitBreaker::new),
+        state_memory: config.max_in_flight_state_bytes.map(MemoryBudget::new),
+        #[cfg(feature = "syzygy")]
+        tablebase: config.tablebase_path.as_ref().map(|path| {
+            crate::syzygy::Tablebase::open(path)
+                .map_err(|why| format!("cannot open {}: {why}", path.display()))
+        }),
+        config,
+    });
+    let router = Router::new()
+        .route(
+            "/",
+            get(get_info).post(handle_move.layer(middleware::from_fn_with_state(
+                state.clone(),
+                limit_state_memory,
+            ))),
+        )
+        .route("/version", get(get_version))
+        .route("/health", get(get_health))
+        .route("/ready", get(get_ready))
+        .route("/complexity", post(get_complexity))
+        .route("/analyze-pgn", post(analyze_pgn))
+        .route("/apply-moves", post(apply_moves))
+        .route("/legal-moves", post(legal_moves))
+        .route("/is-legal", post(check_legal))
+        .route("/premove-check", post(check_premove))
+        .route("/perft/divide", post(get_perft_divide))
+        .route("/features", post(get_features))
+        .route("/threats", post(get_threats))
+        .route("/encode-position", post(encode))
+        .route("/decode-position", post(decode))
+        .route("/hint", post(get_hint))
+        .route("/threat-move", post(get_threat_move))
+        .route("/tree", post(get_tree))
+        .route(
+            "/amend",
+            post(amend.layer(middleware::from_fn_with_state(
+                state.clone(),
+                limit_state_memory,
+            ))),
+        )
+        .route("/claim-win", post(claim_win))
+        .route("/new-games", post(new_games::<E>))
+        .route("/selfplay/stream", get(selfplay_stream))
+        .route("/review", post(review));
+    #[cfg(feature = "debug-endpoints")]
+    let router = router.route("/echo", post(echo));
+    #[cfg(feature = "syzygy")]
+    let router = router.route("/tablebase", post(probe_tablebase));
+    router.with_state(state)
+}
+
+/// Hold a move request back until its estimated share of the state memory budget is free.
+async fn limit_state_memory<E: Engine>(
+    State(s): State<SharedState<E>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let Some(budget) = &s.state_memory else {
+        return next.run(request).await;
+    };
+    let kib = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<u64>().ok())
+        .map_or(budget.kib, |bytes| {
+            u32::try_from(bytes.div_ceil(1024))
+                .unwrap_or(u32::MAX)
+                .clamp(1, budget.kib)
+        });
+    let _permit = budget
+        .semaphore
+        .acquire_many(kib)
+        .await
+        .expect("the budget is never closed");
+    next.run(request).await
+}
+
+/// The request as the server understood it, re-serialized, for checking a client's FEN and UCI before playing.
+#[cfg(feature = "debug-endpoints")]
+async fn echo<E: Engine>(
+    State(_): State<SharedState<E>>,
+    Json(request): Json<EngineRequest<E>>,
+) -> Json<EngineRequest<E>> {
+    Json(request)
+}
 
-            game_after
+async fn get_info<E: Engine>(State(_): State<SharedState<E>>) -> Json<EngineInfo<E>> {
+    let mut info = E::get_info();
+    info.protocol_versions = SUPPORTED_PROTOCOL_VERSIONS.to_vec();
+    Json(info)
+}
+
+async fn get_version<E: Engine>(State(_): State<SharedState<E>>) -> Json<VersionInfo> {
+    let info = E::get_info();
+    Json(VersionInfo {
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        engine_id: info.id,
+        engine_description: info.description,
+        engine_version: info.version,
+        engine_build_hash: info.build_hash,
+    })
+}
+
+/// The server is up. This does not involve the engine.
+async fn get_health<E: Engine>(State(_): State<SharedState<E>>) -> StatusCode {
+    StatusCode::OK
+}
+
+/// The server is ready to take move requests, unless the circuit breaker is open.
+async fn get_ready<E: Engine>(State(s): State<SharedState<E>>) -> StatusCode {
+    match &s.breaker {
+        Some(breaker) if breaker.is_open() => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::OK,
+    }
+}
+
+async fn get_complexity<E: Engine + 'static>(
+    State(s): State<SharedState<E>>,
+    Json(request): Json<ComplexityRequest>,
+) -> Result<Json<ComplexityResponse>, Response> {
+    require_capability(E::get_info().capabilities.supports_complexity, "complexity")
+        .map_err(IntoResponse::into_response)?;
+    spawn_engine_work(&s, |s| async move {
+        let engine = s.engine.lock().await;
+        check_supported(&*engine, &request.position)?;
+        Ok(ComplexityResponse {
+            complexity: engine.estimate_complexity(&request.position).min(100),
+        })
+    })
+    .await
+    .map(Json)
+    .map_err(|why: EngineRequestError| why.into_response())
+}
+
+/// Turn the request away unless the engine supports the operation.
+fn require_capability(supported: bool, operation: &str) -> Result<(), EngineRequestError> {
+    if supported {
+        Ok(())
+    } else {
+        Err(EngineRequestError::Unsupported {
+            operation: operation.to_string(),
+        })
+    }
+}
+
+async fn apply_moves<E: Engine>(
+    State(_): State<SharedState<E>>,
+    Json(request): Json<ApplyMovesRequest>,
+) -> Result<Json<ApplyMovesResponse>, Response> {
+    let position = replay(&request.position, &request.moves).map_err(|index| {
+        EngineRequestError::IllegalMoveInSequence {
+            index,
+            r#move: request.moves[index].clone(),
         }
+        .into_response()
+    })?;
+    Ok(Json(ApplyMovesResponse { position }))
+}
+
+async fn legal_moves<E: Engine>(
+    State(_): State<SharedState<E>>,
+    Json(request): Json<LegalMovesRequest>,
+) -> Json<LegalMovesResponse> {
+    let mode = request.position.castles().mode();
+    Json(LegalMovesResponse {
+        moves: sorted_legal_moves(&request.position)
+            .iter()
+            .map(|m| m.to_uci(mode))
+            .collect(),
+    })
+}
+
+async fn check_legal<E: Engine>(
+    State(_): State<SharedState<E>>,
+    Json(request): Json<IsLegalRequest>,
+) -> Json<IsLegalResponse> {
+    let reason = is_legal(&request.position, &request.r#move).err();
+    Json(IsLegalResponse {
+        legal: reason.is_none(),
+        reason,
+    })
+}
+
+async fn check_premove<E: Engine>(
+    State(_): State<SharedState<E>>,
+    Json(request): Json<PremoveCheckRequest>,
+) -> Result<Json<PremoveCheckResponse>, Response> {
+    let position = replay(
+        &request.position,
+        std::slice::from_ref(&request.engine_move),
+    )
+    .map_err(|index| {
+        EngineRequestError::IllegalMoveInSequence {
+            index,
+            r#move: request.engine_move.clone(),
+        }
+        .into_response()
+    })?;
+    let reason = is_legal(&position, &request.premove).err();
+    Ok(Json(PremoveCheckResponse {
+        legal: reason.is_none(),
+        reason,
+        position,
+    }))
+}
+
+/// The deepest perft that one request may ask for. Counting ties up a blocking thread until it is done,
+/// and depth 5 already visits up to a few hundred million nodes in busy middlegames.
+const MAX_PERFT_DEPTH: u32 = 5;
+
+async fn get_perft_divide<E: Engine>(
+    State(_): State<SharedState<E>>,
+    Json(request): Json<PerftDivideRequest>,
+) -> Result<Json<PerftDivideResponse>, Response> {
+    if request.depth > MAX_PERFT_DEPTH {
+        return Err(EngineRequestError::DepthTooLarge {
+            max: MAX_PERFT_DEPTH,
+        }
+        .into_response());
+    }
+    // Counting is CPU-bound, so it stays off the threads that serve requests.
+    let divide =
+        tokio::task::spawn_blocking(move || perft_divide(&request.position, request.depth))
+            .await
+            .unwrap_or_else(|why| std::panic::resume_unwind(why.into_panic()));
+    Ok(Json(PerftDivideResponse {
+        nodes: if request.depth == 0 {
+            1
+        } else {
+            divide.iter().map(|(_, nodes)| nodes).sum()
+        },
+        moves: divide
+            .into_iter()
+            .map(|(uci, nodes)| (uci.to_string(), nodes))
+            .collect(),
+    }))
+}
+
+async fn get_features<E: Engine>(
+    State(_): State<SharedState<E>>,
+    Json(request): Json<FeaturesRequest>,
+) -> Json<PositionFeatures> {
+    Json(features(&request.position))
+}
+
+/// The most games that one `/new-games` request may start.
+const MAX_NEW_GAMES: usize = 1000;
+
+async fn new_games<E: Engine>(
+    Json(request): Json<NewGamesRequest>,
+) -> Result<Json<NewGamesResponse<E>>, EngineRequestError> {
+    if request.count > MAX_NEW_GAMES {
+        return Err(EngineRequestError::TooManyGames { max: MAX_NEW_GAMES });
+    }
+    let (rand_used, rand_origin) = SeedOrigin::resolve(request.rand);
+    let seeds = RandomSource::new(rand_used);
+    Ok(Json(NewGamesResponse {
+        engine_states: (0..request.count)
+            .map(|i| E::fresh_state(seeds.stream_seed(i)))
+            .collect(),
+        rand_used,
+        rand_origin: Some(rand_origin),
+    }))
+}
+
+/// The side to move lost on time. This does not involve the engine.
+async fn claim_win<E: Engine>(
+    State(s): State<SharedState<E>>,
+    Json(request): Json<ClaimWinRequest>,
+) -> Result<Json<ClaimWinResponse>, EngineRequestError> {
+    if let Some(outcome) = game_outcome(&request.position) {
+        return Err(EngineRequestError::GameAlreadyOver { outcome });
+    }
+    if request
+        .game_id
+        .as_deref()
+        .is_some_and(|v| !valid_game_id(v))
+    {
+        return Err(EngineRequestError::InvalidGameId);
+    }
+    let winner = !request.position.turn();
+    let outcome = if request.position.has_insufficient_material(winner) {
+        GameOutcome::TimeoutVsInsufficientMaterial
     } else {
-        // If the move is a null move, there is nothing to observe.
-        observe_other_rand_used = None;
-        request.game_before.clone()
+        GameOutcome::Timeout { winner }
+    };
+    if let (Some(game_log), Some(game_id)) = (&s.game_log, &request.game_id) {
+        game_log.record_turn(
+            &E::get_info().id,
+            game_id,
+            &request.position,
+            &Uci::Null,
+            &Uci::Null,
+            Some(&outcome),
+        );
+    }
+    Ok(Json(ClaimWinResponse { outcome }))
+}
+
+async fn encode<E: Engine>(
+    State(_): State<SharedState<E>>,
+    Json(request): Json<EncodePositionRequest>,
+) -> Json<EncodePositionResponse> {
+    Json(EncodePositionResponse {
+        encoded: encode_position(&request.position),
+    })
+}
+
+async fn decode<E: Engine>(
+    State(_): State<SharedState<E>>,
+    Json(request): Json<DecodePositionRequest>,
+) -> Result<Json<DecodePositionResponse>, EngineRequestError> {
+    match decode_position(&request.encoded) {
+        Some(position) => Ok(Json(DecodePositionResponse { position })),
+        None => Err(EngineRequestError::InvalidEncodedPosition),
+    }
+}
+
+async fn get_threats<E: Engine>(
+    State(_): State<SharedState<E>>,
+    Json(request): Json<ThreatsRequest>,
+) -> Json<ThreatsResponse> {
+    let turn = request.position.turn();
+    Json(ThreatsResponse {
+        hanging: hanging_pieces(&request.position, turn),
+        targets: hanging_pieces(&request.position, !turn),
+    })
+}
+
+#[cfg(feature = "syzygy")]
+async fn probe_tablebase<E: Engine>(
+    State(s): State<SharedState<E>>,
+    Json(request): Json<TablebaseRequest>,
+) -> Result<Json<TablebaseResponse>, EngineRequestError> {
+    use crate::syzygy::ProbeError;
+
+    let tablebase = match &s.tablebase {
+        None => {
+            return Err(EngineRequestError::Unsupported {
+                operation: "tablebase".to_string(),
+            })
+        }
+        Some(Err(why)) => {
+            return Err(EngineRequestError::TablebaseProbeFailed {
+                reason: why.clone(),
+            })
+        }
+        Some(Ok(tablebase)) => tablebase,
     };
+    match tablebase.probe(&request.position) {
+        Ok(probe) => Ok(Json(TablebaseResponse {
+            wdl: probe.wdl.into(),
+            dtz: probe.dtz,
+            dtz_rounded: probe.dtz_rounded,
+        })),
+        Err(ProbeError::TooManyPieces { pieces, max }) => {
+            Err(EngineRequestError::TablebaseOutOfRange { pieces, max })
+        }
+        Err(why) => Err(EngineRequestError::TablebaseProbeFailed {
+            reason: why.to_string(),
+        }),
+    }
+}
+
+/// The most moves on each level of the search tree that one `/tree` request may ask for.
+const MAX_TREE_BREADTH: usize = 5;
+
+/// The most plies of the search tree that one `/tree` request may ask for.
+const MAX_TREE_DEPTH: usize = 4;
 
-    // Now that the other move has been observed, we need to produce a new move.
+async fn get_tree<E: Engine + 'static>(
+    State(s): State<SharedState<E>>,
+    VersionedJson(mut request): VersionedJson<TreeRequest<E>>,
+) -> Result<Json<TreeResponse>, Response> {
+    request.breadth = request.breadth.min(MAX_TREE_BREADTH);
+    request.depth = request.depth.min(MAX_TREE_DEPTH);
+    spawn_engine_work(&s, |s| async move {
+        let mut engine = s.engine.lock().await;
+        search_tree(&mut *engine, request).await
+    })
+    .await
+    .map(Json)
+    .map_err(turn_error_response)
+}
+
+async fn get_hint<E: Engine + 'static>(
+    State(s): State<SharedState<E>>,
+    VersionedJson(request): VersionedJson<HintRequest<E>>,
+) -> Result<Json<HintResponse>, Response> {
+    spawn_engine_work(&s, |s| async move {
+        let mut engine = s.engine.lock().await;
+        hint(&mut *engine, request).await
+    })
+    .await
+    .map(Json)
+    .map_err(turn_error_response)
+}
+
+/// The response for an engine call that failed, either because of the request or because of the engine.
+fn turn_error_response<E: Engine>(why: TurnError<E>) -> Response {
+    match why {
+        TurnError::RequestError(why) => why.into_response(),
+        TurnError::EngineError(why) => EngineInternalError {
+            error_text: why.to_string(),
+        }
+        .into_response(),
+    }
+}
+
+async fn get_threat_move<E: Engine + 'static>(
+    State(s): State<SharedState<E>>,
+    VersionedJson(request): VersionedJson<HintRequest<E>>,
+) -> Result<Json<ThreatMoveResponse>, Response> {
+    spawn_engine_work(&s, |s| async move {
+        let mut engine = s.engine.lock().await;
+        threat_move(&mut *engine, request).await
+    })
+    .await
+    .map(Json)
+    .map_err(turn_error_response)
+}
+
+/// The most plies of a game that `/analyze-pgn` goes through, so that an analysis always ends in reasonable time.
+const MAX_ANALYZED_PLIES: usize = 1000;
+
+async fn analyze_pgn<E: Engine + 'static>(
+    State(s): State<SharedState<E>>,
+    VersionedJson(request): VersionedJson<AnalyzePgnRequest>,
+) -> Result<Json<AnalyzePgnResponse>, Response> {
+    require_capability(E::get_info().capabilities.supports_evaluate, "analyze-pgn")
+        .map_err(IntoResponse::into_response)?;
+    let game = read_pgn_game(&request.pgn).map_err(|why| {
+        EngineRequestError::InvalidPgn {
+            reason: why.to_string(),
+        }
+        .into_response()
+    })?;
+    if game.moves.len() > MAX_ANALYZED_PLIES {
+        return Err(EngineRequestError::GameTooLong {
+            max: MAX_ANALYZED_PLIES,
+        }
+        .into_response());
+    }
+    let (rand_used, rand_origin) = SeedOrigin::resolve(request.rand);
 
-    produce_rand_used = request.produce_rand.unwrap_or_else(rand::random);
-    let (proposed_move, info) = {
-        let mut engine = e.lock().await;
-        if request.with_status_info {
-            match engine
-                .propose_move(produce_rand_used, &state, &game_after)
+    // Replay the game through the engine, so that its state matches every position it evaluates.
+    // The engine is locked one ply at a time, so that a long game does not hold up the other requests.
+    let plies = spawn_engine_work(&s, move |s| async move {
+        let mut rng = StdRng::seed_from_u64(rand_used);
+        let mut state = E::get_info().initial_state;
+        {
+            let engine = s.engine.lock().await;
+            check_supported(&*engine, &game.start).map_err(TurnError::<E>::RequestError)?;
+            engine.normalize_state(&mut state, &game.start);
+        }
+        let mut position = game.start;
+        let mut plies = Vec::with_capacity(game.moves.len());
+        for (i, m) in game.moves.into_iter().enumerate() {
+            let mut engine = s.engine.lock().await;
+            let castling_mode = position.castles().mode();
+            position.play_unchecked(&m);
+            check_supported(&*engine, &position).map_err(TurnError::RequestError)?;
+            engine
+                .observe_move(rng.next_u64(), &mut state, &m, &position)
                 .await
-            {
-                Ok((a, b)) => (a, Some(b)),
-                Err(why) => return EngineResult::EngineError(why),
-            }
-        } else {
-            match engine
-                .propose_move_without_info(produce_rand_used, &state, &game_after)
+                .map_err(TurnError::EngineError)?;
+            let score = engine
+                .evaluate(rng.next_u64(), &state, &position)
                 .await
-            {
-                Ok(a) => (a, None),
-                Err(why) => return EngineResult::EngineError(why),
-            }
+                .map_err(TurnError::EngineError)?;
+            plies.push(AnalyzedPly {
+                ply: i + 1,
+                r#move: m.to_uci(castling_mode),
+                score,
+                white_score: score.map(|v| v.from_white_perspective(position.turn())),
+            });
         }
+        Ok(plies)
+    })
+    .await
+    .map_err(turn_error_response)?;
+
+    Ok(Json(AnalyzePgnResponse {
+        rand_used,
+        rand_origin: Some(rand_origin),
+        plies,
+    }))
+}
+
+/// The most plies a self-play stream runs for, so that a stream always ends.
+const MAX_SELFPLAY_PLIES: usize = 1000;
+
+/// A self-play game in progress.
+struct Selfplay<E: Engine> {
+    /// Each side's engine state, indexed by `Color as usize`, which puts Black first.
+    states: [E::State; 2],
+    /// The position before the last move.
+    before: Chess,
+    /// The position after the last move.
+    current: Chess,
+    last_move: Uci,
+    /// The hashes of the positions before `before`, for detecting repetitions.
+    history: Vec<u64>,
+    plies: usize,
+}
+
+/// Play the engine against itself, streaming each move as a server-sent event.
+///
+/// If a turn fails, its error is sent as an `error` event, and the stream ends.
+/// If a turn takes longer than the configured [`ServerConfig::stream_idle_timeout`],
+/// a `timeout` event is sent instead, and the stream ends as well.
+async fn selfplay_stream<E: Engine + 'static>(
+    State(s): State<SharedState<E>>,
+    Query(query): Query<SelfplayQuery>,
+) -> Sse<impl Stream<Item = Result<Event, serde_json::Error>>> {
+    let max_plies = query
+        .max_plies
+        .unwrap_or(MAX_SELFPLAY_PLIES)
+        .min(MAX_SELFPLAY_PLIES);
+    let initial_state = E::get_info().initial_state;
+    let game = Selfplay::<E> {
+        states: [initial_state.clone(), initial_state],
+        before: query.fen.clone(),
+        current: query.fen,
+        last_move: Uci::Null,
+        history: Vec::new(),
+        plies: 0,
     };
 
-    // Finally, observe our own move.
+    Sse::new(stream::unfold(Some(game), move |game| {
+        let s = s.clone();
+        async move {
+            let mut game = game.filter(|game| game.plies < max_plies)?;
+            let side = game.current.turn() as usize;
+            let request = EngineRequest::<E> {
+                r#move: game.last_move.clone(),
+                game_before: game.before.clone(),
+                chess960_sp: None,
+                castling_mode: None,
+                engine_state: game.states[side].clone(),
+                observe_other_rand: None,
+                produce_rand: None,
+                produce_rand_streams: Vec::new(),
+                observe_own_rand: None,
+                with_status_info: false,
+                game_id: None,
+                restrict_to: Vec::new(),
+                expected_hash_before: None,
+                expected_hash_after_move: None,
+                with_pv_positions: false,
+                with_position_key: false,
+                strength: None,
+                with_state_delta: false,
+                strict_rules: s.config.strict_rules,
+                position_history: game.history.clone(),
+                protocol_version: None,
+            };
 
-    observe_mine_rand_used = request.observe_your_rand.unwrap_or_else(rand::random);
-    let game_after_mine = match game_after.clone().play(&proposed_move) {
-        Ok(v) => v,
-        Err(_) => {
-            return EngineResult::RequestError(EngineRequestError::EngineSentIllegalMove {
-                r#move: proposed_move.to_uci(shakmaty::CastlingMode::Standard),
+            let turn = run_turn(&s, Turn::Play(request));
+            let result = match s.config.stream_idle_timeout {
+                Some(idle_timeout) => match tokio::time::timeout(idle_timeout, turn).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        let why = EngineInternalError {
+                            error_text: format!("no move within {idle_timeout:?}"),
+                        };
+                        return Some((Event::default().event("timeout").json_data(why), None));
+                    }
+                },
+                None => turn.await,
+            };
+            let response = match result {
+                EngineResult::Ok(response) => response,
+                EngineResult::RequestError(why) => {
+                    return Some((Event::default().event("error").json_data(why), None));
+                }
+                EngineResult::EngineError(why) => {
+                    let why = EngineInternalError {
+                        error_text: why.to_string(),
+                    };
+                    return Some((Event::default().event("error").json_data(why), None));
+                }
+            };
+
+            let event = Event::default().json_data(SelfplayMove {
+                r#move: response.r#move.clone(),
+                fen: response.game_after.clone(),
+                outcome: response.outcome.clone(),
             });
+            if response.outcome.is_some() {
+                return Some((event, None));
+            }
+            game.states[side] = response
+                .engine_state
+                .expect("the full state is sent when no delta is requested");
+            if game.last_move != Uci::Null {
+                game.history.push(position_hash(&game.before));
+            }
+            game.before = std::mem::replace(&mut game.current, response.game_after);
+            game.last_move = response.r#move;
+            game.plies += 1;
+            Some((event, Some(game)))
+        }
+    }))
+}
+
+/// The most plies of a game that `/review` goes through, so that a review always ends in reasonable time.
+const MAX_REVIEW_PLIES: usize = 1000;
+
+/// A game review in progress.
+struct Review<E: Engine> {
+    state: E::State,
+    /// The position before the next move to review.
+    position: Chess,
+    moves: std::vec::IntoIter<Uci>,
+    rng: StdRng,
+    ply: usize,
+}
+
+/// Go through a recorded game, streaming the engine's preferred move and evaluation before each played move
+/// as a server-sent event, for a review UI to highlight the mistakes.
+///
+/// The whole game is checked before the stream starts. The stream opens with a `start` event,
+/// which echoes the seed used, and the engine observes every played move, so that its state follows the game.
+/// If a turn fails, its error is sent as an `error` event, and the stream ends. As for self-play,
+/// a turn that takes longer than the configured [`ServerConfig::stream_idle_timeout`] ends it with a `timeout` event.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use axum::{body::{Body, HttpBody}, http::{header::CONTENT_TYPE, Request}, Router};
+/// use engine_trait::{lookup::LookupEngine, server::{serve_engine_with, ServerConfig}};
+/// # use engine_trait::{async_trait, server_types::EngineInfo, Engine, InfallibleError, NoStatus};
+/// # use shakmaty::{Chess, Move, Position};
+/// # /// Thinks for an hour about every move.
+/// # struct Slow;
+/// # #[async_trait]
+/// # impl Engine for Slow {
+/// #     type State = ();
+/// #     type StatusInfo = NoStatus;
+/// #     type Error = InfallibleError;
+/// #     fn get_info() -> EngineInfo<Self> { EngineInfo::new("slow", "Thinks for an hour", ()) }
+/// #     async fn propose_move(&mut self, _: u64, _: &(), position: &Chess) -> Result<(Move, NoStatus), InfallibleError> {
+/// #         tokio::time::sleep(Duration::from_secs(3600)).await;
+/// #         Ok((position.legal_moves()[0].clone(), NoStatus))
+/// #     }
+/// #     async fn observe_move(&mut self, _: u64, _: &mut (), _: &Move, _: &Chess) -> Result<(), InfallibleError> { Ok(()) }
+/// # }
+///
+/// async fn review(router: Router) -> String {
+///     let body = r#"{"moves": ["e2e4", "e7e5"], "rand": 5}"#;
+///     let request = Request::post("/review").header(CONTENT_TYPE, "application/json").body(Body::from(body)).unwrap();
+///     let mut body = router.oneshot(request).await.unwrap().into_body();
+///     let mut events = String::new();
+///     while let Some(chunk) = body.data().await {
+///         events.push_str(std::str::from_utf8(&chunk.unwrap()).unwrap());
+///     }
+///     events
+/// }
+/// # use tower::ServiceExt;
+///
+/// # tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap().block_on(async {
+/// let events = review(serve_engine_with(LookupEngine::new(), ServerConfig::default()).await).await;
+/// assert!(events.starts_with("event:start\ndata:{\"rand_used\":5,\"rand_origin\":\"provided\",\"plies\":2}"));
+/// assert_eq!(events.matches("\"played\"").count(), 2);
+///
+/// let config = ServerConfig { stream_idle_timeout: Some(Duration::from_millis(10)), ..ServerConfig::default() };
+/// let events = review(serve_engine_with(Slow, config).await).await;
+/// assert!(events.contains("event:timeout"));
+/// assert!(!events.contains("\"played\""));
+/// # });
+/// ```
+async fn review<E: Engine + 'static>(
+    State(s): State<SharedState<E>>,
+    VersionedJson(request): VersionedJson<ReviewRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, serde_json::Error>>>, EngineRequestError> {
+    if request.moves.len() > MAX_REVIEW_PLIES {
+        return Err(EngineRequestError::GameTooLong {
+            max: MAX_REVIEW_PLIES,
+        });
+    }
+    replay(&request.position, &request.moves).map_err(|index| {
+        EngineRequestError::IllegalMoveInSequence {
+            index,
+            r#move: request.moves[index].clone(),
+        }
+    })?;
+    let (rand_used, rand_origin) = SeedOrigin::resolve(request.rand);
+    let start = Event::default().event("start").json_data(ReviewStart {
+        rand_used,
+        rand_origin: Some(rand_origin),
+        plies: request.moves.len(),
+    });
+    let review = Review::<E> {
+        state: E::get_info().initial_state,
+        position: request.position,
+        moves: request.moves.into_iter(),
+        rng: StdRng::seed_from_u64(rand_used),
+        ply: 0,
+    };
+
+    let plies = stream::unfold(Some(review), move |review| {
+        let s = s.clone();
+        async move {
+            let review = review?;
+            let step = spawn_engine_work(&s, |s| review_ply(s, review));
+            match s.config.stream_idle_timeout {
+                Some(idle_timeout) => match tokio::time::timeout(idle_timeout, step).await {
+                    Ok(step) => step,
+                    Err(_) => {
+                        let why = EngineInternalError {
+                            error_text: format!("no move within {idle_timeout:?}"),
+                        };
+                        Some((Event::default().event("timeout").json_data(why), None))
+                    }
+                },
+                None => step.await,
+            }
         }
+    });
+    Ok(Sse::new(stream::once(future::ready(start)).chain(plies)))
+}
+
+/// Review the next ply of `review`, returning its event and the review to continue with, if any.
+async fn review_ply<E: Engine + 'static>(
+    s: SharedState<E>,
+    mut review: Review<E>,
+) -> Option<(Result<Event, serde_json::Error>, Option<Review<E>>)> {
+    let played = review.moves.next()?;
+    let engine_error = |why: E::Error| {
+        let why = EngineInternalError {
+            error_text: why.to_string(),
+        };
+        Some((Event::default().event("error").json_data(why), None))
     };
+
+    let unsupported =
+        |why: EngineRequestError| Some((Event::default().event("error").json_data(why), None));
+
+    let mut engine = s.engine.lock().await;
+    if review.ply == 0 {
+        if let Err(why) = check_supported(&*engine, &review.position) {
+            return unsupported(why);
+        }
+        engine.normalize_state(&mut review.state, &review.position);
+    }
+    let (best, info) = match engine
+        .propose_move(review.rng.next_u64(), &review.state, &review.position)
+        .await
     {
-        let mut engine = e.lock().await;
-        if let Err(why) = engine
-            .observe_move(
-                observe_mine_rand_used,
-                &mut state,
-                &proposed_move,
-                &game_after_mine,
-            )
+        Ok(v) => v,
+        Err(why) => return engine_error(why),
+    };
+    let castling_mode = review.position.castles().mode();
+    if !review.position.is_legal(&best) {
+        let why = EngineRequestError::EngineSentIllegalMove {
+            r#move: best.to_uci(castling_mode),
+        };
+        return Some((Event::default().event("error").json_data(why), None));
+    }
+    let score = match E::summarize(&info).score {
+        Some(score) => Some(score),
+        None => match engine
+            .evaluate(review.rng.next_u64(), &review.state, &review.position)
             .await
         {
-            return EngineResult::EngineError(why);
+            Ok(score) => score,
+            Err(why) => return engine_error(why),
+        },
+    };
+
+    let m = played
+        .to_move(&review.position)
+        .expect("the game was checked before the review started");
+    review.position.play_unchecked(&m);
+    if let Err(why) = check_supported(&*engine, &review.position) {
+        return unsupported(why);
+    }
+    if let Err(why) = engine
+        .observe_move(
+            review.rng.next_u64(),
+            &mut review.state,
+            &m,
+            &review.position,
+        )
+        .await
+    {
+        return engine_error(why);
+    }
+    drop(engine);
+
+    review.ply += 1;
+    let event = Event::default().json_data(ReviewedPly {
+        ply: review.ply,
+        played,
+        best: best.to_uci(castling_mode),
+        score,
+    });
+    Some((event, Some(review)))
+}
+
+/// A JSON request body whose `protocol_version`, if it declares one, is among [`SUPPORTED_PROTOCOL_VERSIONS`].
+///
+/// The version is read from the raw body before the request itself, so that an unsupported version
+/// is reported as such rather than as whatever field it fails to parse. The body is only buffered once:
+/// every supported version is read by the current request types, which accept the older field names.
+struct VersionedJson<T>(T);
+
+/// Just the version of a request; everything else in the body is ignored.
+#[derive(serde::Deserialize)]
+struct DeclaredVersion {
+    #[serde(default)]
+    protocol_version: Option<u32>,
+}
+
+#[axum::async_trait]
+impl<S, T> FromRequest<S, Body> for VersionedJson<T>
+where
+    S: Send + Sync,
+    T: serde::de::DeserializeOwned,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request<Body>, state: &S) -> Result<Self, Response> {
+        let is_json = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("application/json"));
+        if !is_json {
+            return Err((
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "expected a request with `Content-Type: application/json`",
+            )
+                .into_response());
+        }
+        let body = Bytes::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let unprocessable = |why: serde_json::Error| {
+            (StatusCode::UNPROCESSABLE_ENTITY, why.to_string()).into_response()
+        };
+        let declared: DeclaredVersion = serde_json::from_slice(&body).map_err(unprocessable)?;
+        match declared.protocol_version {
+            Some(version) if !SUPPORTED_PROTOCOL_VERSIONS.contains(&version) => {
+                Err(EngineRequestError::UnsupportedProtocolVersion {
+                    version,
+                    supported: SUPPORTED_PROTOCOL_VERSIONS.to_vec(),
+                }
+                .into_response())
+            }
+            _ => serde_json::from_slice(&body)
+                .map(VersionedJson)
+                .map_err(unprocessable),
         }
     }
+}
 
-    // Now that the move was produced and observed, construct a response.
-    EngineResult::Ok(EngineResponse {
-        r#move: proposed_move.to_uci(shakmaty::CastlingMode::Standard),
-        game_after: game_after_mine,
-        status_info: info,
-        observe_other_rand_used,
-        produce_rand_used,
-        observe_mine_rand_used,
-        engine_state: state,
+async fn handle_move<E: Engine + 'static>(
+    State(s): State<SharedState<E>>,
+    VersionedJson(request): VersionedJson<EngineRequest<E>>,
+) -> Result<EngineResult<E>, Response> {
+    serve_turn(&s, Turn::Play(request)).await
+}
+
+/// Everything that the move routes do around a turn: the configured rules and position filter,
+/// the circuit breaker, the game log and the status info cap.
+async fn serve_turn<E: Engine + 'static>(
+    s: &SharedState<E>,
+    mut turn: Turn<E>,
+) -> Result<EngineResult<E>, Response> {
+    let request = turn.request_mut();
+    request.strict_rules |= s.config.strict_rules;
+
+    if s.breaker.as_ref().is_some_and(|v| v.is_open()) {
+        let why = EngineInternalError {
+            error_text: "the engine has failed too often, and is resting".to_string(),
+        };
+        return Err((StatusCode::SERVICE_UNAVAILABLE, Json(why)).into_response());
+    }
+
+    if !position_allowed(&s.config, request) {
+        return Ok(EngineResult::RequestError(
+            EngineRequestError::PositionNotAllowed,
+        ));
+    }
+    if request
+        .game_id
+        .as_deref()
+        .is_some_and(|v| !valid_game_id(v))
+    {
+        return Ok(EngineResult::RequestError(
+            EngineRequestError::InvalidGameId,
+        ));
+    }
+
+    let logged_turn = s
+        .game_log
+        .as_ref()
+        .and(request.game_id.clone())
+        .map(|game_id| {
+            (
+                game_id,
+                request.resolved_game_before(),
+                request.r#move.clone(),
+            )
+        });
+
+    let mut result = run_turn(s, turn).await;
+
+    if let Some(breaker) = &s.breaker {
+        match &result {
+            EngineResult::EngineError(_) => breaker.record_failure(),
+            EngineResult::Ok(_) => breaker.record_success(),
+            // Bad requests say nothing about the engine's health.
+            EngineResult::RequestError(_) => {}
+        }
+    }
+
+    if let (
+        Some(game_log),
+        Some((game_id, Ok(game_before), their_move)),
+        EngineResult::Ok(response),
+    ) = (&s.game_log, logged_turn, &result)
+    {
+        game_log.record_turn(
+            &E::get_info().id,
+            &game_id,
+            &game_before,
+            &their_move,
+            &response.r#move,
+            response.outcome.as_ref(),
+        );
+    }
+
+    if let (EngineResult::Ok(response), Some(max_bytes)) =
+        (&mut result, s.config.max_status_info_bytes)
+    {
+        cap_status_info(response, max_bytes);
+    }
+
+    Ok(result)
+}
+
+/// Play a turn on the engine, in its own task on the configured runtime.
+///
+/// The whole turn runs in that task while holding the engine lock,
+/// so the engine is never left between observing the other move and observing its own move.
+async fn run_turn<E: Engine + 'static>(s: &SharedState<E>, turn: Turn<E>) -> EngineResult<E> {
+    spawn_engine_work(s, move |task_state| async move {
+        let mut engine = task_state.engine.lock().await;
+        let on_illegal_move = task_state.config.on_illegal_move;
+        let slow_move = task_state.config.slow_move_threshold.and_then(|threshold| {
+            Some((threshold, position_to_move(turn.request())?, Instant::now()))
+        });
+        let result = match turn {
+            Turn::Play(request) => play_turn_with(&mut *engine, request, on_illegal_move).await,
+            Turn::Amend(amend) => amend_turn(&mut *engine, amend, on_illegal_move).await,
+        };
+        if let Some((threshold, position, started)) = slow_move {
+            let elapsed = started.elapsed();
+            if elapsed > threshold {
+                tracing::warn!(
+                    fen = %Fen::from_position(position, EnPassantMode::Legal),
+                    elapsed_ms = elapsed.as_millis() as u64,
+                    "slow move",
+                );
+            }
+        }
+        result
     })
+    .await
+}
+
+/// Run `work` in its own task on the configured [`ServerConfig::runtime`], or on the current one if none is set.
+///
+/// Every call into the engine goes through here, so that the configured runtime bounds all of them.
+/// If the client disconnects, the handler future is dropped, but the task still runs to completion,
+/// so the engine is never abandoned partway through a call.
+async fn spawn_engine_work<E, F, Fut>(s: &SharedState<E>, work: F) -> Fut::Output
+where
+    E: Engine + 'static,
+    F: FnOnce(SharedState<E>) -> Fut,
+    Fut: Future + Send + 'static,
+    Fut::Output: Send + 'static,
+{
+    let work = work(s.clone());
+    let task = match &s.config.runtime {
+        Some(runtime) => runtime.spawn(work),
+        None => tokio::spawn(work),
+    };
+    match task.await {
+        Ok(output) => output,
+        Err(why) => std::panic::resume_unwind(why.into_panic()),
+    }
+}
+
+/// The position that the engine moves in: the request's starting position after the client's move.
+/// None if the request is invalid, since then the engine does not move.
+fn position_to_move<E: Engine>(request: &EngineRequest<E>) -> Option<Chess> {
+    let mut position = request.resolved_game_before().ok()?;
+    if request.r#move != Uci::Null {
+        let m = request.r#move.to_move(&position).ok()?;
+        position.play_unchecked(&m);
+    }
+    Some(position)
+}
+
+/// What [`run_turn`] does with the engine.
+enum Turn<E: Engine> {
+    Play(EngineRequest<E>),
+    Amend(AmendRequest<E>),
+}
+
+impl<E: Engine> Turn<E> {
+    /// The move request of the turn; for an amendment, the one for the replacement move.
+    fn request(&self) -> &EngineRequest<E> {
+        match self {
+            Turn::Play(request) => request,
+            Turn::Amend(amend) => &amend.request,
+        }
+    }
+
+    fn request_mut(&mut self) -> &mut EngineRequest<E> {
+        match self {
+            Turn::Play(request) => request,
+            Turn::Amend(amend) => &mut amend.request,
+        }
+    }
+}
+
+/// Whether the request's starting position passes the configured filter.
+/// Positions that cannot be resolved pass, since the turn itself reports that error.
+fn position_allowed<E: Engine>(config: &ServerConfig, request: &EngineRequest<E>) -> bool {
+    match (&config.allowed_positions, request.resolved_game_before()) {
+        (Some(allowed), Ok(position)) => allowed(&position),
+        _ => true,
+    }
+}
+
+/// Take a move back and play a replacement, as one turn. See [`amend_turn`].
+async fn amend<E: Engine + 'static>(
+    State(s): State<SharedState<E>>,
+    VersionedJson(amend): VersionedJson<AmendRequest<E>>,
+) -> Result<EngineResult<E>, Response> {
+    require_capability(E::get_info().capabilities.supports_unobserve, "amend")
+        .map_err(IntoResponse::into_response)?;
+    serve_turn(&s, Turn::Amend(amend)).await
+}
+
+/// Drop the status info if it would take more than `max_bytes` to serialize.
+fn cap_status_info<E: Engine>(response: &mut EngineResponse<E>, max_bytes: usize) {
+    let too_big = match &response.status_info {
+        Some(info) => serde_json::to_vec(info).map_or(true, |v| v.len() > max_bytes),
+        None => false,
+    };
+    if too_big {
+        response.status_info = None;
+        response.status_info_truncated = true;
+    }
 }
@@ -1,12 +1,34 @@
 #[cfg(feature = "server")]
 use axum::{http::StatusCode, response::IntoResponse, Json};
+use std::{collections::BTreeMap, time::Duration};
+
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use shakmaty::{uci::Uci, Chess};
+use shakmaty::{
+    uci::Uci, CastlingMode, CastlingSide, Chess, Color, EnPassantMode, FromSetup, Outcome,
+    Position, Square,
+};
 
-use crate::Engine;
+use crate::{
+    position::{chess960_start, position_hash, IllegalMoveReason},
+    Engine,
+};
 
 /// Request the engine to take a move.
+///
+/// A turn calls the engine three times, each with its own seed:
+///
+/// | Call | Request field | Response field |
+/// |------|---------------|----------------|
+/// | [`Engine::observe_move`] on the other side's `move` | `observe_other_rand` | `observe_other_rand_used` |
+/// | [`Engine::propose_move`] | `produce_rand` | `produce_rand_used` |
+/// | [`Engine::observe_move`] on the engine's own move | `observe_own_rand` | `observe_own_rand_used` |
+///
+/// The old names `observe_mine_rand` and `observe_your_rand` are still accepted, but they are easy to mix up,
+/// since they were named from the client's point of view; new clients should not send them.
+///
+/// Each `x_rand_used` field comes with an `x_rand_origin` field, which is `"provided"` if the request
+/// set that seed, and `"generated"` if the server picked it; see [`SeedOrigin`].
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EngineRequest<E: Engine> {
     /// The move that the user took. Put a null move here if the engine is making the first move.
@@ -14,26 +36,238 @@ pub struct EngineRequest<E: Engine> {
     pub r#move: Uci,
 
     /// The game state before the move was played
-    #[serde(with = "crate::chess_serde::position_serde")]
+    #[serde(with = "crate::chess_serde::position_serde", default)]
     pub game_before: Chess,
 
+    /// Start from the Chess960 start position with this Scharnagl number (0 to 959) instead of `game_before`.
+    #[serde(default)]
+    pub chess960_sp: Option<u16>,
+
+    /// How castling moves are written: `"standard"` as the king's two-square move (`e1g1`),
+    /// or `"chess960"` as the king taking its own rook (`e1h1`).
+    /// If left out, it is detected from the castling rights of the starting position.
+    #[serde(with = "crate::chess_serde::castling_mode_serde", default)]
+    pub castling_mode: Option<CastlingMode>,
+
     /// The engine's internal state after its last move.
+    /// If left out, the state's default is used, which is all that stateless engines need.
+    /// Stateful engines are not protected from a client leaving it out mid-game; see [`Engine::State`].
+    /// Its JSON key order depends on the state type; hash or compare it through
+    /// [`canonical_json`](crate::chess_serde::canonical_json).
+    #[serde(default)]
     pub engine_state: E::State,
 
-    /// What random number to give to the engine when observing this move?
+    /// What random number to give to the engine when observing the other side's `move`?
     /// If None, it will be generated.
-    pub observe_mine_rand: Option<u64>,
+    ///
+    /// This used to be called `observe_mine_rand`, from the client's point of view, and that name is still accepted.
+    #[serde(alias = "observe_mine_rand")]
+    pub observe_other_rand: Option<u64>,
 
     /// What random number to give to the engine when producing a new move?
     /// If None, it will be generated.
     pub produce_rand: Option<u64>,
 
+    /// Explicit seeds for the engine's independent random streams when producing a new move.
+    /// If not empty, the engine is given a [`RandomSource`](crate::random::RandomSource)
+    /// made of `produce_rand` and these seeds. They are ignored when `restrict_to` is set.
+    #[serde(default)]
+    pub produce_rand_streams: Vec<u64>,
+
     /// What random number to give to the engine when observing the engine's own move?
     /// If None, it will be generated.
-    pub observe_your_rand: Option<u64>,
+    ///
+    /// This used to be called `observe_your_rand`, and that name is still accepted.
+    #[serde(alias = "observe_your_rand")]
+    pub observe_own_rand: Option<u64>,
 
     /// Should status info be returned?
+    /// This is ignored for engines without meaningful status info, such as those using [`NoStatus`](crate::NoStatus).
     pub with_status_info: bool,
+
+    /// Identifies the game that this request belongs to, for servers that keep a game log.
+    #[serde(default)]
+    pub game_id: Option<String>,
+
+    /// If not empty, the engine may only choose among these moves.
+    /// They must all be legal in the position after `move`.
+    #[serde(with = "crate::chess_serde::uci_list_serde", default)]
+    pub restrict_to: Vec<Uci>,
+
+    /// If set, the response includes the position after each move of the engine's principal variation.
+    /// This needs status info, since that is where the engine reports its principal variation.
+    #[serde(default)]
+    pub with_pv_positions: bool,
+
+    /// If set, the response includes the [`position_key`](crate::position::position_key) of `game_after`,
+    /// alongside the full position.
+    #[serde(default)]
+    pub with_position_key: bool,
+
+    /// If set, the engine plays its move at roughly this Elo rating, if it supports that.
+    /// See [`Engine::set_strength`].
+    #[serde(default)]
+    pub strength: Option<u16>,
+
+    /// If set, the request is rejected with [`EngineRequestError::StateDesync`]
+    /// unless the position before `move` has this [`position_hash`](crate::position::position_hash).
+    /// Clients get the hash to send from the previous response's `game_after_hash`.
+    /// Like that, it is a decimal string on the wire, though a plain number is accepted too.
+    #[serde(with = "crate::chess_serde::u64_string_option_serde", default)]
+    pub expected_hash_before: Option<u64>,
+
+    /// If set, the request is rejected with [`EngineRequestError::StateDesync`]
+    /// unless the position after `move` has this hash. This is checked before the engine sees the move.
+    #[serde(with = "crate::chess_serde::u64_string_option_serde", default)]
+    pub expected_hash_after_move: Option<u64>,
+
+    /// If set, and the engine supports it, the response carries only the change to `engine_state`
+    /// in `engine_state_delta`, instead of the full state.
+    #[serde(default)]
+    pub with_state_delta: bool,
+
+    /// If set, the game also ends automatically in a draw on fivefold repetition or the 75-move rule,
+    /// as under the FIDE rules, instead of only on checkmate, stalemate or insufficient material.
+    #[serde(default)]
+    pub strict_rules: bool,
+
+    /// The [`position_hash`](crate::position::position_hash) of every earlier position of the game,
+    /// oldest first, up to but not including `game_before`. Only needed for `strict_rules`,
+    /// to detect repetitions; positions before the last capture or pawn move can be left out.
+    #[serde(default)]
+    pub position_history: Vec<u64>,
+
+    /// The version of this request's schema, from [`SUPPORTED_PROTOCOL_VERSIONS`].
+    /// Every version is read the same way, since this type also accepts everything that version 1 clients send.
+    /// The server rejects requests for other versions with [`EngineRequestError::UnsupportedProtocolVersion`],
+    /// and so does it for the other requests that reach the engine, such as `/amend` and `/hint`,
+    /// if they declare a `protocol_version` of their own.
+    #[serde(default)]
+    pub protocol_version: Option<u32>,
+}
+
+/// The version of the move request schema that [`EngineRequest`] implements.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// The move request schema versions that the server can read, oldest first.
+///
+/// Version 1 requests name the observation seeds from the client's point of view, and leave out every optional field:
+///
+/// ```
+/// use engine_trait::server_types::EngineRequest;
+/// # use engine_trait::fixtures::FirstMove;
+///
+/// let request: EngineRequest<FirstMove> = serde_json::from_value(serde_json::json!({
+///     "move": "e2e4",
+///     "game_before": "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+///     "engine_state": null,
+///     "observe_mine_rand": 1,
+///     "produce_rand": 2,
+///     "observe_your_rand": 3,
+///     "with_status_info": false,
+///     "protocol_version": 1,
+/// }))
+/// .unwrap();
+/// assert_eq!(request.observe_other_rand, Some(1));
+/// assert_eq!(request.observe_own_rand, Some(3));
+/// ```
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[u32] = &[1, PROTOCOL_VERSION];
+
+impl<E: Engine> EngineRequest<E> {
+    /// The position before the move: either `game_before`, or the requested Chess960 start position,
+    /// in the requested `castling_mode` if there is one.
+    ///
+    /// ```
+    /// use engine_trait::{
+    ///     game::play_turn,
+    ///     server_types::{EngineRequest, EngineRequestError, EngineResult},
+    ///     shakmaty::{CastlingMode, Position},
+    /// };
+    /// # use engine_trait::{async_trait, server_types::EngineInfo, Engine, InfallibleError, NoStatus};
+    /// # use shakmaty::{Chess, Move};
+    /// # struct Castler;
+    /// # #[async_trait]
+    /// # impl Engine for Castler {
+    /// #     type State = ();
+    /// #     type StatusInfo = NoStatus;
+    /// #     type Error = InfallibleError;
+    /// #     fn get_info() -> EngineInfo<Self> {
+    /// #         EngineInfo::new("castler", "Castles whenever it can", ())
+    /// #     }
+    /// #     async fn propose_move(&mut self, _: u64, _: &(), position: &Chess) -> Result<(Move, NoStatus), InfallibleError> {
+    /// #         let moves = position.legal_moves();
+    /// #         Ok((moves.iter().find(|m| m.is_castle()).unwrap_or(&moves[0]).clone(), NoStatus))
+    /// #     }
+    /// #     async fn observe_move(&mut self, _: u64, _: &mut (), _: &Move, _: &Chess) -> Result<(), InfallibleError> { Ok(()) }
+    /// # }
+    ///
+    /// let request = |castling_mode: Option<&str>| -> EngineRequest<Castler> {
+    ///     serde_json::from_value(serde_json::json!({
+    ///         "move": "0000",
+    ///         "game_before": "4k3/8/8/8/8/8/8/4K2R w K - 0 1",
+    ///         "castling_mode": castling_mode,
+    ///         "with_status_info": false,
+    ///     }))
+    ///     .unwrap()
+    /// };
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let EngineResult::Ok(response) = play_turn(&mut Castler, request(None)).await else { panic!() };
+    /// assert_eq!(response.r#move.to_string(), "e1g1");
+    ///
+    /// let chess960 = request(Some("chess960"));
+    /// assert_eq!(chess960.resolved_game_before().unwrap().castles().mode(), CastlingMode::Chess960);
+    /// let EngineResult::Ok(response) = play_turn(&mut Castler, chess960).await else { panic!() };
+    /// assert_eq!(response.r#move.to_string(), "e1h1");
+    /// # });
+    ///
+    /// // Castling with a rook on the b-file cannot be written in standard notation.
+    /// let mut request = request(Some("standard"));
+    /// request.chess960_sp = Some(0);
+    /// assert!(matches!(
+    ///     request.resolved_game_before(),
+    ///     Err(EngineRequestError::CastlingModeMismatch)
+    /// ));
+    /// ```
+    pub fn resolved_game_before(&self) -> Result<Chess, EngineRequestError> {
+        let position = match self.chess960_sp {
+            Some(sp) => {
+                chess960_start(sp).ok_or(EngineRequestError::InvalidChess960Number { sp })?
+            }
+            None => self.game_before.clone(),
+        };
+        match self.castling_mode {
+            Some(mode) if mode != position.castles().mode() => {
+                Chess::from_setup(position.into_setup(EnPassantMode::Legal), mode)
+                    .map_err(|_| EngineRequestError::CastlingModeMismatch)
+            }
+            _ => Ok(position),
+        }
+    }
+
+    /// Use the seeds that a previous response reported, to reproduce that turn exactly.
+    ///
+    /// Seeds the response does not have are left as they are.
+    pub fn reuse_seeds(&mut self, seeds: &SeedsUsed) {
+        self.observe_other_rand = seeds.observe_other.or(self.observe_other_rand);
+        self.produce_rand = seeds.produce.or(self.produce_rand);
+        self.observe_own_rand = seeds.observe_own.or(self.observe_own_rand);
+    }
+
+    /// The seeds this request sets, in the same shape as a response reports them.
+    pub fn seeds(&self) -> SeedsUsed {
+        SeedsUsed {
+            observe_other: self.observe_other_rand,
+            produce: self.produce_rand,
+            observe_own: self.observe_own_rand,
+            observe_other_origin: self.observe_other_rand.map(|_| SeedOrigin::Provided),
+            produce_origin: self.produce_rand.map(|_| SeedOrigin::Provided),
+            observe_own_origin: self.observe_own_rand.map(|_| SeedOrigin::Provided),
+            unobserve_reply: None,
+            unobserve_last: None,
+            unobserve_reply_origin: None,
+            unobserve_last_origin: None,
+        }
+    }
 }
 
 /// General engine info, including initial state.
@@ -45,10 +279,157 @@ pub struct EngineInfo<E: Engine> {
     /// A human-readable description of what the engine does.
     pub description: String,
 
+    /// The name to show for the engine in lists, if it differs from the id.
+    #[serde(default)]
+    pub display_name: Option<String>,
+
+    /// Who wrote the engine.
+    #[serde(default)]
+    pub author: Option<String>,
+
+    /// A URL of an image to show next to the engine's name.
+    #[serde(default)]
+    pub icon_url: Option<String>,
+
+    /// The engine's own version string, if it reports one.
+    #[serde(default)]
+    pub version: Option<String>,
+
+    /// The source revision or build ID the engine was built from, if it reports one.
+    /// Engines usually set this at build time, e.g. with `option_env!("GIT_HASH")`.
+    /// Unlike `version`, it identifies the exact build, for checking which one produced a move.
+    #[serde(default)]
+    pub build_hash: Option<String>,
+
+    /// Which optional features the engine actually implements.
+    #[serde(default)]
+    pub capabilities: Capabilities,
+
+    /// The move request schema versions that the server can read; see [`EngineRequest::protocol_version`].
+    /// The server fills this in, so engines can leave it empty. Empty for servers from before versioning,
+    /// which only read version 1 requests reliably.
+    #[serde(default)]
+    pub protocol_versions: Vec<u32>,
+
+    /// The settings that the engine can be configured with, for clients to build a settings panel from.
+    #[serde(default)]
+    pub options: Vec<EngineOption>,
+
     /// Initial state value. Pass this when making a move.
     pub initial_state: E::State,
 }
 
+impl<E: Engine> EngineInfo<E> {
+    /// Info with only the required fields, and everything optional left out.
+    /// Set the others with struct update syntax:
+    ///
+    /// ```
+    /// use engine_trait::{lookup::LookupEngine, server_types::EngineInfo};
+    ///
+    /// let info = EngineInfo::<LookupEngine> {
+    ///     author: Some("Jane Doe".to_string()),
+    ///     ..EngineInfo::new("lookup", "Plays book moves", ())
+    /// };
+    /// assert_eq!(info.id, "lookup");
+    /// assert!(info.display_name.is_none() && info.options.is_empty());
+    /// ```
+    pub fn new(
+        id: impl Into<String>,
+        description: impl Into<String>,
+        initial_state: E::State,
+    ) -> EngineInfo<E> {
+        EngineInfo {
+            id: id.into(),
+            description: description.into(),
+            display_name: None,
+            author: None,
+            icon_url: None,
+            version: None,
+            build_hash: None,
+            capabilities: Capabilities::default(),
+            protocol_versions: Vec::new(),
+            options: Vec::new(),
+            initial_state,
+        }
+    }
+}
+
+/// Optional features that an engine may implement beyond the required trait methods.
+///
+/// Engines set these in [`EngineInfo`], so that clients can adapt to what the engine supports.
+/// Everything defaults to unsupported.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Capabilities {
+    /// The engine overrides [`Engine::estimate_complexity`] with a meaningful estimate.
+    pub supports_complexity: bool,
+
+    /// The engine overrides [`Engine::evaluate`], so positions can be analyzed.
+    pub supports_evaluate: bool,
+
+    /// The engine overrides [`Engine::state_delta`], so `with_state_delta` requests get smaller responses.
+    pub supports_state_delta: bool,
+
+    /// The Elo ratings that [`Engine::set_strength`] supports, lowest first.
+    /// Requested strengths are clamped to this range. None if the engine always plays at full strength.
+    pub strength_range: Option<(u16, u16)>,
+
+    /// The engine varies its openings with `produce_rand`, e.g. with [`weighted_choice`](crate::random::weighted_choice),
+    /// so clients that want varied games should send a different seed for each game.
+    pub book_diversity: bool,
+
+    /// The engine overrides [`Engine::unobserve_move`], or its state does not depend on the moves it observes,
+    /// so moves can be taken back with `/amend`.
+    pub supports_unobserve: bool,
+
+    /// The engine reports a [`SearchTree`] of its search in [`MoveSummary::search_tree`](crate::MoveSummary::search_tree),
+    /// so move responses with status info include one.
+    pub supports_search_tree: bool,
+}
+
+/// A setting that an engine can be configured with, like a UCI option.
+///
+/// ```
+/// use engine_trait::server_types::{EngineOption, EngineOptionKind};
+///
+/// let option = EngineOption {
+///     name: "Hash".to_string(),
+///     kind: EngineOptionKind::Spin { default: 16, min: 1, max: 1024 },
+/// };
+/// assert_eq!(
+///     serde_json::to_value(&option).unwrap(),
+///     serde_json::json!({ "name": "Hash", "type": "spin", "default": 16, "min": 1, "max": 1024 }),
+/// );
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EngineOption {
+    pub name: String,
+
+    /// What kind of value the option takes, and its default.
+    #[serde(flatten)]
+    pub kind: EngineOptionKind,
+}
+
+/// The kinds of values an [`EngineOption`] can take, mirroring the UCI option types.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EngineOptionKind {
+    /// A whole number between `min` and `max`, inclusive.
+    Spin { default: i64, min: i64, max: i64 },
+
+    /// On or off.
+    Check { default: bool },
+
+    /// One of a fixed list of strings.
+    Combo {
+        default: String,
+        values: Vec<String>,
+    },
+
+    /// Any string.
+    String { default: String },
+}
+
 /// Type-erased [`EngineInfo`], where the engine-specific fields have been replaced with [`serde_json::Value`].
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AnyEngineInfo {
@@ -58,10 +439,963 @@ pub struct AnyEngineInfo {
     /// A human-readable description of what the engine does.
     pub description: String,
 
+    /// The name to show for the engine in lists, if it differs from the id.
+    #[serde(default)]
+    pub display_name: Option<String>,
+
+    /// Who wrote the engine.
+    #[serde(default)]
+    pub author: Option<String>,
+
+    /// A URL of an image to show next to the engine's name.
+    #[serde(default)]
+    pub icon_url: Option<String>,
+
+    /// The engine's own version string, if it reports one.
+    #[serde(default)]
+    pub version: Option<String>,
+
+    /// The source revision or build ID the engine was built from, if it reports one.
+    /// Engines usually set this at build time, e.g. with `option_env!("GIT_HASH")`.
+    /// Unlike `version`, it identifies the exact build, for checking which one produced a move.
+    #[serde(default)]
+    pub build_hash: Option<String>,
+
+    /// Which optional features the engine actually implements.
+    #[serde(default)]
+    pub capabilities: Capabilities,
+
+    /// The move request schema versions that the server can read; see [`EngineRequest::protocol_version`].
+    /// The server fills this in, so engines can leave it empty. Empty for servers from before versioning,
+    /// which only read version 1 requests reliably.
+    #[serde(default)]
+    pub protocol_versions: Vec<u32>,
+
+    /// The settings that the engine can be configured with, for clients to build a settings panel from.
+    #[serde(default)]
+    pub options: Vec<EngineOption>,
+
     /// Initial state value. Pass this when making a move.
     pub initial_state: Value,
 }
 
+/// Versions of the software serving an engine, for debugging deployments.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VersionInfo {
+    /// The version of this crate that the server was built with.
+    pub crate_version: String,
+
+    /// The engine's algorithm ID.
+    pub engine_id: String,
+
+    /// A human-readable description of what the engine does.
+    pub engine_description: String,
+
+    /// The engine's own version string, if it reports one.
+    pub engine_version: Option<String>,
+
+    /// The engine's build hash, if it reports one. See [`EngineInfo::build_hash`].
+    pub engine_build_hash: Option<String>,
+}
+
+/// Ask the engine how complex a position is.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ComplexityRequest {
+    /// The position to estimate.
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+}
+
+/// The engine's estimate of a position's complexity.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ComplexityResponse {
+    /// From 0 (trivial) to 100 (very complex).
+    pub complexity: u8,
+}
+
+/// An engine's evaluation of a position.
+///
+/// Scores are always from the perspective of the side to move:
+/// a positive score means that the side to move is better.
+/// Use [`Score::from_white_perspective`] to get a score that means the same thing for both colors.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Score {
+    /// An advantage measured in hundredths of a pawn.
+    Centipawns(i32),
+
+    /// A forced mate in this many moves.
+    /// It is negative if the side to move is the one getting mated.
+    ///
+    /// An engine that reports one must report the mating line as its principal variation,
+    /// which in debug builds must end in mate after exactly as many moves, or the turn fails with
+    /// [`EngineRequestError::EngineSentInvalidMateLine`]:
+    ///
+    /// ```
+    /// use engine_trait::{
+    ///     async_trait,
+    ///     game::play_turn,
+    ///     server_types::{EngineInfo, EngineRequest, EngineRequestError, EngineResult, Score},
+    ///     shakmaty::{Chess, Move, Role, Square},
+    ///     Engine, InfallibleError, MoveSummary,
+    /// };
+    ///
+    /// // Fool's mate, which the engine below plays.
+    /// const QH4: Move = Move::Normal { role: Role::Queen, from: Square::D8, capture: None, to: Square::H4, promotion: None };
+    ///
+    /// /// Plays Qh4, and claims that it is a mate in this many moves.
+    /// struct Mater(i32);
+    ///
+    /// #[async_trait]
+    /// impl Engine for Mater {
+    ///     type State = ();
+    ///     type StatusInfo = i32;
+    ///     type Error = InfallibleError;
+    ///
+    ///     fn get_info() -> EngineInfo<Self> {
+    ///         EngineInfo::new("mater", "Plays Qh4, whatever it says", ())
+    ///     }
+    ///
+    ///     async fn propose_move(&mut self, _: u64, _: &(), _: &Chess) -> Result<(Move, i32), InfallibleError> {
+    ///         Ok((QH4, self.0))
+    ///     }
+    ///
+    ///     async fn observe_move(&mut self, _: u64, _: &mut (), _: &Move, _: &Chess) -> Result<(), InfallibleError> {
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn summarize(moves: &i32) -> MoveSummary {
+    ///         MoveSummary { score: Some(Score::Mate(*moves)), pv: vec![QH4], ..MoveSummary::default() }
+    ///     }
+    /// }
+    ///
+    /// let request = || -> EngineRequest<Mater> {
+    ///     serde_json::from_value(serde_json::json!({
+    ///         "game_before": "rnbqkbnr/pppp1ppp/8/4p3/8/5P2/PPPPP1PP/RNBQKBNR w KQkq - 0 2",
+    ///         "move": "g2g4",
+    ///         "with_status_info": true,
+    ///     }))
+    ///     .unwrap()
+    /// };
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let EngineResult::Ok(response) = play_turn(&mut Mater(1), request()).await else {
+    ///     panic!("Qh4 is mate in one");
+    /// };
+    /// assert_eq!(response.mate_line, ["d8h4".parse().unwrap()]);
+    ///
+    /// let result = play_turn(&mut Mater(2), request()).await;
+    /// if cfg!(debug_assertions) {
+    ///     assert!(matches!(result, EngineResult::RequestError(EngineRequestError::EngineSentInvalidMateLine { .. })));
+    /// } else {
+    ///     assert!(matches!(result, EngineResult::Ok(_)));
+    /// }
+    /// # });
+    /// ```
+    Mate(i32),
+}
+
+impl Score {
+    /// Convert a score from the perspective of `side_to_move` into one from White's perspective.
+    ///
+    /// This flips the sign if Black is to move.
+    /// The conversion is its own inverse, so it also converts White's perspective back into the side to move's.
+    pub fn from_white_perspective(self, side_to_move: Color) -> Score {
+        match side_to_move {
+            Color::White => self,
+            Color::Black => -self,
+        }
+    }
+}
+
+/// A move an engine considered, with its score and the replies it considered to it, for showing a search as a tree.
+///
+/// Engines that do not search a tree get the default [`Engine::analyze_tree`], which gives only their move,
+/// without a score or replies. Every move in a tree is checked before it is sent, promotions included:
+///
+/// ```
+/// use engine_trait::{
+///     game::search_tree,
+///     server_types::TreeRequest,
+///     shakmaty::{fen::Fen, CastlingMode, Chess},
+/// };
+/// # use engine_trait::fixtures::FirstMove;
+///
+/// // White's king is boxed in, so every legal move is a promotion.
+/// let position: Chess = "8/4P3/8/8/8/1p6/2k5/K7 w - - 0 1"
+///     .parse::<Fen>()
+///     .unwrap()
+///     .into_position(CastlingMode::Standard)
+///     .unwrap();
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let request = TreeRequest::<FirstMove> { position, engine_state: (), rand: None, breadth: 3, depth: 2 };
+/// let response = search_tree(&mut FirstMove, request).await.unwrap();
+/// assert_eq!(response.tree.len(), 1);
+/// let tree = &response.tree[0];
+/// assert!(tree.r#move.to_string().starts_with("e7e8"));
+/// assert_eq!(tree.r#move.to_string().len(), 5);
+/// assert_eq!((tree.score, tree.children.len()), (None, 0));
+/// # });
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SearchTree {
+    #[serde(with = "crate::chess_serde::uci_serde")]
+    pub r#move: Uci,
+
+    /// The score of the move, from the perspective of the side that plays it.
+    #[serde(default)]
+    pub score: Option<Score>,
+
+    /// The replies to the move, best first.
+    #[serde(default)]
+    pub children: Vec<SearchTree>,
+}
+
+impl SearchTree {
+    /// Cut `trees` down to at most `breadth` moves on each level, and `depth` levels in total.
+    ///
+    /// ```
+    /// use engine_trait::server_types::SearchTree;
+    ///
+    /// let leaf = |uci: &str| SearchTree { r#move: uci.parse().unwrap(), score: None, children: Vec::new() };
+    /// let mut trees = vec![
+    ///     SearchTree { children: vec![leaf("e7e5"), leaf("c7c5")], ..leaf("e2e4") },
+    ///     leaf("d2d4"),
+    /// ];
+    /// SearchTree::prune(&mut trees, 1, 1);
+    /// assert_eq!(trees, vec![leaf("e2e4")]);
+    /// ```
+    pub fn prune(trees: &mut Vec<SearchTree>, breadth: usize, depth: usize) {
+        if depth == 0 {
+            trees.clear();
+            return;
+        }
+        trees.truncate(breadth);
+        for tree in trees {
+            SearchTree::prune(&mut tree.children, breadth, depth - 1);
+        }
+    }
+
+    /// The first move in `trees`, in depth-first order, that is not legal where it is played, starting from `position`.
+    ///
+    /// Trees from [`Engine::analyze_tree`] and from [`MoveSummary::search_tree`](crate::MoveSummary::search_tree)
+    /// are both checked with this, and an illegal move is reported as [`EngineRequestError::EngineSentIllegalMove`]:
+    ///
+    /// ```
+    /// use engine_trait::{
+    ///     async_trait,
+    ///     game::play_turn,
+    ///     server_types::{EngineInfo, EngineRequest, EngineRequestError, EngineResult, SearchTree},
+    ///     shakmaty::{Chess, Move, Position},
+    ///     Engine, InfallibleError, MoveSummary,
+    /// };
+    ///
+    /// /// Plays the first legal move, and shows it with a reply that may not be legal.
+    /// struct Dreamer(&'static str);
+    ///
+    /// #[async_trait]
+    /// impl Engine for Dreamer {
+    ///     type State = ();
+    ///     type StatusInfo = (String, String);
+    ///     type Error = InfallibleError;
+    ///
+    ///     fn get_info() -> EngineInfo<Self> {
+    ///         EngineInfo::new("dreamer", "Expects the reply it likes", ())
+    ///     }
+    ///
+    ///     async fn propose_move(&mut self, _: u64, _: &(), position: &Chess) -> Result<(Move, (String, String)), InfallibleError> {
+    ///         let m = position.legal_moves()[0].clone();
+    ///         let uci = m.to_uci(position.castles().mode()).to_string();
+    ///         Ok((m, (uci, self.0.to_string())))
+    ///     }
+    ///
+    ///     async fn observe_move(&mut self, _: u64, _: &mut (), _: &Move, _: &Chess) -> Result<(), InfallibleError> {
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn summarize((m, reply): &(String, String)) -> MoveSummary {
+    ///         let leaf = |uci: &str| SearchTree { r#move: uci.parse().unwrap(), score: None, children: Vec::new() };
+    ///         MoveSummary { search_tree: vec![SearchTree { children: vec![leaf(reply)], ..leaf(m) }], ..MoveSummary::default() }
+    ///     }
+    /// }
+    ///
+    /// let request = || -> EngineRequest<Dreamer> {
+    ///     serde_json::from_value(serde_json::json!({ "move": "e2e4", "with_status_info": true })).unwrap()
+    /// };
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let EngineResult::Ok(response) = play_turn(&mut Dreamer("g1f3"), request()).await else {
+    ///     panic!("white can answer with Nf3");
+    /// };
+    /// assert_eq!(response.search_tree[0].children[0].r#move.to_string(), "g1f3");
+    ///
+    /// // White's queen cannot jump over its own pawn.
+    /// let result = play_turn(&mut Dreamer("d1d3"), request()).await;
+    /// assert!(matches!(
+    ///     result,
+    ///     EngineResult::RequestError(EngineRequestError::EngineSentIllegalMove { r#move }) if r#move.to_string() == "d1d3"
+    /// ));
+    /// # });
+    /// ```
+    pub fn first_illegal_move(trees: &[SearchTree], position: &Chess) -> Option<Uci> {
+        trees
+            .iter()
+            .find_map(|tree| match tree.r#move.to_move(position) {
+                Ok(m) => {
+                    let mut after = position.clone();
+                    after.play_unchecked(&m);
+                    SearchTree::first_illegal_move(&tree.children, &after)
+                }
+                Err(_) => Some(tree.r#move.clone()),
+            })
+    }
+
+    /// The most nodes that a search tree in a move response may have.
+    pub const MAX_NODES: usize = 256;
+
+    /// How many nodes `trees` has, counting every move on every level.
+    pub fn node_count(trees: &[SearchTree]) -> usize {
+        trees
+            .iter()
+            .map(|tree| 1 + SearchTree::node_count(&tree.children))
+            .sum()
+    }
+
+    /// Cut `trees` down to their first `max` nodes in breadth-first order, so that the moves closest to the root,
+    /// which viewers show first, are the ones kept.
+    ///
+    /// ```
+    /// use engine_trait::server_types::SearchTree;
+    ///
+    /// let leaf = |uci: &str| SearchTree { r#move: uci.parse().unwrap(), score: None, children: Vec::new() };
+    /// let mut trees = vec![
+    ///     SearchTree { children: vec![leaf("e7e5"), leaf("c7c5")], ..leaf("e2e4") },
+    ///     SearchTree { children: vec![leaf("d7d5")], ..leaf("d2d4") },
+    /// ];
+    /// SearchTree::limit_nodes(&mut trees, 3);
+    /// assert_eq!(SearchTree::node_count(&trees), 3);
+    /// assert_eq!(trees[0].children, vec![leaf("e7e5")]);
+    /// assert_eq!(trees[1], leaf("d2d4"));
+    /// ```
+    pub fn limit_nodes(trees: &mut Vec<SearchTree>, max: usize) {
+        let mut budget = max;
+        let mut level = vec![trees];
+        while !level.is_empty() {
+            let mut next = Vec::new();
+            for trees in level {
+                trees.truncate(budget);
+                budget -= trees.len();
+                next.extend(trees.iter_mut().map(|tree| &mut tree.children));
+            }
+            level = next;
+        }
+    }
+}
+
+/// A standard reason for choosing a move, for frontends that explain an engine's moves.
+///
+/// The common reasons have names of their own, so that frontends can show them consistently across engines;
+/// anything else is a plain string, which frontends may show as it is.
+///
+/// ```
+/// use engine_trait::server_types::MoveReason;
+///
+/// let reasons = vec![MoveReason::DevelopsPiece, MoveReason::Other("eyes_f7".to_string())];
+/// let json = serde_json::to_string(&reasons).unwrap();
+/// assert_eq!(json, r#"["develops_piece","eyes_f7"]"#);
+/// assert_eq!(serde_json::from_str::<Vec<MoveReason>>(&json).unwrap(), reasons);
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum MoveReason {
+    /// The move brings a piece off its starting square.
+    DevelopsPiece,
+
+    /// The move occupies or attacks the central squares.
+    ControlsCenter,
+
+    /// The move castles, or otherwise shelters the king.
+    ImprovesKingSafety,
+
+    /// The move captures material.
+    WinsMaterial,
+
+    /// The move attacks the opponent's queen.
+    AttacksQueen,
+
+    /// The move attacks two or more pieces at once.
+    Fork,
+
+    /// The move pins a piece to a more valuable one behind it.
+    Pin,
+
+    /// The move gives check.
+    Check,
+
+    /// The move gives checkmate.
+    Checkmate,
+
+    /// The move defends a piece that was under attack.
+    DefendsPiece,
+
+    /// The move takes a piece out of an attack.
+    EscapesThreat,
+
+    /// The move advances a passed pawn, or promotes one.
+    AdvancesPassedPawn,
+
+    /// A reason that has no name of its own.
+    #[serde(untagged)]
+    Other(String),
+}
+
+/// Where an engine's move came from, for frontends that show book or tablebase moves differently.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MoveSource {
+    /// The move was taken from an opening book.
+    Book,
+
+    /// The engine chose the move by thinking about the position.
+    #[default]
+    Search,
+
+    /// The move was looked up in an endgame tablebase.
+    Tablebase,
+}
+
+impl std::ops::Neg for Score {
+    type Output = Score;
+
+    /// Saturates, so that an engine reporting `i32::MIN` cannot make the server overflow.
+    fn neg(self) -> Score {
+        match self {
+            Score::Centipawns(v) => Score::Centipawns(v.saturating_neg()),
+            Score::Mate(v) => Score::Mate(v.saturating_neg()),
+        }
+    }
+}
+
+/// Ask the engine which move it would play in a position, without playing it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HintRequest<E: Engine> {
+    /// The position to probe. It is not treated as part of a game.
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+
+    /// The engine's state in `position`. If left out, the state's default is used.
+    #[serde(default)]
+    pub engine_state: E::State,
+
+    /// What random number to give to the engine? If None, it will be generated.
+    #[serde(default)]
+    pub rand: Option<u64>,
+}
+
+/// Start several games at once.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewGamesRequest {
+    /// How many games to start. Servers refuse more than 1000 with [`EngineRequestError::TooManyGames`].
+    pub count: usize,
+
+    /// What random number to derive each game's seed from? If None, it will be generated.
+    #[serde(default)]
+    pub rand: Option<u64>,
+}
+
+/// The engine's starting state for each of the new games, from [`Engine::fresh_state`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewGamesResponse<E: Engine> {
+    pub engine_states: Vec<E::State>,
+
+    /// The random number that each game's seed was derived from.
+    /// The seed of game `i` is [`RandomSource::stream_seed`](crate::random::RandomSource::stream_seed)`(i)`.
+    pub rand_used: u64,
+
+    /// Whether `rand_used` came from the request or was generated by the server.
+    /// None if the server was too old to say.
+    #[serde(default)]
+    pub rand_origin: Option<SeedOrigin>,
+}
+
+/// Ask the engine for a small search tree of a position. See [`Engine::analyze_tree`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TreeRequest<E: Engine> {
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+
+    /// The engine's state in `position`. If left out, the state's default is used.
+    #[serde(default)]
+    pub engine_state: E::State,
+
+    /// What random number to give to the engine? If None, it will be generated.
+    #[serde(default)]
+    pub rand: Option<u64>,
+
+    /// How many moves to consider on each level. The server may lower it.
+    pub breadth: usize,
+
+    /// How many plies deep to go. The server may lower it.
+    pub depth: usize,
+}
+
+/// The engine's search tree of a position.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TreeResponse {
+    /// The moves in the position, best first, each with its replies.
+    pub tree: Vec<SearchTree>,
+
+    /// The breadth and depth that were actually used, after the server's limits.
+    pub breadth: usize,
+    pub depth: usize,
+
+    /// The random number that the engine was given.
+    pub rand_used: u64,
+
+    /// Whether `rand_used` came from the request or was generated by the server.
+    /// None if the server was too old to say.
+    #[serde(default)]
+    pub rand_origin: Option<SeedOrigin>,
+}
+
+/// The move the engine would play. Nothing about the game or the engine's state changes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HintResponse {
+    #[serde(with = "crate::chess_serde::uci_serde")]
+    pub r#move: Uci,
+
+    /// The position from the request, exactly as it was, counters included.
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+
+    /// The random number that the engine was given.
+    pub rand_used: u64,
+
+    /// Whether `rand_used` came from the request or was generated by the server.
+    /// None if the server was too old to say.
+    #[serde(default)]
+    pub rand_origin: Option<SeedOrigin>,
+}
+
+/// What the opponent of the side to move threatens, from [`threat_move`](crate::game::threat_move).
+/// The request is a [`HintRequest`] for the position as it is, before passing the turn.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThreatMoveResponse {
+    /// The move that the opponent would play if it were its turn.
+    /// None if the opponent would have no legal moves, or if the side to move is in check.
+    #[serde(with = "crate::chess_serde::uci_option_serde")]
+    pub threat: Option<Uci>,
+
+    /// How the threat was found.
+    pub method: ThreatMethod,
+
+    /// The random number that the engine was given. It is not used with [`ThreatMethod::InCheck`].
+    pub rand_used: u64,
+
+    /// Whether `rand_used` came from the request or was generated by the server.
+    /// None if the server was too old to say.
+    #[serde(default)]
+    pub rand_origin: Option<SeedOrigin>,
+}
+
+/// How a [`ThreatMoveResponse`] found its threat.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreatMethod {
+    /// The turn was passed with a null move, and the engine was asked for its move as the opponent.
+    NullMove,
+
+    /// The side to move is in check, so it cannot pass, and the engine was not asked.
+    /// The threat is the check itself, so there is no threat move: capturing the king is not a move.
+    InCheck,
+}
+
+/// End a game because the side to move ran out of time, without asking the engine for a move.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClaimWinRequest {
+    /// The position in which the side to move ran out of time.
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+
+    /// The game being ended, so that the server's game log can record it. See [`EngineRequest::game_id`].
+    #[serde(default)]
+    pub game_id: Option<String>,
+}
+
+/// How a game ended on time.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClaimWinResponse {
+    /// Either [`GameOutcome::Timeout`], or [`GameOutcome::TimeoutVsInsufficientMaterial`]
+    /// if the other side could not have won anyway.
+    pub outcome: GameOutcome,
+}
+
+/// List the legal moves in a position, without involving the engine.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LegalMovesRequest {
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+}
+
+/// The legal moves in a position.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LegalMovesResponse {
+    /// The moves, sorted by their UCI notation so that the output is reproducible.
+    #[serde(with = "crate::chess_serde::uci_list_serde")]
+    pub moves: Vec<Uci>,
+}
+
+/// Describe a position with [`PositionFeatures`](crate::features::PositionFeatures), without involving the engine.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FeaturesRequest {
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+}
+
+/// Find the hanging pieces in a position, without involving the engine.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThreatsRequest {
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+}
+
+/// The hanging pieces of both sides, as defined by [`hanging_pieces`](crate::features::hanging_pieces).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThreatsResponse {
+    /// The side to move's pieces that are hanging, i.e. the threats it has to deal with.
+    #[serde(with = "crate::chess_serde::square_list_serde")]
+    pub hanging: Vec<Square>,
+
+    /// The other side's pieces that are hanging, i.e. what the side to move can capture for free.
+    #[serde(with = "crate::chess_serde::square_list_serde")]
+    pub targets: Vec<Square>,
+}
+
+/// Look up a position in the server's Syzygy tablebases, without involving the engine.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TablebaseRequest {
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+}
+
+/// What the tablebases say about a position, for the side to move.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TablebaseResponse {
+    /// The result with best play, assuming that the position was reached by a capture or pawn move.
+    pub wdl: TablebaseWdl,
+
+    /// The plies until the next capture or pawn move with best play: positive when winning,
+    /// negative when losing, and zero when drawn.
+    pub dtz: i32,
+
+    /// Set if `dtz` may be one ply more than the truth, since the tables store some distances rounded.
+    pub dtz_rounded: bool,
+}
+
+/// A tablebase result under the 50-move rule.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TablebaseWdl {
+    Loss,
+
+    /// A loss, except that the 50-move rule saves it.
+    BlessedLoss,
+
+    Draw,
+
+    /// A win, except that the 50-move rule spoils it.
+    CursedWin,
+
+    Win,
+}
+
+/// Take back the last move of the game, and the engine's reply to it if there was one,
+/// then play a replacement move instead, all in one step. See [`Engine::unobserve_move`].
+///
+/// Apart from the moves to take back, this is a move request for the replacement:
+/// its `move` is the replacement, its `game_before` is the position that `last_move` was played in,
+/// and its `engine_state` is the state after all the moves being taken back.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(bound = "")]
+pub struct AmendRequest<E: Engine> {
+    /// The move being replaced.
+    #[serde(with = "crate::chess_serde::uci_serde")]
+    pub last_move: Uci,
+
+    /// The engine's reply to `last_move`, if it already made one. It is taken back first.
+    #[serde(with = "crate::chess_serde::uci_option_serde", default)]
+    pub engine_reply: Option<Uci>,
+
+    /// What random number to give to the engine when taking back `engine_reply`? If None, it will be generated.
+    #[serde(default)]
+    pub unobserve_reply_rand: Option<u64>,
+
+    /// What random number to give to the engine when taking back `last_move`? If None, it will be generated.
+    #[serde(default)]
+    pub unobserve_last_rand: Option<u64>,
+
+    #[serde(flatten)]
+    pub request: EngineRequest<E>,
+}
+
+/// Encode a position in the compact binary form of [`encode_position`](crate::position::encode_position).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EncodePositionRequest {
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+}
+
+/// A position in compact binary form, written in hexadecimal.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EncodePositionResponse {
+    #[serde(with = "crate::chess_serde::hex_serde")]
+    pub encoded: Vec<u8>,
+}
+
+/// Decode a position from the compact binary form of [`encode_position`](crate::position::encode_position).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DecodePositionRequest {
+    #[serde(with = "crate::chess_serde::hex_serde")]
+    pub encoded: Vec<u8>,
+}
+
+/// The decoded position, as FEN.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DecodePositionResponse {
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+}
+
+/// Check whether a move is legal, without involving the engine.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IsLegalRequest {
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+
+    #[serde(with = "crate::chess_serde::uci_serde")]
+    pub r#move: Uci,
+}
+
+/// Whether a move is legal.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IsLegalResponse {
+    pub legal: bool,
+
+    /// If the move is not legal, why.
+    #[serde(default)]
+    pub reason: Option<IllegalMoveReason>,
+}
+
+/// Check whether a queued premove will be legal once the engine has moved, without involving the engine.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PremoveCheckRequest {
+    /// The position before the engine's move.
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+
+    #[serde(with = "crate::chess_serde::uci_serde")]
+    pub engine_move: Uci,
+
+    /// The move that the player queued up while the engine was thinking.
+    #[serde(with = "crate::chess_serde::uci_serde")]
+    pub premove: Uci,
+}
+
+/// Whether a premove is legal after the engine's move.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PremoveCheckResponse {
+    pub legal: bool,
+
+    /// If the premove is not legal, why.
+    #[serde(default)]
+    pub reason: Option<IllegalMoveReason>,
+
+    /// The position after the engine's move, in which the premove would be played.
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+}
+
+/// Count the positions reachable from a position, split by the first move, without involving the engine.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PerftDivideRequest {
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+
+    /// How many plies deep to count.
+    pub depth: u32,
+}
+
+/// The leaf counts of a perft divide.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PerftDivideResponse {
+    /// The number of leaf positions below each legal move, keyed by the move in UCI, sorted by move.
+    pub moves: BTreeMap<String, u64>,
+
+    /// The number of leaf positions in total.
+    pub nodes: u64,
+}
+
+/// Apply a sequence of moves to a position, without involving the engine.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApplyMovesRequest {
+    /// The position to start from.
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+
+    /// The moves to play, in order.
+    #[serde(with = "crate::chess_serde::uci_list_serde")]
+    pub moves: Vec<Uci>,
+}
+
+/// Go through a recorded game with the engine, for reviewing it. See [`ReviewedPly`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReviewRequest {
+    /// The position the game started from.
+    #[serde(with = "crate::chess_serde::position_serde", default)]
+    pub position: Chess,
+
+    /// The moves of the game, in order. The server limits how many it goes through.
+    #[serde(with = "crate::chess_serde::uci_list_serde")]
+    pub moves: Vec<Uci>,
+
+    /// What random number to seed the engine's calls with?
+    /// If None, it will be generated.
+    #[serde(default)]
+    pub rand: Option<u64>,
+}
+
+/// The first event of a review stream, before any [`ReviewedPly`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReviewStart {
+    /// The random number that the engine's calls were seeded with, for reproducing the review.
+    pub rand_used: u64,
+
+    /// Whether `rand_used` came from the request or was generated by the server.
+    /// None if the server was too old to say.
+    #[serde(default)]
+    pub rand_origin: Option<SeedOrigin>,
+
+    /// How many plies the review will go through, unless a turn fails.
+    pub plies: usize,
+}
+
+/// What the engine would have played instead of one move of a reviewed game.
+/// A review UI can compare `played` with `best`, and the scores of successive plies, to find the mistakes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReviewedPly {
+    /// The ply of the played move, counting from 1.
+    pub ply: usize,
+
+    /// The move that was played.
+    #[serde(with = "crate::chess_serde::uci_serde")]
+    pub played: Uci,
+
+    /// The move that the engine prefers in the same position.
+    #[serde(with = "crate::chess_serde::uci_serde")]
+    pub best: Uci,
+
+    /// The engine's evaluation of the position before the move, from the perspective of the side that played it.
+    /// It comes from the engine's status info, or else from [`Engine::evaluate`]; None if neither has one.
+    pub score: Option<Score>,
+}
+
+/// The position reached by applying a sequence of moves.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApplyMovesResponse {
+    /// The position after the last move.
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub position: Chess,
+}
+
+/// Query parameters for streaming a game that the engine plays against itself.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SelfplayQuery {
+    /// The position to start from. Defaults to the standard start position.
+    #[serde(with = "crate::chess_serde::position_serde", default)]
+    pub fen: Chess,
+
+    /// Stop after this many plies, even if the game is not over.
+    /// The server caps this to its own limit.
+    #[serde(default)]
+    pub max_plies: Option<usize>,
+}
+
+/// One move of a streamed self-play game.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SelfplayMove {
+    /// The move that was played.
+    /// It is a null move if the start position was already over.
+    #[serde(with = "crate::chess_serde::uci_serde")]
+    pub r#move: Uci,
+
+    /// The position after the move.
+    #[serde(with = "crate::chess_serde::position_serde")]
+    pub fen: Chess,
+
+    /// If this move ended the game, how it ended. This is the last event of the stream.
+    #[serde(default)]
+    pub outcome: Option<GameOutcome>,
+}
+
+/// Ask the engine to evaluate every position in a PGN game.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AnalyzePgnRequest {
+    /// The game, in PGN format. Only the first game and its main line are analyzed.
+    pub pgn: String,
+
+    /// What random number to seed the engine's calls with?
+    /// If None, it will be generated.
+    pub rand: Option<u64>,
+}
+
+/// The engine's evaluation after one move of an analyzed game.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AnalyzedPly {
+    /// The ply that was just played, counting from 1.
+    pub ply: usize,
+
+    /// The move that was played.
+    #[serde(with = "crate::chess_serde::uci_serde")]
+    pub r#move: Uci,
+
+    /// The engine's evaluation of the position after the move, from the perspective of the side to move.
+    pub score: Option<Score>,
+
+    /// The same evaluation, from White's perspective.
+    pub white_score: Option<Score>,
+}
+
+/// The result of analyzing a PGN game.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AnalyzePgnResponse {
+    /// The random number that the engine's calls were seeded with.
+    pub rand_used: u64,
+
+    /// Whether `rand_used` came from the request or was generated by the server.
+    /// None if the server was too old to say.
+    #[serde(default)]
+    pub rand_origin: Option<SeedOrigin>,
+
+    /// The evaluation after each move of the game, in order.
+    pub plies: Vec<AnalyzedPly>,
+}
+
+/// What one engine of an A/B pair answered to a compared request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompareSide {
+    /// The HTTP status that the engine's own endpoint answered with.
+    pub status: u16,
+
+    /// The body that the engine's own endpoint answered with, or null if it was not JSON.
+    pub body: Value,
+}
+
+/// The answers of both engines of an A/B pair to the same request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompareResponse {
+    pub a: CompareSide,
+    pub b: CompareSide,
+}
+
 /// Errors relating to a submitted request, independent of the engine.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[non_exhaustive]
@@ -69,13 +1403,318 @@ pub enum EngineRequestError {
     /// The provided move is not legal in the provided position, or not at all.
     PositionMoveMismatch,
 
-    /// The engine has generated a move that is not legal in the corresponding position.
+    /// The engine has generated a move that is not legal in the corresponding position,
+    /// or that is not among the moves the request restricted it to.
     /// This is a bug in the engine.
     /// The suggested move is included.
     EngineSentIllegalMove {
         #[serde(with = "crate::chess_serde::uci_serde")]
         r#move: Uci,
     },
+
+    /// The requested Chess960 start position number is not between 0 and 959.
+    InvalidChess960Number { sp: u16 },
+
+    /// The server does not accept requests starting from this position.
+    PositionNotAllowed,
+
+    /// The submitted PGN could not be read.
+    InvalidPgn { reason: String },
+
+    /// A move in a sequence is not legal in the position it is played in.
+    /// The index of the move in the sequence is included.
+    IllegalMoveInSequence {
+        index: usize,
+        #[serde(with = "crate::chess_serde::uci_serde")]
+        r#move: Uci,
+    },
+
+    /// The engine claimed a forced mate, but its line does not deliver one, or not in the number of moves claimed.
+    /// This is a bug in the engine, and is only checked in debug builds.
+    EngineSentInvalidMateLine {
+        #[serde(with = "crate::chess_serde::uci_list_serde")]
+        line: Vec<Uci>,
+    },
+
+    /// The engine's principal variation contains a move that is not legal where it is played.
+    /// This is a bug in the engine. The line up to and including that move is included.
+    EngineSentIllegalPv {
+        #[serde(with = "crate::chess_serde::uci_list_serde")]
+        line: Vec<Uci>,
+    },
+
+    /// The engine does not support this optional operation, according to its [`Capabilities`].
+    /// The server answers this with `501 Not Implemented`.
+    Unsupported { operation: String },
+
+    /// A position did not have the hash that the request expected,
+    /// so the client and the server disagree about the game.
+    StateDesync {
+        step: DesyncStep,
+        expected: u64,
+        actual: u64,
+    },
+
+    /// A move in `restrict_to` is not legal in the position the engine has to move in.
+    IllegalRestriction {
+        #[serde(with = "crate::chess_serde::uci_serde")]
+        r#move: Uci,
+    },
+
+    /// The game was already over, so it cannot end another way.
+    GameAlreadyOver { outcome: GameOutcome },
+
+    /// A perft request asked for a depth that would take too long to count.
+    DepthTooLarge { max: u32 },
+
+    /// A game to review or analyze has more plies than the server goes through.
+    GameTooLong { max: usize },
+
+    /// A request to start new games asked for more games than the server starts at once.
+    TooManyGames { max: usize },
+
+    /// The engine claimed a draw, but neither threefold repetition nor the 50-move rule applies.
+    /// Repetitions can only be found in the request's `position_history`.
+    InvalidDrawClaim,
+
+    /// The engine changed the board after its move into a position that is not legal.
+    /// This is a bug in the engine. The position and the reason it is illegal are included.
+    EngineSentIllegalPosition { fen: String, reason: String },
+
+    /// The engine's win, draw and loss probabilities are negative or do not sum to 1.
+    /// This is a bug in the engine, and is only checked in debug builds.
+    EngineSentInvalidWdl { wdl: (f32, f32, f32) },
+
+    /// The starting position has castling rights that cannot be written in the requested `castling_mode`.
+    CastlingModeMismatch,
+
+    /// The bytes are not a binary encoding of a legal position.
+    InvalidEncodedPosition,
+
+    /// The request's `protocol_version` is not one that this server can read.
+    UnsupportedProtocolVersion { version: u32, supported: Vec<u32> },
+
+    /// The position has more pieces than the server's tablebases cover.
+    TablebaseOutOfRange { pieces: usize, max: usize },
+
+    /// The position could not be looked up in the server's tablebases,
+    /// for example because it has castling rights, or a table is missing.
+    TablebaseProbeFailed { reason: String },
+
+    /// The engine cannot play in the position before or after the move, as decided by
+    /// [`Engine::supports_position`], for the given reason.
+    UnsupportedPosition { reason: String },
+
+    /// The `game_id` contains control characters, so the server's game log cannot record it.
+    InvalidGameId,
+}
+
+/// Which position failed a hash check. See [`EngineRequestError::StateDesync`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DesyncStep {
+    /// The position before the other side's move.
+    GameBefore,
+
+    /// The position after the other side's move.
+    GameAfterMove,
+}
+
+/// How a game ended.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GameOutcome {
+    /// The side to move is checkmated.
+    Checkmate {
+        #[serde(with = "crate::chess_serde::color_serde")]
+        winner: Color,
+    },
+
+    /// The side to move has no legal moves, but is not in check.
+    Stalemate,
+
+    /// Neither side has enough material to checkmate.
+    InsufficientMaterial,
+
+    /// A variant-specific win condition was met, like three checks, or a king reaching the center.
+    VariantWin {
+        #[serde(with = "crate::chess_serde::color_serde")]
+        winner: Color,
+    },
+
+    /// A variant-specific rule ended the game in a draw.
+    VariantDraw,
+
+    /// The same position occurred for the fifth time. Only detected under strict rules.
+    FivefoldRepetition,
+
+    /// 75 moves by each side passed without a capture or pawn move. Only detected under strict rules.
+    SeventyFiveMoveRule,
+
+    /// The same position occurred for the third time, and the engine claimed a draw.
+    ThreefoldRepetition,
+
+    /// 50 moves by each side passed without a capture or pawn move, and the engine claimed a draw.
+    FiftyMoveRule,
+
+    /// The side to move ran out of time, and lost. Only declared through `/claim-win`.
+    Timeout {
+        #[serde(with = "crate::chess_serde::color_serde")]
+        winner: Color,
+    },
+
+    /// The side to move ran out of time, but the other side has too little material to ever checkmate,
+    /// so the game is drawn. Only declared through `/claim-win`.
+    TimeoutVsInsufficientMaterial,
+
+    /// The engine ended the game without a result, with [`EngineAction::Abort`](crate::EngineAction::Abort).
+    /// Neither side won or drew; the game should be discarded, or replayed.
+    Aborted { reason: String },
+}
+
+impl GameOutcome {
+    /// The result of the game, or None if it was aborted and has no result.
+    ///
+    /// This used to return an [`Outcome`] directly, before games could be aborted;
+    /// callers that cannot handle aborted games can still count them as draws with `.unwrap_or(Outcome::Draw)`.
+    ///
+    /// ```
+    /// use engine_trait::{
+    ///     game::play_turn, gauntlet::run_gauntlet,
+    ///     server_types::{EngineRequest, EngineResult, GameOutcome},
+    ///     shakmaty::uci::Uci,
+    /// };
+    /// # use engine_trait::fixtures::Aborter;
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let request: EngineRequest<Aborter> =
+    ///     serde_json::from_value(serde_json::json!({ "move": "0000", "with_status_info": false })).unwrap();
+    /// let EngineResult::Ok(response) = play_turn(&mut Aborter, request).await else {
+    ///     panic!("aborting is not a failure");
+    /// };
+    /// assert_eq!(response.r#move, Uci::Null);
+    /// let outcome = response.outcome.unwrap();
+    /// assert!(matches!(outcome, GameOutcome::Aborted { .. }));
+    /// assert_eq!(outcome.result(), None);
+    ///
+    /// // Aborted games count for neither side.
+    /// let table = run_gauntlet(Aborter, vec![Aborter], 2, 7).await.unwrap();
+    /// assert_eq!((table[0].aborted, table[0].games()), (2, 2));
+    /// assert_eq!(table[0].score(), 0.0);
+    /// # });
+    /// ```
+    pub fn result(&self) -> Option<Outcome> {
+        Some(match self {
+            GameOutcome::Aborted { .. } => return None,
+            GameOutcome::Checkmate { winner }
+            | GameOutcome::VariantWin { winner }
+            | GameOutcome::Timeout { winner } => Outcome::Decisive { winner: *winner },
+            GameOutcome::Stalemate
+            | GameOutcome::InsufficientMaterial
+            | GameOutcome::VariantDraw
+            | GameOutcome::FivefoldRepetition
+            | GameOutcome::SeventyFiveMoveRule
+            | GameOutcome::ThreefoldRepetition
+            | GameOutcome::FiftyMoveRule
+            | GameOutcome::TimeoutVsInsufficientMaterial => Outcome::Draw,
+        })
+    }
+}
+
+/// The random numbers that the engine was given while handling a request.
+///
+/// Pass these back in a request to reproduce the engine's behavior exactly:
+/// each `x_rand_used` field here goes into the request's `x_rand` field.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeedsUsed {
+    /// The random number we gave to the engine when it was observing the other side's move.
+    /// None if there was no move to observe.
+    #[serde(rename = "observe_other_rand_used")]
+    pub observe_other: Option<u64>,
+
+    /// The random number we gave to the engine when it was producing its move.
+    /// None if the engine did not move.
+    #[serde(rename = "produce_rand_used")]
+    pub produce: Option<u64>,
+
+    /// The random number we gave to the engine when it was observing its own move.
+    /// None if the engine did not move.
+    ///
+    /// This used to be called `observe_mine_rand_used`, and that name is still accepted.
+    #[serde(rename = "observe_own_rand_used", alias = "observe_mine_rand_used")]
+    pub observe_own: Option<u64>,
+
+    /// Whether `observe_other_rand_used` came from the request or was generated by the server.
+    /// None if there was no seed, or if the server was too old to say.
+    #[serde(rename = "observe_other_rand_origin", default)]
+    pub observe_other_origin: Option<SeedOrigin>,
+
+    /// Whether `produce_rand_used` came from the request or was generated by the server.
+    #[serde(rename = "produce_rand_origin", default)]
+    pub produce_origin: Option<SeedOrigin>,
+
+    /// Whether `observe_own_rand_used` came from the request or was generated by the server.
+    #[serde(rename = "observe_own_rand_origin", default)]
+    pub observe_own_origin: Option<SeedOrigin>,
+
+    /// The random number we gave to the engine when it was taking back its reply, for an amended turn.
+    /// None if there was no reply to take back, or the turn was not amended.
+    #[serde(rename = "unobserve_reply_rand_used", default)]
+    pub unobserve_reply: Option<u64>,
+
+    /// The random number we gave to the engine when it was taking back the move being replaced.
+    /// None if the turn was not amended.
+    #[serde(rename = "unobserve_last_rand_used", default)]
+    pub unobserve_last: Option<u64>,
+
+    /// Whether `unobserve_reply_rand_used` came from the request or was generated by the server.
+    #[serde(rename = "unobserve_reply_rand_origin", default)]
+    pub unobserve_reply_origin: Option<SeedOrigin>,
+
+    /// Whether `unobserve_last_rand_used` came from the request or was generated by the server.
+    #[serde(rename = "unobserve_last_rand_origin", default)]
+    pub unobserve_last_origin: Option<SeedOrigin>,
+}
+
+/// Where a random number that the engine was given came from.
+///
+/// Only [`Provided`](SeedOrigin::Provided) seeds were chosen by the client,
+/// so a game can be reproduced without looking at its responses
+/// only if all of its seeds were provided.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SeedOrigin {
+    /// The request set this seed.
+    Provided,
+
+    /// The request left this seed out, so the server picked a random one.
+    Generated,
+}
+
+impl SeedOrigin {
+    /// Use the request's seed if it set one, or pick a random one.
+    ///
+    /// ```
+    /// use engine_trait::server_types::SeedOrigin;
+    ///
+    /// assert_eq!(SeedOrigin::resolve(Some(7)), (7, SeedOrigin::Provided));
+    /// assert_eq!(SeedOrigin::resolve(None).1, SeedOrigin::Generated);
+    /// ```
+    pub fn resolve(requested: Option<u64>) -> (u64, SeedOrigin) {
+        match requested {
+            Some(seed) => (seed, SeedOrigin::Provided),
+            None => (rand::random(), SeedOrigin::Generated),
+        }
+    }
+}
+
+/// A single castling right: one side of the board, for one color.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CastlingRight {
+    #[serde(with = "crate::chess_serde::color_serde")]
+    pub color: Color,
+
+    #[serde(with = "crate::chess_serde::castling_side_serde")]
+    pub side: CastlingSide,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -88,22 +1727,125 @@ pub struct EngineResponse<E: Engine> {
     #[serde(with = "crate::chess_serde::position_serde")]
     pub game_after: Chess,
 
+    /// The [`position_key`](crate::position::position_key) of `game_after`, which leaves out the move counters,
+    /// for comparing transpositions. Only set if the request asked for it with `with_position_key`.
+    #[serde(default)]
+    pub position_key: Option<String>,
+
+    /// The [`position_hash`](crate::position::position_hash) of `game_after`,
+    /// to send as `expected_hash_before` in the next request, or to key a cache of positions.
+    /// It is a decimal string on the wire, for clients that cannot hold a `u64` exactly, like JavaScript ones.
+    #[serde(with = "crate::chess_serde::u64_string_serde", default)]
+    pub game_after_hash: u64,
+
+    /// How many legal moves the side to move has in `game_after`, as a rough measure of how open the position is.
+    #[serde(default)]
+    pub legal_move_count: usize,
+
     /// The engine's status info about this move.
     /// It is None if the request asked for no status info.
     pub status_info: Option<E::StatusInfo>,
 
-    /// The random number we gave to the engine when it was observing the previous move.
-    /// None if it did not observe the previous move.
-    pub observe_other_rand_used: Option<u64>,
+    /// Whether the status info was dropped because it was larger than the server allows.
+    #[serde(default)]
+    pub status_info_truncated: bool,
 
-    /// The random number we gave to the engine when it was producing this move.
-    pub produce_rand_used: u64,
+    /// The castling rights that were lost because of the engine's move.
+    #[serde(default)]
+    pub castling_lost: Vec<CastlingRight>,
 
-    /// The random number used to observe the move the engine had made.
-    pub observe_mine_rand_used: u64,
+    /// The random numbers that the engine was given while handling the request.
+    #[serde(flatten)]
+    pub seeds: SeedsUsed,
 
     /// The engine's state. You need to pass this again if you want to continue this game.
-    pub engine_state: E::State,
+    /// It is None if `engine_state_delta` is sent instead; see [`EngineResponse::engine_state_after`].
+    pub engine_state: Option<E::State>,
+
+    /// The change to the request's `engine_state`, if the request asked for a delta and the engine supports it.
+    #[serde(default)]
+    pub engine_state_delta: Option<Value>,
+
+    /// If the game is over, how it ended.
+    /// If it ended with the other side's move, the engine did not move, and `move` is a null move.
+    #[serde(default)]
+    pub outcome: Option<GameOutcome>,
+
+    /// How much extra thinking time the engine would like on its next move, in milliseconds.
+    /// This is advisory only.
+    #[serde(with = "crate::chess_serde::duration_ms_option_serde", default)]
+    pub requested_extra_time: Option<Duration>,
+
+    /// The engine's evaluation of the position it moved in, from its own perspective.
+    /// This is only reported along with status info.
+    #[serde(default)]
+    pub score: Option<Score>,
+
+    /// If the score is a forced mate, the line that leads to it, starting with the engine's move.
+    #[serde(with = "crate::chess_serde::uci_list_serde", default)]
+    pub mate_line: Vec<Uci>,
+
+    /// How clearly the chosen move stood out, from 0 to 1, as judged by the engine.
+    /// This is only reported along with status info.
+    #[serde(default)]
+    pub confidence: Option<f32>,
+
+    /// The engine's chances of winning, drawing and losing, in that order, summing to 1.
+    /// This is only reported along with status info, and only by engines that estimate it.
+    #[serde(default)]
+    pub wdl: Option<(f32, f32, f32)>,
+
+    /// Why the engine chose its move, in standard terms. This is only reported along with status info.
+    #[serde(default)]
+    pub reasons: Vec<MoveReason>,
+
+    /// How many plies deep the engine searched. This is only reported along with status info,
+    /// and only by engines that search to a depth.
+    #[serde(default)]
+    pub depth: Option<u32>,
+
+    /// The moves the engine considered, as a tree, limited to [`SearchTree::MAX_NODES`] nodes.
+    /// This is only reported along with status info, and only by engines that
+    /// [support it](Capabilities::supports_search_tree).
+    #[serde(default)]
+    pub search_tree: Vec<SearchTree>,
+
+    /// The position after each move of the engine's principal variation, starting with its chosen move.
+    /// This is only reported if the request asked for it.
+    #[serde(with = "crate::chess_serde::position_list_serde", default)]
+    pub pv_positions: Vec<Chess>,
+
+    /// Where the engine's move came from. This is only reported along with status info,
+    /// and is [`MoveSource::Search`] otherwise.
+    #[serde(default)]
+    pub move_source: MoveSource,
+
+    /// Whether the server replaced an illegal move from the engine with a random legal one.
+    /// This only happens if the server is configured to; see [`IllegalMovePolicy`](crate::game::IllegalMovePolicy).
+    #[serde(default)]
+    pub substituted: bool,
+
+    /// Whether the engine changed the board after its move, so that `game_after` is not simply the move played.
+    /// See [`Engine::transform_position`].
+    #[serde(default)]
+    pub position_transformed: bool,
+
+    /// The build hash of the engine that produced this response. See [`EngineInfo::build_hash`].
+    #[serde(default)]
+    pub build_hash: Option<String>,
+}
+
+impl<E: Engine> EngineResponse<E> {
+    /// The engine's state after this response, given the `engine_state` that was sent in the request.
+    ///
+    /// Returns None if the response carries a delta that does not apply to `state_before`.
+    pub fn engine_state_after(&self, state_before: &E::State) -> Option<E::State> {
+        match (&self.engine_state, &self.engine_state_delta) {
+            (Some(state), _) => Some(state.clone()),
+            (None, Some(delta)) => E::apply_state_delta(state_before, delta),
+            (None, None) => None,
+        }
+    }
 }
 
 /// Type-erased [`EngineResponse`], where the engine-specific fields have been replaced with [`serde_json::Value`].
@@ -117,33 +1859,311 @@ pub struct AnyEngineResponse {
     #[serde(with = "crate::chess_serde::position_serde")]
     pub game_after: Chess,
 
+    /// The [`position_key`](crate::position::position_key) of `game_after`, which leaves out the move counters,
+    /// for comparing transpositions. Only set if the request asked for it with `with_position_key`.
+    #[serde(default)]
+    pub position_key: Option<String>,
+
+    /// The [`position_hash`](crate::position::position_hash) of `game_after`,
+    /// to send as `expected_hash_before` in the next request, or to key a cache of positions.
+    /// It is a decimal string on the wire, for clients that cannot hold a `u64` exactly, like JavaScript ones.
+    #[serde(with = "crate::chess_serde::u64_string_serde", default)]
+    pub game_after_hash: u64,
+
+    /// How many legal moves the side to move has in `game_after`, as a rough measure of how open the position is.
+    #[serde(default)]
+    pub legal_move_count: usize,
+
     /// The engine's status info about this move.
     /// It is None if the request asked for no status info.
     pub status_info: Option<Value>,
 
-    /// The random number we gave to the engine when it was observing the previous move.
-    /// None if it did not observe the previous move.
-    pub observe_other_rand_used: Option<u64>,
+    /// Whether the status info was dropped because it was larger than the server allows.
+    #[serde(default)]
+    pub status_info_truncated: bool,
 
-    /// The random number we gave to the engine when it was producing this move.
-    pub produce_rand_used: u64,
+    /// The castling rights that were lost because of the engine's move.
+    #[serde(default)]
+    pub castling_lost: Vec<CastlingRight>,
 
-    /// The random number used to observe the move the engine had made.
-    pub observe_mine_rand_used: u64,
+    /// The random numbers that the engine was given while handling the request.
+    #[serde(flatten)]
+    pub seeds: SeedsUsed,
 
     /// The engine's state. You need to pass this again if you want to continue this game.
-    pub engine_state: Value,
+    /// It is None if `engine_state_delta` is sent instead.
+    pub engine_state: Option<Value>,
+
+    /// The change to the request's `engine_state`, if the request asked for a delta and the engine supports it.
+    #[serde(default)]
+    pub engine_state_delta: Option<Value>,
+
+    /// If the game is over, how it ended.
+    /// If it ended with the other side's move, the engine did not move, and `move` is a null move.
+    #[serde(default)]
+    pub outcome: Option<GameOutcome>,
+
+    /// How much extra thinking time the engine would like on its next move, in milliseconds.
+    /// This is advisory only.
+    #[serde(with = "crate::chess_serde::duration_ms_option_serde", default)]
+    pub requested_extra_time: Option<Duration>,
+
+    /// The engine's evaluation of the position it moved in, from its own perspective.
+    /// This is only reported along with status info.
+    #[serde(default)]
+    pub score: Option<Score>,
+
+    /// If the score is a forced mate, the line that leads to it, starting with the engine's move.
+    #[serde(with = "crate::chess_serde::uci_list_serde", default)]
+    pub mate_line: Vec<Uci>,
+
+    /// How clearly the chosen move stood out, from 0 to 1, as judged by the engine.
+    /// This is only reported along with status info.
+    #[serde(default)]
+    pub confidence: Option<f32>,
+
+    /// The engine's chances of winning, drawing and losing, in that order, summing to 1.
+    /// This is only reported along with status info, and only by engines that estimate it.
+    #[serde(default)]
+    pub wdl: Option<(f32, f32, f32)>,
+
+    /// Why the engine chose its move, in standard terms. This is only reported along with status info.
+    #[serde(default)]
+    pub reasons: Vec<MoveReason>,
+
+    /// How many plies deep the engine searched. This is only reported along with status info,
+    /// and only by engines that search to a depth.
+    #[serde(default)]
+    pub depth: Option<u32>,
+
+    /// The moves the engine considered, as a tree, limited to [`SearchTree::MAX_NODES`] nodes.
+    /// This is only reported along with status info, and only by engines that
+    /// [support it](Capabilities::supports_search_tree).
+    #[serde(default)]
+    pub search_tree: Vec<SearchTree>,
+
+    /// The position after each move of the engine's principal variation, starting with its chosen move.
+    /// This is only reported if the request asked for it.
+    #[serde(with = "crate::chess_serde::position_list_serde", default)]
+    pub pv_positions: Vec<Chess>,
+
+    /// Where the engine's move came from. This is only reported along with status info,
+    /// and is [`MoveSource::Search`] otherwise.
+    #[serde(default)]
+    pub move_source: MoveSource,
+
+    /// Whether the server replaced an illegal move from the engine with a random legal one.
+    /// This only happens if the server is configured to; see [`IllegalMovePolicy`](crate::game::IllegalMovePolicy).
+    #[serde(default)]
+    pub substituted: bool,
+
+    /// Whether the engine changed the board after its move, so that `game_after` is not simply the move played.
+    /// See [`Engine::transform_position`].
+    #[serde(default)]
+    pub position_transformed: bool,
+
+    /// The build hash of the engine that produced this response. See [`EngineInfo::build_hash`].
+    #[serde(default)]
+    pub build_hash: Option<String>,
 }
 
-#[derive(Clone, Debug)]
+/// The result of a turn.
+///
+/// Over HTTP, the variants are told apart by status code.
+/// For other transports, such as message queues, this serializes to a single JSON object,
+/// tagged by `result` (`"ok"`, `"request_error"` or `"engine_error"`), with the payload under `value`.
+/// This needs the engine's error type to be serializable; [`AnyEngineResult`] has the same representation.
+///
+/// ```
+/// use engine_trait::server_types::{AnyEngineResult, EngineRequestError, EngineResult};
+/// # use engine_trait::fixtures::FirstMove;
+///
+/// let result: EngineResult<FirstMove> = EngineResult::RequestError(EngineRequestError::InvalidDrawClaim);
+/// let json = serde_json::to_value(&result).unwrap();
+/// assert_eq!(json["result"], "request_error");
+///
+/// let any: AnyEngineResult = serde_json::from_value(json).unwrap();
+/// assert!(matches!(any, AnyEngineResult::RequestError(EngineRequestError::InvalidDrawClaim)));
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(
+    tag = "result",
+    content = "value",
+    rename_all = "snake_case",
+    bound(
+        serialize = "E::Error: Serialize",
+        deserialize = "E::Error: Deserialize<'de>"
+    )
+)]
+#[allow(clippy::large_enum_variant)]
 pub enum EngineResult<E: Engine> {
     RequestError(EngineRequestError),
     EngineError(E::Error),
     Ok(EngineResponse<E>),
 }
 
+/// The engine's scores over a game, as `(ply, score)` pairs, for drawing an evaluation graph.
+///
+/// The ply is that of the engine's move, counting from 1 at White's first move of the game,
+/// and the score is converted to White's perspective, so that the series is continuous across both sides.
+/// Responses without a score, such as those requested without status info, are left out.
+pub fn eval_curve(responses: &[AnyEngineResponse]) -> Vec<(usize, Score)> {
+    responses
+        .iter()
+        .filter(|response| response.r#move != Uci::Null)
+        .filter_map(|response| {
+            let position = &response.game_after;
+            let ply = (position.fullmoves().get() as usize - 1) * 2
+                + (position.turn() == Color::Black) as usize;
+            // The engine scored the position it moved in, so the side to move was the one that is not to move now.
+            let score = response.score?.from_white_perspective(!position.turn());
+            Some((ply, score))
+        })
+        .collect()
+}
+
+/// Ways in which a sequence of responses fails to be one game. See [`verify_game`].
+///
+/// Indices count responses from 0.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyError {
+    /// The response's `game_after_hash` is not the hash of its `game_after`.
+    HashMismatch { index: usize },
+
+    /// The response's move is not legal in the position it must have been played in.
+    IllegalMove {
+        index: usize,
+        #[serde(with = "crate::chess_serde::uci_serde")]
+        r#move: Uci,
+    },
+
+    /// The response's `game_after` does not follow from the previous response,
+    /// by the response's move, after one move of the other side for [`GameSides::OneSide`].
+    Discontinuity { index: usize },
+
+    /// The previous response ended the game, but this one continues it.
+    PlayedAfterGameOver { index: usize },
+}
+
+/// Whose responses are given to [`verify_game`], which decides whether a move of the other side may come between two of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameSides {
+    /// The responses of two engines taking turns: each response's move is played right after the previous one.
+    Alternating,
+
+    /// The responses of one engine playing one side: exactly one move of the other side,
+    /// which is not among the responses, comes before each response's move.
+    OneSide,
+}
+
+/// Check that `responses` make up one game: every `game_after` follows legally from the previous one,
+/// through its stated move, and, for [`GameSides::OneSide`], one move of the other side before it.
+///
+/// The first response can only be checked for consistent hashes, since the position it was played in is unknown,
+/// and so can responses whose engine changed the board after its move.
+///
+/// ```
+/// use engine_trait::server_types::{verify_game, AnyEngineResponse, GameSides, VerifyError};
+///
+/// let response = |uci: &str, fen: &str| -> AnyEngineResponse {
+///     serde_json::from_value(serde_json::json!({
+///         "move": uci,
+///         "game_after": fen,
+///         "engine_state": null,
+///     }))
+///     .unwrap()
+/// };
+/// let e4 = response("e2e4", "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+/// let e5 = response("e7e5", "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+/// let nf3 = response("g1f3", "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+/// let both = [e4.clone(), e5.clone(), nf3.clone()];
+/// assert_eq!(verify_game(&both, GameSides::Alternating), Ok(()));
+/// // One engine playing White only sees its own moves; Black's e5 came in between.
+/// let white = [e4.clone(), nf3.clone()];
+/// assert_eq!(verify_game(&white, GameSides::OneSide), Ok(()));
+/// // Without saying so, a response cannot skip the other side's move, nor can one engine play both sides.
+/// assert!(matches!(
+///     verify_game(&white, GameSides::Alternating),
+///     Err(VerifyError::IllegalMove { index: 1, .. }),
+/// ));
+/// assert!(verify_game(&both, GameSides::OneSide).is_err());
+///
+/// let teleport = response("g1f3", "rnbqkbnr/pppp1ppp/8/4p3/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 1 2");
+/// assert_eq!(
+///     verify_game(&[e4.clone(), e5, teleport], GameSides::Alternating),
+///     Err(VerifyError::Discontinuity { index: 2 }),
+/// );
+/// let illegal = response("e4e6", "rnbqkbnr/pppp1ppp/4P3/4p3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2");
+/// assert!(matches!(
+///     verify_game(&[e4, illegal], GameSides::OneSide),
+///     Err(VerifyError::IllegalMove { index: 1, .. }),
+/// ));
+/// ```
+pub fn verify_game(responses: &[AnyEngineResponse], sides: GameSides) -> Result<(), VerifyError> {
+    for (index, response) in responses.iter().enumerate() {
+        // Hashes that were left out deserialize to 0, and are not checked.
+        if response.game_after_hash != 0
+            && response.game_after_hash != position_hash(&response.game_after)
+        {
+            return Err(VerifyError::HashMismatch { index });
+        }
+        let Some(previous) = index.checked_sub(1).map(|i| &responses[i]) else {
+            continue;
+        };
+        if previous.outcome.is_some() {
+            return Err(VerifyError::PlayedAfterGameOver { index });
+        }
+        // A transformed board does not follow from any move.
+        if response.position_transformed {
+            continue;
+        }
+
+        let starts = match sides {
+            GameSides::Alternating => vec![previous.game_after.clone()],
+            GameSides::OneSide => previous
+                .game_after
+                .legal_moves()
+                .iter()
+                .map(|m| {
+                    let mut start = previous.game_after.clone();
+                    start.play_unchecked(m);
+                    start
+                })
+                .collect(),
+        };
+        let mut legal = false;
+        let mut follows = false;
+        for mut after in starts {
+            if response.r#move != Uci::Null {
+                let Ok(m) = response.r#move.to_move(&after) else {
+                    continue;
+                };
+                after.play_unchecked(&m);
+            }
+            legal = true;
+            if after == response.game_after {
+                follows = true;
+                break;
+            }
+        }
+        if !legal {
+            return Err(VerifyError::IllegalMove {
+                index,
+                r#move: response.r#move.clone(),
+            });
+        }
+        if !follows {
+            return Err(VerifyError::Discontinuity { index });
+        }
+    }
+    Ok(())
+}
+
 /// Type-erased [`EngineResult`], where the engine-specific fields have been replaced with [`serde_json::Value`].
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "result", content = "value", rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
 pub enum AnyEngineResult {
     RequestError(EngineRequestError),
     EngineError(Value),
@@ -155,6 +2175,24 @@ pub struct EngineInternalError {
     pub error_text: String,
 }
 
+#[cfg(feature = "server")]
+impl IntoResponse for EngineRequestError {
+    fn into_response(self) -> axum::response::Response {
+        let status = match self {
+            EngineRequestError::Unsupported { .. } => StatusCode::NOT_IMPLEMENTED,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
+#[cfg(feature = "server")]
+impl IntoResponse for EngineInternalError {
+    fn into_response(self) -> axum::response::Response {
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(self)).into_response()
+    }
+}
+
 #[cfg(feature = "server")]
 impl<E> IntoResponse for EngineResult<E>
 where
@@ -162,16 +2200,11 @@ where
 {
     fn into_response(self) -> axum::response::Response {
         match self {
-            EngineResult::RequestError(what) => {
-                (StatusCode::BAD_REQUEST, Json(what)).into_response()
+            EngineResult::RequestError(what) => what.into_response(),
+            EngineResult::EngineError(what) => EngineInternalError {
+                error_text: what.to_string(),
             }
-            EngineResult::EngineError(what) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(EngineInternalError {
-                    error_text: what.to_string(),
-                }),
-            )
-                .into_response(),
+            .into_response(),
             EngineResult::Ok(what) => (StatusCode::OK, Json(what)).into_response(),
         }
     }
@@ -0,0 +1,147 @@
+//! Probing Syzygy endgame tablebases, for engines that want to play endgames perfectly.
+//!
+//! Engines that play a move found here should report [`MoveSource::Tablebase`](crate::server_types::MoveSource)
+//! from [`Engine::summarize`](crate::Engine::summarize), so that clients can tell it apart from a searched move.
+
+use std::{io, path::Path};
+
+use shakmaty::{Chess, Move, Position};
+use shakmaty_syzygy::{MaybeRounded, SyzygyError};
+
+use crate::server_types::TablebaseWdl;
+
+pub use shakmaty_syzygy::Wdl;
+
+/// A directory of Syzygy tables, opened once and probed for many positions.
+///
+/// Table files are only read when a position with their material is probed.
+#[derive(Debug)]
+pub struct Tablebase {
+    tables: shakmaty_syzygy::Tablebase<Chess>,
+}
+
+impl Tablebase {
+    /// Open all the tables in the directory `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Tablebase> {
+        let mut tables = shakmaty_syzygy::Tablebase::new();
+        tables.add_directory(path)?;
+        Ok(Tablebase { tables })
+    }
+
+    /// The most pieces that any of the tables covers, kings included.
+    pub fn max_pieces(&self) -> usize {
+        self.tables.max_pieces()
+    }
+
+    /// The best move in `position`, and the result that it leads to for the side to move,
+    /// assuming that the position was reached by a capture or pawn move.
+    ///
+    /// Returns None if the game is over, there are too many pieces, or the tables for the position are missing.
+    pub fn probe_best(&self, position: &Chess) -> Option<(Move, Wdl)> {
+        if position.board().occupied().count() > self.max_pieces() {
+            return None;
+        }
+        let (m, dtz) = self.tables.best_move(position).ok()??;
+        Some((m, Wdl::from_dtz_after_zeroing(dtz)))
+    }
+
+    /// The result of `position` for the side to move, and how far away the next capture or pawn move is,
+    /// for endgame training tools that show both.
+    ///
+    /// ```
+    /// use engine_trait::{shakmaty::Chess, syzygy::{ProbeError, Tablebase}};
+    ///
+    /// // A directory without any tables covers no positions at all.
+    /// let tablebase = Tablebase::open(std::env::temp_dir()).unwrap();
+    /// assert!(matches!(
+    ///     tablebase.probe(&Chess::new()),
+    ///     Err(ProbeError::TooManyPieces { pieces: 32, max: 0 })
+    /// ));
+    /// ```
+    pub fn probe(&self, position: &Chess) -> Result<Probe, ProbeError> {
+        let pieces = position.board().occupied().count();
+        if pieces > self.max_pieces() {
+            return Err(ProbeError::TooManyPieces {
+                pieces,
+                max: self.max_pieces(),
+            });
+        }
+        let wdl = self.tables.probe_wdl_after_zeroing(position)?;
+        let dtz = self.tables.probe_dtz(position)?;
+        Ok(Probe {
+            wdl,
+            dtz: dtz.ignore_rounding().0,
+            dtz_rounded: matches!(dtz, MaybeRounded::Rounded(_)),
+        })
+    }
+}
+
+/// What the tables say about a position, for the side to move. See [`Tablebase::probe`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Probe {
+    /// The result with best play, assuming that the position was reached by a capture or pawn move.
+    pub wdl: Wdl,
+
+    /// The plies until the next capture or pawn move with best play: positive when winning,
+    /// negative when losing, and zero when drawn.
+    pub dtz: i32,
+
+    /// Set if `dtz` may be one ply more than the truth, since the tables store some distances rounded.
+    pub dtz_rounded: bool,
+}
+
+/// Why a position could not be looked up. See [`Tablebase::probe`].
+#[derive(Debug)]
+pub enum ProbeError {
+    /// The position has more pieces than any of the tables cover.
+    TooManyPieces { pieces: usize, max: usize },
+
+    /// The position is not in the tables, for example because it has castling rights or a table is missing,
+    /// or a table could not be read.
+    Failed(SyzygyError),
+}
+
+impl From<SyzygyError> for ProbeError {
+    fn from(error: SyzygyError) -> Self {
+        ProbeError::Failed(error)
+    }
+}
+
+impl std::fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProbeError::TooManyPieces { pieces, max } => {
+                write!(f, "{pieces} pieces, but the tables cover at most {max}")
+            }
+            ProbeError::Failed(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ProbeError {}
+
+impl From<Wdl> for TablebaseWdl {
+    fn from(wdl: Wdl) -> Self {
+        match wdl {
+            Wdl::Loss => TablebaseWdl::Loss,
+            Wdl::BlessedLoss => TablebaseWdl::BlessedLoss,
+            Wdl::Draw => TablebaseWdl::Draw,
+            Wdl::CursedWin => TablebaseWdl::CursedWin,
+            Wdl::Win => TablebaseWdl::Win,
+        }
+    }
+}
+
+/// Open the tables in the directory `tb_path`, and look up the best move in `position`.
+///
+/// This opens the tables again on every call; engines that probe often should keep a [`Tablebase`] instead.
+///
+/// ```
+/// use engine_trait::{shakmaty::Chess, syzygy::probe_best};
+///
+/// // There are no tables for the start position, or anywhere in a missing directory.
+/// assert_eq!(probe_best(&Chess::new(), "no-such-directory"), None);
+/// ```
+pub fn probe_best(position: &Chess, tb_path: impl AsRef<Path>) -> Option<(Move, Wdl)> {
+    Tablebase::open(tb_path).ok()?.probe_best(position)
+}
@@ -0,0 +1,122 @@
+//! Helpers for testing clients without a real engine.
+//!
+//! ```
+//! use engine_trait::{server_types::Score, test_support::AnyEngineResponseBuilder};
+//!
+//! let response = AnyEngineResponseBuilder::new(
+//!     "e7e5",
+//!     "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+//! )
+//! .engine_state(serde_json::json!({ "depth": 3 }))
+//! .score(Score::Centipawns(-20))
+//! .build();
+//! assert_eq!(response.r#move.to_string(), "e7e5");
+//! assert_eq!(response.engine_state.unwrap()["depth"], 3);
+//! // White has 14 pawn moves, five knight moves, four queen moves, five bishop moves and a king move.
+//! assert_eq!(response.legal_move_count, 29);
+//! ```
+
+use std::str::FromStr;
+
+use serde_json::Value;
+use shakmaty::{fen::Fen, uci::Uci, CastlingMode, Chess, Position};
+
+use crate::{
+    position::position_hash,
+    server_types::{AnyEngineResponse, GameOutcome, MoveSource, Score, SeedsUsed},
+};
+
+/// Builds an [`AnyEngineResponse`] from plain values.
+///
+/// Everything that is not set explicitly is empty, as if the engine reported nothing extra.
+/// Invalid moves or FENs panic, since this is only meant for tests.
+#[derive(Clone, Debug)]
+pub struct AnyEngineResponseBuilder {
+    response: AnyEngineResponse,
+}
+
+impl AnyEngineResponseBuilder {
+    /// A response in which the engine played `uci`, reaching the position `fen`.
+    pub fn new(uci: &str, fen: &str) -> AnyEngineResponseBuilder {
+        let r#move = Uci::from_str(uci).expect("the move is valid UCI");
+        let fen = Fen::from_str(fen).expect("the FEN is valid");
+        let mode = CastlingMode::detect(fen.as_setup());
+        let game_after: Chess = fen
+            .into_position(mode)
+            .expect("the FEN is a legal position");
+        AnyEngineResponseBuilder {
+            response: AnyEngineResponse {
+                r#move,
+                game_after_hash: position_hash(&game_after),
+                legal_move_count: game_after.legal_moves().len(),
+                game_after,
+                position_key: None,
+                status_info: None,
+                status_info_truncated: false,
+                castling_lost: Vec::new(),
+                seeds: SeedsUsed {
+                    observe_other: None,
+                    produce: None,
+                    observe_own: None,
+                    observe_other_origin: None,
+                    produce_origin: None,
+                    observe_own_origin: None,
+                    unobserve_reply: None,
+                    unobserve_last: None,
+                    unobserve_reply_origin: None,
+                    unobserve_last_origin: None,
+                },
+                engine_state: Some(Value::Null),
+                engine_state_delta: None,
+                outcome: None,
+                requested_extra_time: None,
+                score: None,
+                mate_line: Vec::new(),
+                confidence: None,
+                wdl: None,
+                reasons: Vec::new(),
+                search_tree: Vec::new(),
+                depth: None,
+                move_source: MoveSource::default(),
+                substituted: false,
+                position_transformed: false,
+                pv_positions: Vec::new(),
+                build_hash: None,
+            },
+        }
+    }
+
+    pub fn engine_state(mut self, state: Value) -> Self {
+        self.response.engine_state = Some(state);
+        self
+    }
+
+    pub fn status_info(mut self, info: Value) -> Self {
+        self.response.status_info = Some(info);
+        self
+    }
+
+    pub fn seeds(mut self, seeds: SeedsUsed) -> Self {
+        self.response.seeds = seeds;
+        self
+    }
+
+    pub fn outcome(mut self, outcome: GameOutcome) -> Self {
+        self.response.outcome = Some(outcome);
+        self
+    }
+
+    pub fn score(mut self, score: Score) -> Self {
+        self.response.score = Some(score);
+        self
+    }
+
+    pub fn move_source(mut self, source: MoveSource) -> Self {
+        self.response.move_source = source;
+        self
+    }
+
+    pub fn build(self) -> AnyEngineResponse {
+        self.response
+    }
+}